@@ -1,5 +1,37 @@
-use bytes::{BytesMut, BufMut, Buf};
+pub mod varint;
+pub mod schema;
+
+pub use varint::{VarInt, VarLong, ZigZagInt, ZigZagLong};
+
+/// Used by the derives, so crates deriving `BukrsType` don't need their own proptest dependency
+#[cfg(feature = "proptest")]
+pub use proptest;
+
+/// Keeps the items if the `proptest` feature is on. The derives wrap their `Arbitrary` impls in this,
+/// so whether they exist depends on this crate's features and not on the deriving crate's.
+#[cfg(feature = "proptest")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_proptest {
+    ($($item:item)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "proptest"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_proptest {
+    ($($item:item)*) => {};
+}
+
+use std::{collections::{HashMap, BTreeMap}, hash::Hash, fmt::Display};
+
+use bytes::{Bytes, BytesMut, BufMut, Buf};
+use schema::{TypeSchema, PacketSchema};
+use serde::Serialize;
+use uuid::Uuid;
+use varint::VarIntError;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum BukrsNativeType {
     U8,
     U16,
@@ -13,16 +45,69 @@ pub enum BukrsNativeType {
     F64,
     VECTOR,
     STRING,
+    BOOL,
+    OPTION,
+    MAP,
+    TUPLE,
+    ARRAY,
+    BYTES,
+    UUID,
+    VARINT,
+    VARLONG,
+    ZIGZAGINT,
+    ZIGZAGLONG,
     CUSTOM
 }
 
+/// Why bytes couldn't be decoded. Frames come from the network, so every `decode` returns this instead of panicking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before the value did
+    Truncated,
+    /// A varint ran on past 5 bytes, or a varlong past 10
+    VarIntTooBig,
+    /// The bytes don't stand for a value of the type, with the reason
+    Invalid(String)
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "Input ended early"),
+            DecodeError::VarIntTooBig => write!(f, "Varint too big"),
+            DecodeError::Invalid(reason) => write!(f, "{}", reason)
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<VarIntError> for DecodeError {
+    fn from(error: VarIntError) -> Self {
+        match error {
+            VarIntError::TooBig => DecodeError::VarIntTooBig,
+            VarIntError::Truncated => DecodeError::Truncated
+        }
+    }
+}
+
+/// Fails unless `len` more bytes are left, for reads that would otherwise panic
+pub fn ensure_remaining(bytes: &BytesMut, len: usize) -> Result<(), DecodeError> {
+    if bytes.remaining() < len {
+        return Err(DecodeError::Truncated);
+    }
+    Ok(())
+}
+
 pub trait BukrsPacket {
     fn id(&self) -> String;
     fn encode(&self, bytes: &mut BytesMut);
+
+    fn schema() -> PacketSchema where Self: Sized;
 }
 
 pub trait BukrsDecodable {
-    fn decode(bytes: &mut BytesMut) -> Self;
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> where Self: Sized;
 }
 
 pub trait BukrsType {
@@ -30,7 +115,12 @@ pub trait BukrsType {
 
     fn encode(&self, bytes: &mut BytesMut);
 
-    fn decode(bytes: &mut BytesMut) -> Self;
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> where Self: Sized;
+
+    /// Layout on the wire. Hand-written impls are only known by name unless they override this.
+    fn schema() -> TypeSchema where Self: Sized {
+        TypeSchema::custom::<Self>()
+    }
 }
 
 
@@ -39,12 +129,17 @@ impl BukrsType for u8 {
         BukrsNativeType::U8
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::U8)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u8(self.clone());
+        bytes.put_u8(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_u8()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 1)?;
+        Ok(bytes.get_u8())
     }
 }
 
@@ -53,12 +148,17 @@ impl BukrsType for u16 {
         BukrsNativeType::U16
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::U16)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u16(self.clone());
+        bytes.put_u16(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_u16()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 2)?;
+        Ok(bytes.get_u16())
     }
 }
 
@@ -67,12 +167,17 @@ impl BukrsType for u32 {
         BukrsNativeType::U32
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::U32)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u32(self.clone());
+        bytes.put_u32(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_u32()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 4)?;
+        Ok(bytes.get_u32())
     }
 }
 
@@ -81,12 +186,17 @@ impl BukrsType for u64 {
         BukrsNativeType::U64
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::U64)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u64(self.clone());
+        bytes.put_u64(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_u64()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 8)?;
+        Ok(bytes.get_u64())
     }
 }
 
@@ -95,12 +205,17 @@ impl BukrsType for i8 {
         BukrsNativeType::I8
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::I8)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_i8(self.clone());
+        bytes.put_i8(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_i8()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 1)?;
+        Ok(bytes.get_i8())
     }
 }
 
@@ -109,12 +224,17 @@ impl BukrsType for i16 {
         BukrsNativeType::I16
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::I16)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_i16(self.clone());
+        bytes.put_i16(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_i16()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 2)?;
+        Ok(bytes.get_i16())
     }
 }
 
@@ -123,12 +243,17 @@ impl BukrsType for i32 {
         BukrsNativeType::I32
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::I32)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_i32(self.clone());
+        bytes.put_i32(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_i32()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 4)?;
+        Ok(bytes.get_i32())
     }
 }
 
@@ -137,12 +262,17 @@ impl BukrsType for i64 {
         BukrsNativeType::I64
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::I64)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_i64(self.clone());
+        bytes.put_i64(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_i64()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 8)?;
+        Ok(bytes.get_i64())
     }
 }
 
@@ -151,12 +281,17 @@ impl BukrsType for f32 {
         BukrsNativeType::F32
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::F32)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_f32(self.clone());
+        bytes.put_f32(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_f32()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 4)?;
+        Ok(bytes.get_f32())
     }
 }
 
@@ -165,12 +300,17 @@ impl BukrsType for f64 {
         BukrsNativeType::F64
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::F64)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_f64(self.clone());
+        bytes.put_f64(*self);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        bytes.get_f64()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        ensure_remaining(bytes, 8)?;
+        Ok(bytes.get_f64())
     }
 }
 
@@ -179,16 +319,20 @@ impl BukrsType for String {
         BukrsNativeType::STRING
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::STRING)
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
         let str_bytes = self.as_bytes();
-        bytes.put_u32(str_bytes.len() as u32);
+        varint::write_length(str_bytes.len(), bytes);
         bytes.put(str_bytes);
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        let len = bytes.get_u32() as usize;
-        let str_bytes = (0..len).map(|_|bytes.get_u8()).collect::<Vec<u8>>();
-        String::from_utf8(str_bytes).unwrap()
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let len = varint::read_length(bytes)?;
+        ensure_remaining(bytes, len)?;
+        String::from_utf8(bytes.split_to(len).to_vec()).map_err(|error| DecodeError::Invalid(error.to_string()))
     }
 }
 
@@ -197,20 +341,292 @@ impl <T> BukrsType for Vec<T> where T: BukrsType {
         BukrsNativeType::VECTOR
     }
 
+    fn schema() -> TypeSchema {
+        TypeSchema::Vector { item: Box::new(T::schema()) }
+    }
+
     fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u32(self.len() as u32);
+        varint::write_length(self.len(), bytes);
         for item in self.iter() {
             item.encode(bytes);
         }
     }
 
-    fn decode(bytes: &mut BytesMut) -> Self {
-        let size = bytes.get_u32();
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let size = varint::read_length(bytes)?;
         let mut vec = vec![];
         for _ in 0..size {
-            let item = T::decode(bytes);
+            let item = T::decode(bytes)?;
             vec.push(item);
         }
-        vec
+        Ok(vec)
+    }
+}
+
+/// A single byte, 0 or 1. Any other non-zero byte also reads as true.
+impl BukrsType for bool {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::BOOL
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::BOOL)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        bytes.put_u8(*self as u8);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(u8::decode(bytes)? != 0)
+    }
+}
+
+/// A presence byte like `bool`, followed by the value if present
+impl <T> BukrsType for Option<T> where T: BukrsType {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::OPTION
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Option { item: Box::new(T::schema()) }
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.is_some().encode(bytes);
+        if let Some(value) = self {
+            value.encode(bytes);
+        }
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        if bool::decode(bytes)? {
+            Ok(Some(T::decode(bytes)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn encode_entries<'a, K: BukrsType + 'a, V: BukrsType + 'a>(len: usize, entries: impl Iterator<Item = (&'a K, &'a V)>, bytes: &mut BytesMut) {
+    varint::write_length(len, bytes);
+    for (key, value) in entries {
+        key.encode(bytes);
+        value.encode(bytes);
+    }
+}
+
+/// Inserts one entry at a time, as reserving the sent count up front lets a bad length abort the process
+fn decode_entries<K: BukrsType, V: BukrsType, M: Default + Extend<(K, V)>>(bytes: &mut BytesMut) -> Result<M, DecodeError> {
+    let size = varint::read_length(bytes)?;
+    let mut entries = M::default();
+    for _ in 0..size {
+        entries.extend([(K::decode(bytes)?, V::decode(bytes)?)]);
+    }
+    Ok(entries)
+}
+
+/// The entry count as a varint, then each key followed by its value, in the map's iteration order
+impl <K, V> BukrsType for HashMap<K, V> where K: BukrsType + Eq + Hash, V: BukrsType {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::MAP
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Map { key: Box::new(K::schema()), value: Box::new(V::schema()) }
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        encode_entries(self.len(), self.iter(), bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        decode_entries(bytes)
+    }
+}
+
+/// Same as `HashMap`, with the keys in ascending order
+impl <K, V> BukrsType for BTreeMap<K, V> where K: BukrsType + Ord, V: BukrsType {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::MAP
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Map { key: Box::new(K::schema()), value: Box::new(V::schema()) }
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        encode_entries(self.len(), self.iter(), bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        decode_entries(bytes)
+    }
+}
+
+/// Tuples are their elements in order, with nothing in between
+macro_rules! tuple_impl {
+    ($($name:ident),+) => {
+        impl <$($name),+> BukrsType for ($($name,)+) where $($name: BukrsType),+ {
+            fn ty(&self) -> BukrsNativeType {
+                BukrsNativeType::TUPLE
+            }
+
+            fn schema() -> TypeSchema {
+                TypeSchema::Tuple { items: vec![$($name::schema()),+] }
+            }
+
+            #[allow(non_snake_case)]
+            fn encode(&self, bytes: &mut BytesMut) {
+                let ($($name,)+) = self;
+                $($name.encode(bytes);)+
+            }
+
+            fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+                Ok(($($name::decode(bytes)?,)+))
+            }
+        }
+    };
+}
+
+tuple_impl!(A);
+tuple_impl!(A, B);
+tuple_impl!(A, B, C);
+tuple_impl!(A, B, C, D);
+tuple_impl!(A, B, C, D, E);
+tuple_impl!(A, B, C, D, E, F);
+
+/// The elements in order. The length is part of the type, so unlike `Vec` it isn't written.
+impl <T, const N: usize> BukrsType for [T; N] where T: BukrsType {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::ARRAY
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Array { item: Box::new(T::schema()), len: N }
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        for item in self.iter() {
+            item.encode(bytes);
+        }
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let items = (0..N).map(|_| T::decode(bytes)).collect::<Result<Vec<T>, DecodeError>>()?;
+        match items.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("Collected exactly {} items", N)
+        }
+    }
+}
+
+/// A byte blob, written like `Vec<u8>` but copied in one go
+impl BukrsType for Bytes {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::BYTES
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::BYTES)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        varint::write_length(self.len(), bytes);
+        bytes.put_slice(self);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let len = varint::read_length(bytes)?;
+        ensure_remaining(bytes, len)?;
+        Ok(bytes.split_to(len).freeze())
+    }
+}
+
+/// The least significant 64 bits, then the most significant, both as u64 like Java's `UUID`
+impl BukrsType for Uuid {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::UUID
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::UUID)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        let (msb, lsb) = self.as_u64_pair();
+        bytes.put_u64(lsb);
+        bytes.put_u64(msb);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let lsb = u64::decode(bytes)?;
+        let msb = u64::decode(bytes)?;
+        Ok(Uuid::from_u64_pair(msb, lsb))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::{HashMap, BTreeMap}, fmt::Debug};
+
+    use bytes::{Bytes, BytesMut};
+    use uuid::Uuid;
+
+    use super::{BukrsType, DecodeError};
+
+    fn round_trip<T: BukrsType + PartialEq + Debug>(value: T) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        value.encode(&mut bytes);
+        let encoded = bytes.clone();
+        assert_eq!(T::decode(&mut bytes).unwrap(), value);
+        assert!(bytes.is_empty(), "{:?} left bytes behind", value);
+        encoded
+    }
+
+    #[test]
+    fn test_round_trips() {
+        assert_eq!(&round_trip(true)[..], [1]);
+        assert_eq!(&round_trip(Some(7u16))[..], [1, 0, 7]);
+        assert_eq!(&round_trip(None::<u16>)[..], [0]);
+        assert_eq!(&round_trip((1u8, -1i16, "a".to_string()))[..], [1, 0xff, 0xff, 1, b'a']);
+        assert_eq!(&round_trip([3u8, 4, 5])[..], [3, 4, 5]);
+        assert_eq!(round_trip(Bytes::from_static(&[9, 8, 7])), round_trip(vec![9u8, 8, 7]));
+
+        let slots = HashMap::from([(0u8, "sword".to_string()), (8, "bread".to_string())]);
+        round_trip(slots);
+        let ordered = BTreeMap::from([(2u32, vec![Some(true)]), (1, vec![None])]);
+        assert_eq!(&round_trip(ordered)[..7], [2, 0, 0, 0, 1, 1, 0]);
+
+        let uuid = Uuid::from_u128(0x0123456789abcdef_fedcba9876543210);
+        assert_eq!(&round_trip(uuid)[..], [0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+    }
+
+    /// Each length says 2^32 - 1 but the input ends right after it, which must fail rather than allocate
+    #[test]
+    fn test_huge_lengths_fail() {
+        let huge = || BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0x0f][..]);
+        assert_eq!(BTreeMap::<String, String>::decode(&mut huge()), Err(DecodeError::Truncated));
+        assert_eq!(HashMap::<u8, u8>::decode(&mut huge()), Err(DecodeError::Truncated));
+        assert_eq!(Vec::<u8>::decode(&mut huge()), Err(DecodeError::Truncated));
+        assert_eq!(String::decode(&mut huge()), Err(DecodeError::Truncated));
+        assert_eq!(Bytes::decode(&mut huge()), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn test_bad_input_fails() {
+        assert_eq!(u32::decode(&mut BytesMut::from(&[0, 0, 1][..])), Err(DecodeError::Truncated));
+        assert_eq!(Uuid::decode(&mut BytesMut::from(&[0; 15][..])), Err(DecodeError::Truncated));
+        assert_eq!(<[u16; 2]>::decode(&mut BytesMut::from(&[0, 1, 0][..])), Err(DecodeError::Truncated));
+        assert_eq!(Vec::<u8>::decode(&mut BytesMut::from(&[0x80][..])), Err(DecodeError::Truncated));
+        assert_eq!(Vec::<u8>::decode(&mut BytesMut::from(&[0xff; 5][..])), Err(DecodeError::VarIntTooBig));
+        assert!(matches!(String::decode(&mut BytesMut::from(&[1, 0xff][..])), Err(DecodeError::Invalid(_))));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_any_round_trips(value: (u8, Option<String>, Vec<i64>, BTreeMap<u16, bool>, [i32; 3], (u32, u64))) {
+            round_trip(value);
+        }
     }
-}
\ No newline at end of file
+}
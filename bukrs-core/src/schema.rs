@@ -0,0 +1,79 @@
+//! Layout of types and packets on the wire, to generate and check bindings in other languages
+
+use std::cell::RefCell;
+
+use serde::Serialize;
+
+use crate::BukrsNativeType;
+
+/// How a type is written on the wire
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeSchema {
+    /// A type with a fixed encoding, like `U32`, `STRING` or `UUID`
+    Native { ty: BukrsNativeType },
+    Vector { item: Box<TypeSchema> },
+    Option { item: Box<TypeSchema> },
+    Map { key: Box<TypeSchema>, value: Box<TypeSchema> },
+    Tuple { items: Vec<TypeSchema> },
+    Array { item: Box<TypeSchema>, len: usize },
+    /// Derived with `BukrsType`, the fields in order
+    Struct { name: String, fields: Vec<FieldSchema> },
+    /// Derived with `BukrsType`, a varint discriminant followed by the variant's fields
+    Enum { name: String, variants: Vec<VariantSchema> },
+    /// Any registered packet, written as its name followed by its fields
+    Packet,
+    /// A derived type inside its own fields, named instead of expanded again
+    Recursive { name: String },
+    /// A hand-written encoding, only known by name
+    Custom { name: String }
+}
+
+impl TypeSchema {
+    pub fn native(ty: BukrsNativeType) -> TypeSchema {
+        TypeSchema::Native { ty }
+    }
+
+    /// Names the type without its module path
+    pub fn custom<T: ?Sized>() -> TypeSchema {
+        let name = std::any::type_name::<T>();
+        TypeSchema::Custom { name: name.rsplit("::").next().unwrap_or(name).to_string() }
+    }
+}
+
+thread_local! {
+    /// Names of the derived types whose schema is being built on this thread
+    static EXPANDING: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Schema of the derived type `name`, built by `build` unless `name` contains itself
+pub fn derived(name: &str, build: impl FnOnce() -> TypeSchema) -> TypeSchema {
+    if EXPANDING.with(|expanding| expanding.borrow().iter().any(|expanding| expanding == name)) {
+        return TypeSchema::Recursive { name: name.to_string() };
+    }
+    EXPANDING.with(|expanding| expanding.borrow_mut().push(name.to_string()));
+    let schema = build();
+    EXPANDING.with(|expanding| expanding.borrow_mut().pop());
+    schema
+}
+
+/// Unnamed fields are named by their index
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: TypeSchema
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VariantSchema {
+    pub name: String,
+    pub discriminant: i32,
+    pub fields: Vec<FieldSchema>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PacketSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>
+}
@@ -0,0 +1,301 @@
+// https://wiki.vg/Protocol
+
+use std::fmt::Display;
+
+use bytes::{BytesMut, BufMut, Buf};
+
+use crate::{BukrsType, BukrsNativeType, DecodeError, schema::TypeSchema};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarIntError {
+    /// A varint ran on past 5 bytes, or a varlong past 10
+    TooBig,
+    /// The input ended before the last byte, which may just not have arrived yet
+    Truncated
+}
+
+impl Display for VarIntError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarIntError::TooBig => write!(f, "Varint Too Big"),
+            VarIntError::Truncated => write!(f, "Varint Truncated")
+        }
+    }
+}
+
+impl std::error::Error for VarIntError {}
+
+pub static SEGMENT_BITS: i32 = 0x7F; // 2^7 - 1
+pub static CONTINUE_BIT: i32 = 0x80; // 2^7
+
+pub fn write_varint(value: i32, buffer: &mut BytesMut) {
+    let mut latest_value = value;   // This value is updated every loop
+
+    loop {
+        if (latest_value & !SEGMENT_BITS) == 0 {   // if value < 128
+            buffer.put_u8(latest_value as u8); // value can be coerced to a u8 type
+            return; // Done.
+        }
+
+        buffer.put_u8(((latest_value & SEGMENT_BITS) | CONTINUE_BIT) as u8);   // value can be coerced to a u8 type
+
+        latest_value = ((latest_value as u32) >> 7) as i32; // 'unsigned shift right' ( >>> )
+    }
+}
+
+pub fn read_varint(src: &mut BytesMut) -> Result<i32, VarIntError> {
+    let mut value = 0;
+    let mut position = 0;
+    let mut current_byte;
+
+    loop {
+        if !src.has_remaining() {
+            return Err(VarIntError::Truncated);
+        }
+        current_byte = src.get_u8() as i32;
+        value |= (current_byte & SEGMENT_BITS) << position;
+
+        if (current_byte & CONTINUE_BIT) == 0 { break; }
+
+        position += 7;
+
+        if position >= 32 { 
+            return Err(VarIntError::TooBig);
+        }
+    }
+
+    Ok(value)
+}
+
+pub fn write_varlong(value: i64, buffer: &mut BytesMut) {
+    let mut latest_value = value as u64;
+
+    while latest_value >= 0x80 {
+        buffer.put_u8((latest_value as u8 & 0x7F) | 0x80);
+        latest_value >>= 7;
+    }
+    buffer.put_u8(latest_value as u8);
+}
+
+pub fn read_varlong(src: &mut BytesMut) -> Result<i64, VarIntError> {
+    let mut value = 0u64;
+    let mut position = 0;
+
+    loop {
+        if !src.has_remaining() {
+            return Err(VarIntError::Truncated);
+        }
+        let current_byte = src.get_u8();
+        value |= ((current_byte & 0x7F) as u64) << position;
+
+        if (current_byte & 0x80) == 0 { break; }
+
+        position += 7;
+
+        if position >= 64 {
+            return Err(VarIntError::TooBig);
+        }
+    }
+
+    Ok(value as i64)
+}
+
+/// Length of a string or collection. Lengths are never negative, so they are written as an unsigned varint.
+pub fn write_length(len: usize, buffer: &mut BytesMut) {
+    write_varint(len as u32 as i32, buffer);
+}
+
+/// Fails on a bad varint. The length itself isn't checked against the input, callers read that many items or bytes.
+pub fn read_length(src: &mut BytesMut) -> Result<usize, DecodeError> {
+    Ok(read_varint(src)? as u32 as usize)
+}
+
+/// Maps signed values onto unsigned ones so small negative numbers stay short: 0, -1, 1, -2 become 0, 1, 2, 3
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn zigzag_decode(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// An i32 written as a varint. Small non-negative values take one byte, negative ones always take five.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VarInt(pub i32);
+
+/// An i64 written as a varlong, up to ten bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VarLong(pub i64);
+
+/// An i32 zigzag encoded, then written as a varint. Better than [`VarInt`] for values that are often negative.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ZigZagInt(pub i32);
+
+/// An i64 zigzag encoded, then written as a varlong
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ZigZagLong(pub i64);
+
+impl BukrsType for VarInt {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::VARINT
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::VARINT)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        write_varint(self.0, bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(VarInt(read_varint(bytes)?))
+    }
+}
+
+impl BukrsType for VarLong {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::VARLONG
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::VARLONG)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        write_varlong(self.0, bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(VarLong(read_varlong(bytes)?))
+    }
+}
+
+impl BukrsType for ZigZagInt {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::ZIGZAGINT
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::ZIGZAGINT)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        write_varint(zigzag_encode(self.0 as i64) as i32, bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(ZigZagInt(zigzag_decode(read_varint(bytes)? as u32 as u64) as i32))
+    }
+}
+
+impl BukrsType for ZigZagLong {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::ZIGZAGLONG
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::ZIGZAGLONG)
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        write_varlong(zigzag_encode(self.0) as i64, bytes);
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(ZigZagLong(zigzag_decode(read_varlong(bytes)? as u64)))
+    }
+}
+
+/// Any value of the wrapped integer
+#[cfg(feature = "proptest")]
+macro_rules! arbitrary_wrapper {
+    ($($wrapper:ident($inner:ty)),*) => {
+        $(
+            impl proptest::arbitrary::Arbitrary for $wrapper {
+                type Parameters = ();
+                type Strategy = proptest::arbitrary::Mapped<$inner, $wrapper>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    proptest::strategy::Strategy::prop_map(proptest::arbitrary::any::<$inner>(), $wrapper)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "proptest")]
+arbitrary_wrapper!(VarInt(i32), VarLong(i64), ZigZagInt(i32), ZigZagLong(i64));
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::BukrsType;
+
+    use super::{write_varint, read_varint, write_varlong, read_varlong, VarInt, VarLong, ZigZagInt, ZigZagLong, VarIntError};
+
+    #[test]
+    fn test_varint() {
+        let mut buffer = BytesMut::with_capacity(1024); // Setup Buffer
+        let num = 12589; // any random i32
+
+        write_varint(num, &mut buffer);  // Encode to Buffer
+        let value = read_varint(&mut buffer).expect("Uh Oh...");    // Decode Buffer
+        assert_eq!(value, num);
+
+        for num in [0, 127, 128, 16384, i32::MAX, -1, i32::MIN] {
+            write_varint(num, &mut buffer);
+            assert_eq!(read_varint(&mut buffer).unwrap(), num);
+        }
+        for num in [0, 300, i64::MAX, -1, i64::MIN] {
+            write_varlong(num, &mut buffer);
+            assert_eq!(read_varlong(&mut buffer).unwrap(), num);
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_varint_errors() {
+        let mut truncated = BytesMut::from(&[0x80, 0x80][..]);
+        assert_eq!(read_varint(&mut truncated.clone()), Err(VarIntError::Truncated));
+        assert_eq!(read_varlong(&mut truncated), Err(VarIntError::Truncated));
+        assert_eq!(read_varint(&mut BytesMut::new()), Err(VarIntError::Truncated));
+        assert_eq!(read_varint(&mut BytesMut::from(&[0xff; 6][..])), Err(VarIntError::TooBig));
+    }
+
+    #[test]
+    fn test_wrapper_sizes() {
+        let encoded = |value: &dyn Fn(&mut BytesMut)| {
+            let mut bytes = BytesMut::new();
+            value(&mut bytes);
+            bytes.len()
+        };
+        assert_eq!(encoded(&|bytes| VarInt(-1).encode(bytes)), 5);
+        assert_eq!(encoded(&|bytes| VarLong(-1).encode(bytes)), 10);
+        assert_eq!(encoded(&|bytes| ZigZagInt(-1).encode(bytes)), 1);
+        assert_eq!(encoded(&|bytes| ZigZagLong(-64).encode(bytes)), 1);
+
+        let mut bytes = BytesMut::new();
+        ZigZagInt(i32::MIN).encode(&mut bytes);
+        ZigZagLong(i64::MIN).encode(&mut bytes);
+        assert_eq!(ZigZagInt::decode(&mut bytes), Ok(ZigZagInt(i32::MIN)));
+        assert_eq!(ZigZagLong::decode(&mut bytes), Ok(ZigZagLong(i64::MIN)));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_any_varint(int: i32, long: i64) {
+            let mut bytes = BytesMut::new();
+            VarInt(int).encode(&mut bytes);
+            VarLong(long).encode(&mut bytes);
+            ZigZagInt(int).encode(&mut bytes);
+            ZigZagLong(long).encode(&mut bytes);
+            proptest::prop_assert_eq!(VarInt::decode(&mut bytes), Ok(VarInt(int)));
+            proptest::prop_assert_eq!(VarLong::decode(&mut bytes), Ok(VarLong(long)));
+            proptest::prop_assert_eq!(ZigZagInt::decode(&mut bytes), Ok(ZigZagInt(int)));
+            proptest::prop_assert_eq!(ZigZagLong::decode(&mut bytes), Ok(ZigZagLong(long)));
+            proptest::prop_assert!(bytes.is_empty());
+        }
+    }
+}
@@ -1,59 +1,385 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as NextStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput, DataStruct, Data, Fields, FieldsNamed};
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, parse_quote, DeriveInput, DataStruct, DataEnum, Data, Fields, Field, Attribute, Meta, NestedMeta, MetaNameValue, Lit, GenericParam, Type};
 
-#[proc_macro_derive(BukrsPacket)]
+/// Derives `BukrsPacket` and `BukrsDecodable`, writing the fields in declaration order.
+/// Fields take the same `#[bukrs(...)]` options as [`BukrsType`](macro@BukrsType), and get the same `Arbitrary` impl.
+#[proc_macro_derive(BukrsPacket, attributes(bukrs))]
 pub fn bukrs_packet(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let fields = if let Data::Struct(DataStruct { fields: Fields::Named(FieldsNamed { ref named, .. }), .. }) = &input.data {
-        named
-    } else {
-        panic!("BukrsPacket can only be derived for structs");
+    match derive_bukrs_packet(input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => TokenStream::from(error.to_compile_error())
+    }
+}
+
+fn derive_bukrs_packet(input: DeriveInput) -> syn::Result<NextStream> {
+    let Data::Struct(DataStruct { fields: fields @ Fields::Named(_), .. }) = &input.data else {
+        return Err(syn::Error::new_spanned(&input, "BukrsPacket can only be derived for structs with named fields"));
     };
     let ident = &input.ident;
-    let encode_methods = fields.iter().map(|field| {
-        let fident = field.ident.as_ref().unwrap();
-        quote! {
-            self.#fident.encode(target);
-        }
-    });
+    let FieldsCode { pattern, encode, construct, schema, arbitrary } = fields_code(fields, quote! { Self })?;
 
-    let decode = fields.iter().map(|field| {
-        let ty = &field.ty;
-        quote! {
-            #ty::decode(src)
-        }
-    });
+    Ok(quote! {
+        bukrs_core::__if_proptest! {
+            impl bukrs_core::proptest::arbitrary::Arbitrary for #ident {
+                type Parameters = ();
+                type Strategy = bukrs_core::proptest::strategy::BoxedStrategy<Self>;
 
-    let decode_to_struct = (0..fields.len()).map(|i|{
-        let field = &fields[i];
-        let fident = field.ident.as_ref().unwrap();
-        let decode = &decode.clone().collect::<Vec<NextStream>>()[i];
-        quote! {
-            #fident: #decode
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    bukrs_core::proptest::strategy::Strategy::boxed(#arbitrary)
+                }
+            }
         }
-    });
 
-    let expanded = quote! {
         impl bukrs_core::BukrsPacket for #ident {
             fn id(&self) -> String {
                 stringify!(#ident).to_string()
             }
 
-            fn encode(&self, target: &mut bytes::BytesMut) {
-                #(#encode_methods)*
+            #[allow(unused_variables)]
+            fn encode(&self, bytes: &mut bytes::BytesMut) {
+                let #ident #pattern = self;
+                #encode
+            }
+
+            fn schema() -> bukrs_core::schema::PacketSchema {
+                bukrs_core::schema::PacketSchema { name: stringify!(#ident).to_string(), fields: #schema }
             }
         }
 
         impl bukrs_core::BukrsDecodable for #ident {
-            fn decode(src: &mut bytes::BytesMut) -> #ident {
-                #ident {
-                    #(#decode_to_struct),*
+            #[allow(unused_variables)]
+            fn decode(bytes: &mut bytes::BytesMut) -> Result<#ident, bukrs_core::DecodeError> {
+                Ok(#ident #construct)
+            }
+        }
+    })
+}
+
+/// Options of a field, given as `#[bukrs(skip)]`, `#[bukrs(varint)]`, `#[bukrs(zigzag)]`, `#[bukrs(with = "module")]`
+/// or `#[bukrs(strategy = "function")]`. A `with` module has `encode(&T, &mut BytesMut)` and `decode(&mut BytesMut) -> Result<T, DecodeError>`.
+#[derive(Default)]
+struct FieldOptions {
+    skip: bool,
+    varint: bool,
+    zigzag: bool,
+    with: Option<syn::Path>,
+    strategy: Option<syn::Path>
+}
+
+/// 64 bit integers are written as varlongs instead of varints
+fn is_wide(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if ["u64", "i64", "usize", "isize"].iter().any(|wide| path.path.is_ident(wide)))
+}
+
+/// Zigzag decoding goes through `i64`, so unsigned values above the signed range come back wrong
+fn is_unsigned(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if ["u8", "u16", "u32", "u64", "u128", "usize"].iter().any(|unsigned| path.path.is_ident(unsigned)))
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+        let mut options = FieldOptions::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("bukrs")) {
+            let Meta::List(list) = attr.parse_meta()? else { return Err(syn::Error::new_spanned(attr, "expected #[bukrs(...)]")) };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => options.skip = true,
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("varint") => options.varint = true,
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("zigzag") => options.zigzag = true,
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(module), .. })) if path.is_ident("with") => options.with = Some(module.parse()?),
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(function), .. })) if path.is_ident("strategy") => options.strategy = Some(function.parse()?),
+                    other => return Err(syn::Error::new_spanned(other, "expected `skip`, `varint`, `zigzag`, `with = \"module\"` or `strategy = \"function\"`"))
                 }
             }
         }
+        Ok(options)
+    }
+
+    /// Writes `value`, a reference to the field, to `bytes`
+    fn encode(&self, value: &NextStream, ty: &Type) -> NextStream {
+        if self.skip {
+            quote! {}
+        } else if let Some(with) = &self.with {
+            quote! { #with::encode(#value, bytes); }
+        } else if self.zigzag && is_wide(ty) {
+            quote! { bukrs_core::varint::write_varlong(bukrs_core::varint::zigzag_encode(*#value as i64) as i64, bytes); }
+        } else if self.zigzag {
+            quote! { bukrs_core::varint::write_varint(bukrs_core::varint::zigzag_encode(*#value as i64) as i32, bytes); }
+        } else if self.varint && is_wide(ty) {
+            quote! { bukrs_core::varint::write_varlong(*#value as i64, bytes); }
+        } else if self.varint {
+            quote! { bukrs_core::varint::write_varint(*#value as i32, bytes); }
+        } else {
+            quote! { bukrs_core::BukrsType::encode(#value, bytes); }
+        }
+    }
+
+    /// Skipped fields aren't written, so they have no schema
+    fn schema(&self, ty: &Type) -> Option<NextStream> {
+        let native = |native: &str| {
+            let native = format_ident!("{}", native);
+            quote! { bukrs_core::schema::TypeSchema::native(bukrs_core::BukrsNativeType::#native) }
+        };
+        if self.skip {
+            None
+        } else if let Some(with) = &self.with {
+            let name = quote!(#with).to_string().replace(' ', "");
+            Some(quote! { bukrs_core::schema::TypeSchema::Custom { name: #name.to_string() } })
+        } else if self.zigzag {
+            Some(native(if is_wide(ty) { "ZIGZAGLONG" } else { "ZIGZAGINT" }))
+        } else if self.varint {
+            Some(native(if is_wide(ty) { "VARLONG" } else { "VARINT" }))
+        } else {
+            Some(quote! { <#ty as bukrs_core::BukrsType>::schema() })
+        }
+    }
+
+    /// Values that survive being written and read back. Skipped fields are always read as their default,
+    /// and `with` modules that lose information need a `strategy` function returning a narrower `Strategy`.
+    fn arbitrary(&self, ty: &Type) -> NextStream {
+        if self.skip {
+            quote! { bukrs_core::proptest::strategy::LazyJust::new(<#ty as Default>::default) }
+        } else if let Some(strategy) = &self.strategy {
+            quote! { #strategy() }
+        } else {
+            quote! { bukrs_core::proptest::arbitrary::any::<#ty>() }
+        }
+    }
+
+    fn decode(&self, field: &Field) -> NextStream {
+        let ty = &field.ty;
+        if self.skip {
+            quote! { Default::default() }
+        } else if let Some(with) = &self.with {
+            quote! { #with::decode(bytes)? }
+        } else if self.zigzag && is_wide(ty) {
+            quote! { bukrs_core::varint::zigzag_decode(bukrs_core::varint::read_varlong(bytes)? as u64) as #ty }
+        } else if self.zigzag {
+            quote! { bukrs_core::varint::zigzag_decode(bukrs_core::varint::read_varint(bytes)? as u32 as u64) as #ty }
+        } else if self.varint && is_wide(ty) {
+            quote! { bukrs_core::varint::read_varlong(bytes)? as #ty }
+        } else if self.varint {
+            quote! { bukrs_core::varint::read_varint(bytes)? as #ty }
+        } else {
+            quote! { <#ty as bukrs_core::BukrsType>::decode(bytes)? }
+        }
+    }
+}
+
+/// Encoding of one set of fields, read from and written to the bindings `pattern` gives them.
+/// `arbitrary` is a `Strategy` building the value with `path`, `Self` or `Self::Variant`.
+struct FieldsCode {
+    pattern: NextStream,
+    encode: NextStream,
+    construct: NextStream,
+    schema: NextStream,
+    arbitrary: NextStream
+}
+
+/// proptest only implements `Strategy` for tuples of up to 12, so the field strategies are grouped by 8
+fn arbitrary_code(fields: &Fields, path: &NextStream, strategies: &[NextStream], bindings: &[NextStream]) -> NextStream {
+    let strategies = strategies.chunks(8).map(|chunk| quote! { (#(#chunk,)*) });
+    let groups = bindings.chunks(8).map(|chunk| quote! { (#(#chunk,)*) });
+    let build = match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote! { { #(#names: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+        Fields::Unit => quote! {}
     };
+    if bindings.is_empty() {
+        return quote! { bukrs_core::proptest::strategy::LazyJust::new(|| #path #build) };
+    }
+    quote! {
+        bukrs_core::proptest::strategy::Strategy::prop_map((#(#strategies,)*), |(#(#groups,)*)| #path #build)
+    }
+}
+
+fn fields_code(fields: &Fields, path: NextStream) -> syn::Result<FieldsCode> {
+    let mut patterns = vec![];
+    let mut encode = vec![];
+    let mut decode = vec![];
+    let mut schema = vec![];
+    let mut strategies = vec![];
+    let mut bindings = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        let options = FieldOptions::parse(&field.attrs)?;
+        if options.zigzag && is_unsigned(&field.ty) {
+            return Err(syn::Error::new_spanned(&field.ty, "`zigzag` is for signed integers, use `varint` for unsigned ones"));
+        }
+        if let Some(ty) = options.schema(&field.ty) {
+            let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_else(|| index.to_string());
+            schema.push(quote! { bukrs_core::schema::FieldSchema { name: #name.to_string(), ty: #ty } });
+        }
+        let binding = format_ident!("__field{}", index);
+        let pattern = if options.skip { quote! { _ } } else { quote! { #binding } };
+        patterns.push(match &field.ident {
+            Some(ident) => quote! { #ident: #pattern },
+            None => pattern
+        });
+        encode.push(options.encode(&quote! { #binding }, &field.ty));
+        strategies.push(options.arbitrary(&field.ty));
+        bindings.push(quote! { #binding });
+        let value = options.decode(field);
+        decode.push(match &field.ident {
+            Some(ident) => quote! { #ident: #value },
+            None => value
+        });
+    }
+
+    let encode = quote! { #(#encode)* };
+    let schema = quote! { vec![#(#schema),*] };
+    let arbitrary = arbitrary_code(fields, &path, &strategies, &bindings);
+    Ok(match fields {
+        Fields::Named(_) => FieldsCode { pattern: quote! { { #(#patterns),* } }, encode, construct: quote! { { #(#decode),* } }, schema, arbitrary },
+        Fields::Unnamed(_) => FieldsCode { pattern: quote! { ( #(#patterns),* ) }, encode, construct: quote! { ( #(#decode),* ) }, schema, arbitrary },
+        Fields::Unit => FieldsCode { pattern: quote! {}, encode, construct: quote! {}, schema, arbitrary }
+    })
+}
+
+/// Derives `BukrsType` by writing the fields in declaration order.
+/// Enums are written as the variant's discriminant, as a varint, followed by the variant's fields.
+///
+/// With bukrs-core's `proptest` feature, also implements `Arbitrary` from the fields' own impls.
+/// Types that contain themselves need a `strategy` on the recursive field, or building the strategy never ends.
+#[proc_macro_derive(BukrsType, attributes(bukrs))]
+pub fn bukrs_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_bukrs_type(input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => TokenStream::from(error.to_compile_error())
+    }
+}
+
+fn derive_bukrs_type(mut input: DeriveInput) -> syn::Result<NextStream> {
+    let ident = &input.ident;
+    let (encode, decode, schema, arbitrary) = match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            let FieldsCode { pattern, encode, construct, schema, arbitrary } = fields_code(fields, quote! { Self })?;
+            (quote! {
+                let #ident #pattern = self;
+                #encode
+            }, quote! {
+                Ok(#ident #construct)
+            }, quote! {
+                bukrs_core::schema::TypeSchema::Struct { name: stringify!(#ident).to_string(), fields: #schema }
+            }, arbitrary)
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            let mut encode_arms = vec![];
+            let mut decode_arms = vec![];
+            let mut variant_schemas = vec![];
+            let mut variant_strategies = vec![];
+            let mut discriminant = quote! { 0i32 };
+            for variant in variants.iter() {
+                if let Some((_, explicit)) = &variant.discriminant {
+                    discriminant = quote! { (#explicit) as i32 };
+                }
+                let name = &variant.ident;
+                let FieldsCode { pattern, encode, construct, schema, arbitrary } = fields_code(&variant.fields, quote! { Self::#name })?;
+                variant_strategies.push(quote! { bukrs_core::proptest::strategy::Strategy::boxed(#arbitrary) });
+                variant_schemas.push(quote! {
+                    bukrs_core::schema::VariantSchema { name: stringify!(#name).to_string(), discriminant: #discriminant, fields: #schema }
+                });
+                encode_arms.push(quote! {
+                    Self::#name #pattern => {
+                        bukrs_core::varint::write_varint(#discriminant, bytes);
+                        #encode
+                    }
+                });
+                decode_arms.push(quote! {
+                    if discriminant == #discriminant {
+                        return Ok(Self::#name #construct);
+                    }
+                });
+                discriminant = quote! { #discriminant + 1 };
+            }
+            (quote! {
+                match self {
+                    #(#encode_arms)*
+                }
+            }, quote! {
+                let discriminant = bukrs_core::varint::read_varint(bytes)?;
+                #(#decode_arms)*
+                Err(bukrs_core::DecodeError::Invalid(format!("No {} variant has discriminant {}", stringify!(#ident), discriminant)))
+            }, quote! {
+                bukrs_core::schema::TypeSchema::Enum { name: stringify!(#ident).to_string(), variants: vec![#(#variant_schemas),*] }
+            }, quote! {
+                bukrs_core::proptest::strategy::Union::new(vec![#(#variant_strategies),*])
+            })
+        }
+        Data::Union(_) => return Err(syn::Error::new_spanned(&input, "BukrsType can't be derived for unions"))
+    };
+
+    let mut arbitrary_generics = input.generics.clone();
+    for param in arbitrary_generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(bukrs_core::proptest::arbitrary::Arbitrary));
+            param.bounds.push(parse_quote!('static));
+        }
+    }
+    let (arbitrary_impl_generics, _, _) = arbitrary_generics.split_for_impl();
+
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(bukrs_core::BukrsType));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        bukrs_core::__if_proptest! {
+            impl #arbitrary_impl_generics bukrs_core::proptest::arbitrary::Arbitrary for #ident #ty_generics #where_clause {
+                type Parameters = ();
+                type Strategy = bukrs_core::proptest::strategy::BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    bukrs_core::proptest::strategy::Strategy::boxed(#arbitrary)
+                }
+            }
+        }
+
+        impl #impl_generics bukrs_core::BukrsType for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn encode(&self, bytes: &mut bytes::BytesMut) {
+                #encode
+            }
+
+            #[allow(unused_variables)]
+            fn decode(bytes: &mut bytes::BytesMut) -> Result<Self, bukrs_core::DecodeError> {
+                #decode
+            }
+
+            fn ty(&self) -> bukrs_core::BukrsNativeType {
+                bukrs_core::BukrsNativeType::CUSTOM
+            }
+
+            fn schema() -> bukrs_core::schema::TypeSchema {
+                bukrs_core::schema::derived(stringify!(#ident), || #schema)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::{parse_quote, DeriveInput, Data};
+
+    use super::fields_code;
+
+    #[test]
+    fn test_zigzag_needs_signed() {
+        let signed: DeriveInput = parse_quote! { struct Signed { #[bukrs(zigzag)] a: i32, #[bukrs(zigzag)] b: i64 } };
+        let unsigned: DeriveInput = parse_quote! { struct Unsigned { #[bukrs(zigzag)] a: u32 } };
+        let (Data::Struct(signed), Data::Struct(unsigned)) = (signed.data, unsigned.data) else { unreachable!() };
 
-    TokenStream::from(expanded)
-}
\ No newline at end of file
+        assert!(fields_code(&signed.fields, quote! { Self }).is_ok());
+        let error = fields_code(&unsigned.fields, quote! { Self }).err().unwrap();
+        assert!(error.to_string().contains("`zigzag` is for signed integers"));
+    }
+}
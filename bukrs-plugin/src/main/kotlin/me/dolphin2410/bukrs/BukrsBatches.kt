@@ -0,0 +1,112 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.Channel
+import io.netty.channel.ChannelHandlerContext
+import io.netty.channel.ChannelOutboundHandlerAdapter
+import io.netty.channel.ChannelPromise
+import org.bukkit.Bukkit
+import java.util.Random
+import java.util.concurrent.ConcurrentHashMap
+import java.util.logging.Level
+
+/**
+ * Packets carried inside another packet, each written as its name and fields like the body of a frame
+ */
+data class PacketList(val values: List<PacketType>)
+
+fun batchCodecs() {
+    pushCodec(PacketList::class.java, object: TypeCodec<PacketList> {
+        private val decoder = BukrsDecoder()
+        private val encoder = BukrsEncoder()
+
+        override fun decode(src: ByteBuf) = PacketList((0 until readLength(src)).map { decoder.decodePacket(decodeType(String::class.java, src), src).getOrThrow() })
+
+        override fun encode(src: PacketList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach {
+                encodeType(String::class.java, it::class.java.simpleName, target)
+                encoder.encodePacket(it, target).getOrThrow()
+            }
+        }
+    })
+}
+
+/**
+ * Batched requests are dispatched together in one main thread task under payload ids of their own. Those are negative,
+ * as clients only pick positive ids, and belong to the batch's channel.
+ * [BatchInterceptor] catches the responses on their way out and sends them as one [DefaultPackets.BukrsResBatch].
+ * A request without a handler fails the whole batch before anything runs, and a handler that throws is answered with a [DefaultPackets.BukrsResError].
+ */
+object BukrsBatches: BukrsListener {
+    private class PendingBatch(val ctx: ChannelHandlerContext, val payloadId: Int, size: Int) {
+        val responses = arrayOfNulls<PacketType>(size)
+        var remaining = size
+    }
+
+    private val slots = ConcurrentHashMap<Pair<Channel, Int>, Pair<PendingBatch, Int>>()
+    private val random = Random()
+
+    @BukrsEventHandler
+    fun batch(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqBatch) {
+        val requests = packet.requests.values
+        if (requests.isEmpty()) {
+            ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResBatch(PacketList(emptyList())))
+            return
+        }
+
+        val unhandled = requests.filterNot { BukrsEvents.handles(it) }
+        if (unhandled.isNotEmpty()) {
+            val error = DefaultPackets.BukrsResError("No handler for ${unhandled.joinToString { it::class.java.simpleName }}")
+            ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResBatch(PacketList(requests.map { error })))
+            return
+        }
+
+        val batch = PendingBatch(ctx, payloadId, requests.size)
+        val ids = requests.indices.map { index ->
+            var id: Int
+            do {
+                id = -(random.nextInt(Int.MAX_VALUE) + 1)
+            } while (slots.putIfAbsent(ctx.channel() to id, batch to index) != null)
+            id
+        }
+        Bukkit.getScheduler().runTask(BukrsMain.instance, Runnable {
+            requests.forEachIndexed { index, request ->
+                try {
+                    BukrsEvents.dispatch(ctx, ids[index] to request)
+                } catch (e: Exception) {
+                    val cause = e.cause ?: e    // Handlers are invoked reflectively
+                    BukrsMain.instance.logger.log(Level.WARNING, "Batched ${request::class.java.simpleName} failed", cause)
+                    complete(ctx.channel(), ids[index], DefaultPackets.BukrsResError("${request::class.java.simpleName} failed: $cause"))
+                }
+            }
+        })
+    }
+
+    /**
+     * @return true if the packet answered a batched request
+     */
+    fun complete(channel: Channel, payloadId: Int, packet: PacketType): Boolean {
+        val (batch, index) = slots.remove(channel to payloadId) ?: return false
+        val done = synchronized(batch) {
+            batch.responses[index] = packet
+            --batch.remaining == 0
+        }
+        if (done) {
+            batch.ctx.pipeline().writeAndFlush(batch.payloadId to DefaultPackets.BukrsResBatch(PacketList(batch.responses.map { it!! })))
+        }
+        return true
+    }
+}
+
+class BatchInterceptor: ChannelOutboundHandlerAdapter() {
+    override fun write(ctx: ChannelHandlerContext, msg: Any, promise: ChannelPromise) {
+        val payloadId = (msg as? Pair<*, *>)?.first
+        val packet = (msg as? Pair<*, *>)?.second
+        if (payloadId is Int && payloadId != 0 && packet is PacketType && BukrsBatches.complete(ctx.channel(), payloadId, packet)) {
+            promise.setSuccess()
+        } else {
+            ctx.write(msg, promise)
+        }
+    }
+}
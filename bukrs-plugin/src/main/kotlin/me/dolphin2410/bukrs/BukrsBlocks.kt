@@ -0,0 +1,165 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import org.bukkit.Location
+import org.bukkit.NamespacedKey
+import org.bukkit.Registry
+import org.bukkit.block.Block
+import org.bukkit.block.BlockFace as BukkitBlockFace
+import org.bukkit.event.EventHandler
+import org.bukkit.event.EventPriority
+import org.bukkit.event.Listener
+import org.bukkit.event.block.Action
+import org.bukkit.event.block.BlockBreakEvent
+import org.bukkit.event.block.BlockPlaceEvent
+import org.bukkit.event.player.PlayerInteractEvent
+import org.bukkit.inventory.EquipmentSlot
+
+data class BlockPos(val world: String, val x: Int, val y: Int, val z: Int)
+
+data class Material(val key: String)
+
+enum class BlockFace {
+    NORTH,
+    EAST,
+    SOUTH,
+    WEST,
+    UP,
+    DOWN,
+    SELF
+}
+
+enum class Hand {
+    MAIN_HAND,
+    OFF_HAND
+}
+
+enum class InteractAction {
+    LEFT_CLICK_BLOCK,
+    RIGHT_CLICK_BLOCK,
+    LEFT_CLICK_AIR,
+    RIGHT_CLICK_AIR,
+    PHYSICAL
+}
+
+sealed class InteractTarget {
+    object Air: InteractTarget()
+
+    data class OfBlock(val pos: BlockPos, val material: Material, val face: BlockFace): InteractTarget()
+}
+
+fun Block.toBlockPos() = BlockPos(world.name, x, y, z)
+
+fun Location.toBlockPos() = BlockPos(world.name, blockX, blockY, blockZ)
+
+fun org.bukkit.Material.toMaterial() = Material(key.toString())
+
+/**
+ * Null if the key is malformed or names no material
+ */
+fun Material.toBukkit(): org.bukkit.Material? = runCatching { NamespacedKey.fromString(key) }.getOrNull()?.let { Registry.MATERIAL.get(it) }
+
+fun BukkitBlockFace.toBlockFace() = BlockFace.values().find { it.name == name } ?: BlockFace.SELF
+
+fun EquipmentSlot?.toHand() = if (this == EquipmentSlot.OFF_HAND) Hand.OFF_HAND else Hand.MAIN_HAND
+
+internal fun readBlockPos(src: ByteBuf) = BlockPos(decodeType(String::class.java, src), src.readInt(), src.readInt(), src.readInt())
+
+internal fun writeBlockPos(src: BlockPos, target: ByteBuf) {
+    encodeType(String::class.java, src.world, target)
+    target.writeInt(src.x)
+    target.writeInt(src.y)
+    target.writeInt(src.z)
+}
+
+private inline fun <reified T: Enum<T>> pushEnumCodec() {
+    pushCodec(T::class.java, object: TypeCodec<T> {
+        override fun decode(src: ByteBuf) = enumValues<T>()[src.readByte().toInt()]
+
+        override fun encode(src: T, target: ByteBuf) {
+            target.writeByte(src.ordinal)
+        }
+    })
+}
+
+fun blockCodecs() {
+    pushCodec(BlockPos::class.java, object: TypeCodec<BlockPos> {
+        override fun decode(src: ByteBuf) = readBlockPos(src)
+
+        override fun encode(src: BlockPos, target: ByteBuf) = writeBlockPos(src, target)
+    })
+
+    pushCodec(Material::class.java, object: TypeCodec<Material> {
+        override fun decode(src: ByteBuf) = Material(decodeType(String::class.java, src))
+
+        override fun encode(src: Material, target: ByteBuf) = encodeType(String::class.java, src.key, target)
+    })
+
+    pushEnumCodec<BlockFace>()
+    pushEnumCodec<Hand>()
+    pushEnumCodec<InteractAction>()
+
+    pushCodec(InteractTarget::class.java, object: TypeCodec<InteractTarget> {
+        override fun decode(src: ByteBuf): InteractTarget {
+            return when (src.readByte().toInt()) {
+                0 -> InteractTarget.Air
+                1 -> InteractTarget.OfBlock(readBlockPos(src), decodeType(Material::class.java, src), decodeType(BlockFace::class.java, src))
+                else -> throw RuntimeException("Invalid InteractTarget")
+            }
+        }
+
+        override fun encode(src: InteractTarget, target: ByteBuf) {
+            when (src) {
+                InteractTarget.Air -> target.writeByte(0)
+                is InteractTarget.OfBlock -> {
+                    target.writeByte(1)
+                    writeBlockPos(src.pos, target)
+                    encodeType(Material::class.java, src.material, target)
+                    encodeType(BlockFace::class.java, src.face, target)
+                }
+            }
+        }
+    })
+}
+
+/**
+ * Block events fire on the main thread: every subscribed client can hold the tick for up to its deadline
+ */
+object BukrsBlocks: Listener {
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onBreak(event: BlockBreakEvent) {
+        if (!BukrsCancellable.isSubscribed("BlockBreak")) return
+
+        val initial = DefaultPackets.BukrsResBlockBreak(false, event.isDropItems)
+        val verdict = BukrsCancellable.verdict("BlockBreak", initial) {
+            DefaultPackets.BukrsSDBlockBreak(PlayerId(event.player.entityId), event.block.toBlockPos(), event.block.type.toMaterial(), it.dropItems)
+        }
+        event.isCancelled = verdict.cancelled
+        event.isDropItems = verdict.dropItems
+    }
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onPlace(event: BlockPlaceEvent) {
+        if (!BukrsCancellable.isSubscribed("BlockPlace")) return
+
+        val verdict = BukrsCancellable.verdict("BlockPlace", DefaultPackets.BukrsResBlockPlace(false)) {
+            DefaultPackets.BukrsSDBlockPlace(PlayerId(event.player.entityId), event.block.toBlockPos(), event.block.type.toMaterial(), event.blockAgainst.toBlockPos(), event.hand.toHand())
+        }
+        event.isCancelled = verdict.cancelled
+    }
+
+    @EventHandler(priority = EventPriority.HIGH)
+    fun onInteract(event: PlayerInteractEvent) {
+        if (!BukrsCancellable.isSubscribed("PlayerInteract")) return
+
+        val block = event.clickedBlock
+        val target = if (block == null) InteractTarget.Air else InteractTarget.OfBlock(block.toBlockPos(), block.type.toMaterial(), event.blockFace.toBlockFace())
+        val action = InteractAction.values()[Action.values().indexOf(event.action)]
+        val item = event.item?.type?.toMaterial() ?: org.bukkit.Material.AIR.toMaterial()
+
+        val verdict = BukrsCancellable.verdict("PlayerInteract", DefaultPackets.BukrsResPlayerInteract(false)) {
+            DefaultPackets.BukrsSDPlayerInteract(PlayerId(event.player.entityId), action, target, event.hand.toHand(), item)
+        }
+        if (verdict.cancelled) event.isCancelled = true
+    }
+}
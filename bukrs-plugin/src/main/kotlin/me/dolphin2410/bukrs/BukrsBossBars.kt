@@ -0,0 +1,143 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.boss.BarColor
+import org.bukkit.boss.BarFlag
+import org.bukkit.boss.BarStyle
+import org.bukkit.boss.BossBar
+import java.util.concurrent.ConcurrentHashMap
+
+/**
+ * Colours, styles and flags are sent as the ordinals of Bukkit's enums
+ */
+sealed class BossBarChange {
+    data class Title(val title: String): BossBarChange()
+    data class Progress(val progress: Double): BossBarChange()
+    data class Color(val color: Byte): BossBarChange()
+    data class Style(val style: Byte): BossBarChange()
+    data class Flags(val flags: Byte): BossBarChange()
+    data class AddPlayers(val players: List<PlayerId>): BossBarChange()
+    data class RemovePlayers(val players: List<PlayerId>): BossBarChange()
+}
+
+data class BossBarChangeList(val values: List<BossBarChange>)
+
+private fun readPlayers(src: ByteBuf) = (0 until readLength(src)).map { PlayerId(src.readInt()) }
+
+private fun writePlayers(players: List<PlayerId>, target: ByteBuf) {
+    writeLength(players.size, target)
+    players.forEach { target.writeInt(it.id) }
+}
+
+private fun readChange(src: ByteBuf): BossBarChange {
+    return when (src.readByte().toInt()) {
+        0 -> BossBarChange.Title(decodeType(String::class.java, src))
+        1 -> BossBarChange.Progress(src.readDouble())
+        2 -> BossBarChange.Color(src.readByte())
+        3 -> BossBarChange.Style(src.readByte())
+        4 -> BossBarChange.Flags(src.readByte())
+        5 -> BossBarChange.AddPlayers(readPlayers(src))
+        6 -> BossBarChange.RemovePlayers(readPlayers(src))
+        else -> throw RuntimeException("Invalid BossBarChange")
+    }
+}
+
+private fun writeChange(src: BossBarChange, target: ByteBuf) {
+    when (src) {
+        is BossBarChange.Title -> {
+            target.writeByte(0)
+            encodeType(String::class.java, src.title, target)
+        }
+        is BossBarChange.Progress -> {
+            target.writeByte(1)
+            target.writeDouble(src.progress)
+        }
+        is BossBarChange.Color -> {
+            target.writeByte(2)
+            target.writeByte(src.color.toInt())
+        }
+        is BossBarChange.Style -> {
+            target.writeByte(3)
+            target.writeByte(src.style.toInt())
+        }
+        is BossBarChange.Flags -> {
+            target.writeByte(4)
+            target.writeByte(src.flags.toInt())
+        }
+        is BossBarChange.AddPlayers -> {
+            target.writeByte(5)
+            writePlayers(src.players, target)
+        }
+        is BossBarChange.RemovePlayers -> {
+            target.writeByte(6)
+            writePlayers(src.players, target)
+        }
+    }
+}
+
+fun bossBarCodecs() {
+    pushCodec(BossBarChangeList::class.java, object: TypeCodec<BossBarChangeList> {
+        override fun decode(src: ByteBuf) = BossBarChangeList((0 until readLength(src)).map { readChange(src) })
+
+        override fun encode(src: BossBarChangeList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { writeChange(it, target) }
+        }
+    })
+}
+
+/**
+ * Bars belong to the client that created them and are removed when it disconnects. Ids are per client.
+ */
+object BukrsBossBars: BukrsListener {
+    private val bars = ConcurrentHashMap<Pair<ChannelHandlerContext, Int>, BossBar>()
+
+    private fun players(ids: List<PlayerId>) = ids.mapNotNull { id -> Bukkit.getOnlinePlayers().find { it.entityId == id.id } }
+
+    private fun apply(bar: BossBar, change: BossBarChange) {
+        when (change) {
+            is BossBarChange.Title -> bar.setTitle(change.title)
+            is BossBarChange.Progress -> bar.progress = change.progress.coerceIn(0.0, 1.0)
+            is BossBarChange.Color -> bar.color = BarColor.values()[change.color.toInt()]
+            is BossBarChange.Style -> bar.style = BarStyle.values()[change.style.toInt()]
+            is BossBarChange.Flags -> BarFlag.values().forEach {
+                if (change.flags.toInt() and (1 shl it.ordinal) != 0) bar.addFlag(it) else bar.removeFlag(it)
+            }
+            is BossBarChange.AddPlayers -> players(change.players).forEach { bar.addPlayer(it) }
+            is BossBarChange.RemovePlayers -> players(change.players).forEach { bar.removePlayer(it) }
+        }
+    }
+
+    @BukrsEventHandler
+    fun create(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqCreateBossBar) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val bar = Bukkit.createBossBar(packet.title, BarColor.values()[packet.color.toInt()], BarStyle.values()[packet.style.toInt()])
+            val key = ctx to packet.barId
+            bars.put(key, bar)?.removeAll()
+            ctx.channel().closeFuture().addListener {
+                if (bars.remove(key, bar)) Bukkit.getScheduler().runTask(BukrsMain.instance, Runnable { bar.removeAll() })
+            }
+            DefaultPackets.BukrsResBossBar(true)
+        }
+    }
+
+    @BukrsEventHandler
+    fun update(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqUpdateBossBar) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val bar = bars[ctx to packet.barId]
+            bar?.let { packet.changes.values.forEach { change -> apply(it, change) } }
+            DefaultPackets.BukrsResBossBar(bar != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun remove(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveBossBar) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val bar = bars.remove(ctx to packet.barId)
+            bar?.removeAll()
+            DefaultPackets.BukrsResBossBar(bar != null)
+        }
+    }
+}
@@ -0,0 +1,70 @@
+package me.dolphin2410.bukrs
+
+import io.netty.channel.ChannelHandlerContext
+import java.util.concurrent.ConcurrentHashMap
+import java.util.concurrent.CopyOnWriteArrayList
+
+/**
+ * Cancellable events: the event is sent to every blocking subscriber in turn, each verdict feeding the next request.
+ * All of them share the longest of their deadlines, so one event holds up the server at most that long.
+ * A client that misses its own deadline leaves the event unchanged and is unsubscribed from it.
+ * Other subscribers only observe: they get the decided event without a payload id, unless it was cancelled.
+ */
+object BukrsCancellable: BukrsListener {
+    private class Subscription(val ctx: ChannelHandlerContext, @Volatile var deadlineMillis: Long, @Volatile var blocking: Boolean)
+
+    private val subscriptions = ConcurrentHashMap<String, CopyOnWriteArrayList<Subscription>>()
+
+    @BukrsEventHandler
+    fun subscribe(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSubscribeEvent) {
+        val list = subscriptions.computeIfAbsent(packet.event) { CopyOnWriteArrayList() }
+        val existing = list.find { it.ctx == ctx }
+        if (existing != null) {
+            existing.deadlineMillis = packet.deadlineMs.toLong()
+            existing.blocking = packet.blocking
+        } else {
+            list.add(Subscription(ctx, packet.deadlineMs.toLong(), packet.blocking))
+            ctx.channel().closeFuture().addListener { list.removeIf { it.ctx == ctx } }
+        }
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResSubscribeEvent())
+    }
+
+    fun isSubscribed(event: String) = subscriptions[event]?.isNotEmpty() == true
+
+    /**
+     * @param wants picks the subscribed connections this event is sent to
+     * @param request builds the event packet from the current verdict
+     */
+    inline fun <reified V: EventVerdict> verdict(event: String, initial: V, noinline wants: (ChannelHandlerContext) -> Boolean = { true }, noinline request: (V) -> PacketType): V {
+        return verdict(event, initial, V::class.java, wants, request)
+    }
+
+    fun <V: EventVerdict> verdict(event: String, initial: V, type: Class<V>, wants: (ChannelHandlerContext) -> Boolean, request: (V) -> PacketType): V {
+        val subscribers = subscriptions[event]?.filter { wants(it.ctx) } ?: return initial
+        val (blocking, observers) = subscribers.partition { it.blocking }
+        val end = System.currentTimeMillis() + (blocking.maxOfOrNull { it.deadlineMillis } ?: 0)
+        var verdict = initial
+        for (subscription in blocking) {
+            val wait = minOf(subscription.deadlineMillis, end - System.currentTimeMillis())
+            if (wait <= 0) break
+            val answer = BukrsRequests.awaitOrNull(subscription.ctx, request(verdict), wait, type)
+            if (answer != null) {
+                verdict = answer
+            } else if (wait == subscription.deadlineMillis) {
+                drop(event, subscription)
+            }
+        }
+
+        if (!verdict.cancelled && observers.isNotEmpty()) {
+            val packet = request(verdict)
+            observers.forEach { it.ctx.pipeline().writeAndFlush(0 to packet) }
+        }
+        return verdict
+    }
+
+    private fun drop(event: String, subscription: Subscription) {
+        subscriptions[event]?.remove(subscription)
+        BukrsMain.instance.logger.warning("Unsubscribed a client from $event after it missed its ${subscription.deadlineMillis}ms deadline")
+        subscription.ctx.pipeline().writeAndFlush(0 to DefaultPackets.BukrsSDEventDropped(event))
+    }
+}
@@ -0,0 +1,35 @@
+package me.dolphin2410.bukrs
+
+import org.bukkit.Bukkit
+import org.bukkit.event.EventHandler
+import org.bukkit.event.EventPriority
+import org.bukkit.event.Listener
+import org.bukkit.event.player.AsyncPlayerChatEvent
+
+/**
+ * Forwards chat to subscribed clients and applies their verdict. Runs on the async chat thread, so waiting is safe.
+ */
+object BukrsChat: Listener {
+    const val EVENT = "PlayerChat"
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onChat(event: AsyncPlayerChatEvent) {
+        if (!BukrsCancellable.isSubscribed(EVENT)) return
+
+        val recipients = PlayerIdList(event.recipients.map { PlayerId(it.entityId) })
+        val initial = DefaultPackets.BukrsResPlayerChat(false, event.message, event.format, recipients)
+        val verdict = BukrsCancellable.verdict(EVENT, initial) {
+            DefaultPackets.BukrsSDPlayerChat(PlayerId(event.player.entityId), it.message, it.format, it.recipients)
+        }
+
+        if (verdict.cancelled) {
+            event.isCancelled = true
+            return
+        }
+        event.message = verdict.message
+        event.format = verdict.format
+        val ids = verdict.recipients.values.map { it.id }.toSet()
+        event.recipients.retainAll { it.entityId in ids }
+        event.recipients.addAll(Bukkit.getOnlinePlayers().filter { it.entityId in ids })
+    }
+}
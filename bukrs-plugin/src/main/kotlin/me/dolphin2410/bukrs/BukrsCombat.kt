@@ -0,0 +1,116 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import org.bukkit.entity.Entity
+import org.bukkit.entity.Projectile
+import org.bukkit.event.EventHandler
+import org.bukkit.event.EventPriority
+import org.bukkit.event.Listener
+import org.bukkit.event.entity.EntityDamageByEntityEvent
+import org.bukkit.event.entity.EntityDamageEvent
+import org.bukkit.event.entity.PlayerDeathEvent
+import org.bukkit.inventory.ItemStack
+
+/**
+ * [data] is Paper's serialized stack, empty for plain items
+ */
+data class ItemData(val material: Material, val amount: Byte, val data: ByteArray) {
+    /**
+     * Null if the material is unknown or the serialized stack can't be read
+     */
+    fun toItemStack(): ItemStack? {
+        val type = material.toBukkit() ?: return null
+        val item = if (data.isEmpty()) ItemStack(type) else runCatching { ItemStack.deserializeBytes(data) }.getOrNull() ?: return null
+        item.type = type
+        item.amount = amount.toInt() and 0xff
+        return item
+    }
+}
+
+data class ItemDataList(val values: List<ItemData>)
+
+fun ItemStack.toItemData() = ItemData(type.toMaterial(), amount.toByte(), serializeAsBytes())
+
+private fun readItem(src: ByteBuf): ItemData {
+    val material = decodeType(Material::class.java, src)
+    val amount = src.readByte()
+    val data = ByteArray(readLength(src))
+    src.readBytes(data)
+    return ItemData(material, amount, data)
+}
+
+private fun writeItem(src: ItemData, target: ByteBuf) {
+    encodeType(Material::class.java, src.material, target)
+    target.writeByte(src.amount.toInt())
+    writeLength(src.data.size, target)
+    target.writeBytes(src.data)
+}
+
+fun combatCodecs() {
+    pushCodec(ItemData::class.java, object: TypeCodec<ItemData> {
+        override fun decode(src: ByteBuf) = readItem(src)
+
+        override fun encode(src: ItemData, target: ByteBuf) = writeItem(src, target)
+    })
+
+    pushCodec(ItemDataList::class.java, object: TypeCodec<ItemDataList> {
+        override fun decode(src: ByteBuf) = ItemDataList((0 until readLength(src)).map { readItem(src) })
+
+        override fun encode(src: ItemDataList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { writeItem(it, target) }
+        }
+    })
+}
+
+object BukrsCombat: Listener {
+    /**
+     * Projectiles count as attacks by whoever shot them
+     */
+    private fun attacker(event: EntityDamageEvent): Entity? {
+        val damager = (event as? EntityDamageByEntityEvent)?.damager ?: return null
+        return ((damager as? Projectile)?.shooter as? Entity) ?: damager
+    }
+
+    @Suppress("DEPRECATION")
+    private fun EntityDamageEvent.setFinalDamage(damage: Double) {
+        EntityDamageEvent.DamageModifier.values()
+            .filter { it != EntityDamageEvent.DamageModifier.BASE && isApplicable(it) }
+            .forEach { setDamage(it, 0.0) }
+        this.damage = damage
+    }
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onDamage(event: EntityDamageEvent) {
+        if (!BukrsCancellable.isSubscribed("EntityDamage")) return
+
+        val attacker = OptionalEntityId(attacker(event)?.let { EntityId(it.entityId) })
+        val verdict = BukrsCancellable.verdict("EntityDamage", DefaultPackets.BukrsResEntityDamage(false, event.finalDamage)) {
+            DefaultPackets.BukrsSDEntityDamage(EntityId(event.entity.entityId), attacker, event.cause.name, event.damage, it.finalDamage)
+        }
+        event.isCancelled = verdict.cancelled
+        if (verdict.finalDamage != event.finalDamage) event.setFinalDamage(verdict.finalDamage)
+    }
+
+    @Suppress("DEPRECATION")
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onDeath(event: PlayerDeathEvent) {
+        if (!BukrsCancellable.isSubscribed("PlayerDeath")) return
+
+        val initial = DefaultPackets.BukrsResPlayerDeath(false, ItemDataList(event.drops.map { it.toItemData() }), event.droppedExp, event.deathMessage ?: "", event.keepInventory)
+        val verdict = BukrsCancellable.verdict("PlayerDeath", initial) {
+            DefaultPackets.BukrsSDPlayerDeath(PlayerId(event.entity.entityId), it.drops, it.droppedExp, it.deathMessage, it.keepInventory)
+        }
+        event.isCancelled = verdict.cancelled
+        val drops = verdict.drops.values.map { it.toItemStack() }
+        if (null in drops) {
+            BukrsMain.instance.logger.warning("Kept the original drops of ${event.entity.name}, a client sent an item the server doesn't know")
+        } else {
+            event.drops.clear()
+            event.drops.addAll(drops.filterNotNull())
+        }
+        event.droppedExp = verdict.droppedExp
+        event.deathMessage = verdict.deathMessage.ifEmpty { null }
+        event.keepInventory = verdict.keepInventory
+    }
+}
@@ -0,0 +1,152 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.command.CommandSender as BukkitSender
+import org.bukkit.command.defaults.BukkitCommand
+import org.bukkit.entity.Player
+
+sealed class CommandSender {
+    object Console: CommandSender()
+
+    data class OfPlayer(val player: PlayerId): CommandSender()
+
+    fun resolve(): BukkitSender? = when (this) {
+        Console -> Bukkit.getConsoleSender()
+        is OfPlayer -> Bukkit.getOnlinePlayers().find { it.entityId == player.id }
+    }
+
+    companion object {
+        fun of(sender: BukkitSender): CommandSender = if (sender is Player) OfPlayer(PlayerId(sender.entityId)) else Console
+    }
+}
+
+enum class ArgKind {
+    STRING,
+    INTEGER,
+    DECIMAL,
+    BOOLEAN,
+    PLAYER
+}
+
+data class ArgSpec(val name: String, val kind: ArgKind, val optional: Boolean)
+
+data class CommandSpec(val name: String, val permission: String, val usage: String, val args: List<ArgSpec>, val subcommands: List<CommandSpec>)
+
+data class StringList(val values: List<String>)
+
+private fun <T> readList(src: ByteBuf, element: (ByteBuf) -> T): List<T> {
+    return (0 until readLength(src)).map { element(src) }
+}
+
+private fun <T> writeList(list: List<T>, target: ByteBuf, element: (T, ByteBuf) -> Unit) {
+    writeLength(list.size, target)
+    list.forEach { element(it, target) }
+}
+
+private fun readString(src: ByteBuf) = decodeType(String::class.java, src)
+
+private fun writeString(value: String, target: ByteBuf) = encodeType(String::class.java, value, target)
+
+private fun readArgSpec(src: ByteBuf) = ArgSpec(readString(src), ArgKind.values()[src.readByte().toInt()], src.readByte().toInt() != 0)
+
+private fun readCommandSpec(src: ByteBuf): CommandSpec {
+    return CommandSpec(readString(src), readString(src), readString(src), readList(src, ::readArgSpec), readList(src, ::readCommandSpec))
+}
+
+fun commandCodecs() {
+    pushCodec(CommandSender::class.java, object: TypeCodec<CommandSender> {
+        override fun decode(src: ByteBuf): CommandSender {
+            return when (src.readByte().toInt()) {
+                0 -> CommandSender.Console
+                1 -> CommandSender.OfPlayer(PlayerId(src.readInt()))
+                else -> throw RuntimeException("Invalid CommandSender")
+            }
+        }
+
+        override fun encode(src: CommandSender, target: ByteBuf) {
+            when (src) {
+                CommandSender.Console -> target.writeByte(0)
+                is CommandSender.OfPlayer -> {
+                    target.writeByte(1)
+                    target.writeInt(src.player.id)
+                }
+            }
+        }
+    })
+
+    pushCodec(CommandSpec::class.java, object: TypeCodec<CommandSpec> {
+        override fun decode(src: ByteBuf) = readCommandSpec(src)
+
+        override fun encode(src: CommandSpec, target: ByteBuf) {
+            throw UnsupportedOperationException("CommandSpec is only sent by the client")
+        }
+    })
+
+    pushCodec(StringList::class.java, object: TypeCodec<StringList> {
+        override fun decode(src: ByteBuf) = StringList(readList(src, ::readString))
+
+        override fun encode(src: StringList, target: ByteBuf) = writeList(src.values, target, ::writeString)
+    })
+}
+
+/**
+ * Bukkit command forwarding every invocation to the client that registered it.
+ * Permissions are checked here for the whole subcommand path, and player arguments of online players are replaced with `#` and their entity id.
+ */
+class BukrsCommand(private val spec: CommandSpec, private val owner: ChannelHandlerContext): BukkitCommand(spec.name) {
+    init {
+        usageMessage = spec.usage
+        if (spec.permission.isNotEmpty()) permission = spec.permission
+    }
+
+    override fun execute(sender: BukkitSender, commandLabel: String, args: Array<out String>): Boolean {
+        var node = spec
+        var depth = 0
+        while (true) {
+            if (node.permission.isNotEmpty() && !sender.hasPermission(node.permission)) {
+                sender.sendMessage("You do not have permission to use this command")
+                return true
+            }
+            node = node.subcommands.find { args.getOrNull(depth).equals(it.name, ignoreCase = true) } ?: break
+            depth++
+        }
+
+        val resolved = args.mapIndexed { i, arg ->
+            val kind = node.args.getOrNull(i - depth)?.kind
+            if (i >= depth && kind == ArgKind.PLAYER) Bukkit.getPlayerExact(arg)?.let { "#${it.entityId}" } ?: arg else arg
+        }
+
+        owner.pipeline().writeAndFlush(0 to DefaultPackets.BukrsSDCommand(CommandSender.of(sender), spec.name, StringList(resolved)))
+        return true
+    }
+
+    override fun tabComplete(sender: BukkitSender, alias: String, args: Array<out String>): List<String> {
+        val request = DefaultPackets.BukrsSDTabComplete(CommandSender.of(sender), spec.name, StringList(args.toList()))
+        val fallback = DefaultPackets.BukrsResTabComplete(StringList(emptyList()))
+        return BukrsRequests.await(owner, request, COMPLETION_BUDGET_MILLIS, fallback).suggestions.values
+    }
+
+    companion object {
+        /**
+         * Longer than `COMPLETION_BUDGET` on the client, so suggestions of a provider that uses all of it still arrive in time
+         */
+        const val COMPLETION_BUDGET_MILLIS = 50L
+    }
+}
+
+object BukrsCommands: BukrsListener {
+    @BukrsEventHandler
+    fun registerCommand(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRegisterCommand) {
+        Bukkit.getCommandMap().register("bukrs", BukrsCommand(packet.command, ctx))
+        Bukkit.getOnlinePlayers().forEach { it.updateCommands() }
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResRegisterCommand())
+    }
+
+    @BukrsEventHandler
+    fun sendMessage(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSendMessage) {
+        packet.target.resolve()?.sendMessage(packet.message)
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResSendMessage())
+    }
+}
@@ -5,15 +5,27 @@ import io.netty.channel.ChannelHandlerContext
 import io.netty.handler.codec.ByteToMessageDecoder
 import java.lang.RuntimeException
 
+/**
+ * Largest payload a client may send, the same as `MAX_FRAME_LEN` in Rust
+ */
+const val MAX_FRAME_LEN = 64 * 1024 * 1024
+
 class BukrsDecoder: ByteToMessageDecoder() {
     /**
-     * Pair of PACKET_SIZE and PAYLOAD_ID
+     * Pair of PACKET_SIZE and PAYLOAD_ID, or null if the header hasn't fully arrived yet
      */
-    fun decodeHeader(buf: ByteBuf): Pair<Int, Int> {
-        val size = readVarInt(buf).getOrThrow()
-        val payloadId = buf.readInt()
-
-        return size to payloadId
+    fun decodeHeader(buf: ByteBuf): Pair<Int, Int>? {
+        for (i in 0 until minOf(buf.readableBytes(), 5)) {
+            if ((buf.getByte(buf.readerIndex() + i).toInt() and CONTINUE_BIT) == 0) {
+                if (buf.readableBytes() < i + 1 + 4) return null
+                val size = readVarInt(buf).getOrThrow()
+                val payloadId = buf.readInt()
+
+                return size to payloadId
+            }
+        }
+        if (buf.readableBytes() >= 5) throw RuntimeException("Varint too big")
+        return null
     }
 
     fun decodePacket(name: String, buf: ByteBuf): Result<PacketType> {
@@ -30,8 +42,8 @@ class BukrsDecoder: ByteToMessageDecoder() {
 
     override fun decode(ctx: ChannelHandlerContext, src: ByteBuf, out: MutableList<Any>) {
         val cloned = src.copy()
-        if (cloned.readableBytes() < 6) return
-        val header = decodeHeader(cloned)
+        val header = decodeHeader(cloned) ?: return
+        if (header.first > MAX_FRAME_LEN) throw RuntimeException("Frame of ${header.first} bytes is larger than $MAX_FRAME_LEN")
         if (src.readableBytes() < cloned.readerIndex() + header.first) {
             return
         }
@@ -0,0 +1,148 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.Color
+import org.bukkit.Particle
+import org.bukkit.SoundCategory
+import org.bukkit.entity.Player
+import org.bukkit.inventory.ItemStack
+
+sealed class EffectTarget {
+    data class OnePlayer(val player: PlayerId): EffectTarget()
+    data class Players(val players: List<PlayerId>): EffectTarget()
+    data class World(val world: String): EffectTarget()
+
+    fun players(): List<Player> = when (this) {
+        is OnePlayer -> Bukkit.getOnlinePlayers().filter { it.entityId == player.id }
+        is Players -> players.mapNotNull { id -> Bukkit.getOnlinePlayers().find { it.entityId == id.id } }
+        is World -> Bukkit.getWorld(world)?.players ?: emptyList()
+    }
+}
+
+/**
+ * Colours are `0xRRGGBB`
+ */
+sealed class ParticleData {
+    object None: ParticleData()
+    data class Dust(val color: Int, val size: Float): ParticleData()
+    data class DustTransition(val from: Int, val to: Int, val size: Float): ParticleData()
+    data class Block(val data: BlockState): ParticleData()
+    data class Item(val material: Material): ParticleData()
+
+    /**
+     * Throws for unknown materials and bad states, which [BukrsMain.respondSync] answers with a [DefaultPackets.BukrsResError]
+     */
+    fun toBukkit(): Any? = when (this) {
+        is None -> null
+        is Dust -> Particle.DustOptions(Color.fromRGB(color), size)
+        is DustTransition -> Particle.DustTransition(Color.fromRGB(from), Color.fromRGB(to), size)
+        is Block -> blockData(data.state)
+        is Item -> ItemStack(material.toBukkit() ?: throw IllegalArgumentException("No material ${material.key}"))
+    }
+}
+
+fun effectCodecs() {
+    pushCodec(EffectTarget::class.java, object: TypeCodec<EffectTarget> {
+        override fun decode(src: ByteBuf): EffectTarget {
+            return when (src.readByte().toInt()) {
+                0 -> EffectTarget.OnePlayer(PlayerId(src.readInt()))
+                1 -> EffectTarget.Players((0 until readLength(src)).map { PlayerId(src.readInt()) })
+                2 -> EffectTarget.World(decodeType(String::class.java, src))
+                else -> throw RuntimeException("Invalid EffectTarget")
+            }
+        }
+
+        override fun encode(src: EffectTarget, target: ByteBuf) {
+            when (src) {
+                is EffectTarget.OnePlayer -> {
+                    target.writeByte(0)
+                    target.writeInt(src.player.id)
+                }
+                is EffectTarget.Players -> {
+                    target.writeByte(1)
+                    writeLength(src.players.size, target)
+                    src.players.forEach { target.writeInt(it.id) }
+                }
+                is EffectTarget.World -> {
+                    target.writeByte(2)
+                    encodeType(String::class.java, src.world, target)
+                }
+            }
+        }
+    })
+
+    pushCodec(ParticleData::class.java, object: TypeCodec<ParticleData> {
+        override fun decode(src: ByteBuf): ParticleData {
+            return when (src.readByte().toInt()) {
+                0 -> ParticleData.None
+                1 -> ParticleData.Dust(src.readInt(), src.readFloat())
+                2 -> ParticleData.DustTransition(src.readInt(), src.readInt(), src.readFloat())
+                3 -> ParticleData.Block(decodeType(BlockState::class.java, src))
+                4 -> ParticleData.Item(decodeType(Material::class.java, src))
+                else -> throw RuntimeException("Invalid ParticleData")
+            }
+        }
+
+        override fun encode(src: ParticleData, target: ByteBuf) {
+            when (src) {
+                is ParticleData.None -> target.writeByte(0)
+                is ParticleData.Dust -> {
+                    target.writeByte(1)
+                    target.writeInt(src.color)
+                    target.writeFloat(src.size)
+                }
+                is ParticleData.DustTransition -> {
+                    target.writeByte(2)
+                    target.writeInt(src.from)
+                    target.writeInt(src.to)
+                    target.writeFloat(src.size)
+                }
+                is ParticleData.Block -> {
+                    target.writeByte(3)
+                    encodeType(BlockState::class.java, src.data, target)
+                }
+                is ParticleData.Item -> {
+                    target.writeByte(4)
+                    encodeType(Material::class.java, src.material, target)
+                }
+            }
+        }
+    })
+}
+
+object BukrsEffects: BukrsListener {
+    @BukrsEventHandler
+    fun playSound(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqPlaySound) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val location = packet.location.location?.let { it.toLocation() ?: return@respondSync DefaultPackets.BukrsResEffect(0) }
+            val players = packet.target.players()
+            val category = SoundCategory.values()[packet.category.toInt()]
+            players.forEach { it.playSound(location ?: it.location, packet.sound, category, packet.volume, packet.pitch) }
+            DefaultPackets.BukrsResEffect(players.size)
+        }
+    }
+
+    @BukrsEventHandler
+    fun spawnParticle(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSpawnParticle) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val location = packet.location.toLocation() ?: return@respondSync DefaultPackets.BukrsResEffect(0)
+            val players = packet.target.players().filter { it.world == location.world }
+            val particle = Particle.valueOf(packet.particle)
+            val data = packet.data.toBukkit()
+            players.forEach { it.spawnParticle(particle, location, packet.count, packet.offset.x, packet.offset.y, packet.offset.z, packet.speed, data) }
+            DefaultPackets.BukrsResEffect(players.size)
+        }
+    }
+
+    @Suppress("DEPRECATION")
+    @BukrsEventHandler
+    fun sendTitle(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSendTitle) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val players = packet.target.players()
+            players.forEach { it.sendTitle(packet.title, packet.subtitle, packet.fadeIn, packet.stay, packet.fadeOut) }
+            DefaultPackets.BukrsResEffect(players.size)
+        }
+    }
+}
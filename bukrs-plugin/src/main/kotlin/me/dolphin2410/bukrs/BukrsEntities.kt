@@ -0,0 +1,329 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import io.netty.util.AttributeKey
+import org.bukkit.Bukkit
+import org.bukkit.Location
+import org.bukkit.NamespacedKey
+import org.bukkit.Registry
+import org.bukkit.entity.Entity
+import org.bukkit.entity.LivingEntity
+import org.bukkit.entity.Player
+import org.bukkit.event.EventHandler
+import org.bukkit.event.EventPriority
+import org.bukkit.event.Listener
+import org.bukkit.event.entity.EntityDeathEvent
+import org.bukkit.event.entity.EntitySpawnEvent
+import org.bukkit.event.entity.PlayerDeathEvent
+import org.bukkit.util.Vector
+
+data class EntityId(val id: Int)
+
+/**
+ * A presence byte followed by the id if present
+ */
+data class OptionalEntityId(val id: EntityId?)
+
+/**
+ * Namespaced entity type, e.g. `minecraft:zombie`
+ */
+data class EntityTypeKey(val key: String)
+
+data class LocationData(val world: String, val x: Double, val y: Double, val z: Double, val yaw: Float, val pitch: Float) {
+    /**
+     * Null if the world doesn't exist
+     */
+    fun toLocation() = Bukkit.getWorld(world)?.let { Location(it, x, y, z, yaw, pitch) }
+}
+
+/**
+ * A presence byte followed by the location if present
+ */
+data class OptionalLocationData(val location: LocationData?)
+
+data class VectorData(val x: Double, val y: Double, val z: Double)
+
+data class EntityData(val id: EntityId, val type: EntityTypeKey, val uniqueId: java.util.UUID, val location: LocationData, val customName: String, val flags: Byte)
+
+data class EntityDataList(val values: List<EntityData>)
+
+data class EntityTypeKeyList(val values: List<EntityTypeKey>)
+
+data class EntityFilter(val types: List<EntityTypeKey>, val includePlayers: Boolean, val limit: Int)
+
+/**
+ * Bit positions of the client's `EntityFlags`
+ */
+object EntityFlags {
+    const val GLOWING = 1
+    const val INVISIBLE = 1 shl 1
+    const val SILENT = 1 shl 2
+    const val NO_GRAVITY = 1 shl 3
+    const val INVULNERABLE = 1 shl 4
+    const val NO_AI = 1 shl 5
+    const val CUSTOM_NAME_VISIBLE = 1 shl 6
+}
+
+fun Location.toLocationData() = LocationData(world.name, x, y, z, yaw, pitch)
+
+fun Entity.flags(): Byte {
+    var flags = 0
+    if (isGlowing) flags = flags or EntityFlags.GLOWING
+    if (this is LivingEntity && isInvisible) flags = flags or EntityFlags.INVISIBLE
+    if (isSilent) flags = flags or EntityFlags.SILENT
+    if (!hasGravity()) flags = flags or EntityFlags.NO_GRAVITY
+    if (isInvulnerable) flags = flags or EntityFlags.INVULNERABLE
+    if (this is LivingEntity && !hasAI()) flags = flags or EntityFlags.NO_AI
+    if (isCustomNameVisible) flags = flags or EntityFlags.CUSTOM_NAME_VISIBLE
+    return flags.toByte()
+}
+
+fun Entity.applyFlags(flags: Int) {
+    isGlowing = flags and EntityFlags.GLOWING != 0
+    if (this is LivingEntity) isInvisible = flags and EntityFlags.INVISIBLE != 0
+    isSilent = flags and EntityFlags.SILENT != 0
+    setGravity(flags and EntityFlags.NO_GRAVITY == 0)
+    isInvulnerable = flags and EntityFlags.INVULNERABLE != 0
+    if (this is LivingEntity) setAI(flags and EntityFlags.NO_AI == 0)
+    isCustomNameVisible = flags and EntityFlags.CUSTOM_NAME_VISIBLE != 0
+}
+
+@Suppress("DEPRECATION")
+fun Entity.toEntityData() = EntityData(EntityId(entityId), EntityTypeKey(type.key.toString()), uniqueId, location.toLocationData(), customName ?: "", flags())
+
+/**
+ * Bukkit only looks entities up by UUID, so the loaded entities are searched
+ */
+fun findEntity(id: EntityId): Entity? = Bukkit.getWorlds().firstNotNullOfOrNull { world -> world.entities.find { it.entityId == id.id } }
+
+private fun readLocation(src: ByteBuf) = LocationData(decodeType(String::class.java, src), src.readDouble(), src.readDouble(), src.readDouble(), src.readFloat(), src.readFloat())
+
+private fun writeLocation(src: LocationData, target: ByteBuf) {
+    encodeType(String::class.java, src.world, target)
+    target.writeDouble(src.x)
+    target.writeDouble(src.y)
+    target.writeDouble(src.z)
+    target.writeFloat(src.yaw)
+    target.writeFloat(src.pitch)
+}
+
+private fun readEntityData(src: ByteBuf) = EntityData(EntityId(src.readInt()), EntityTypeKey(decodeType(String::class.java, src)), readUuid(src), readLocation(src), decodeType(String::class.java, src), src.readByte())
+
+private fun writeEntityData(src: EntityData, target: ByteBuf) {
+    target.writeInt(src.id.id)
+    encodeType(String::class.java, src.type.key, target)
+    writeUuid(src.uniqueId, target)
+    writeLocation(src.location, target)
+    encodeType(String::class.java, src.customName, target)
+    target.writeByte(src.flags.toInt())
+}
+
+fun entityCodecs() {
+    pushCodec(EntityId::class.java, object: TypeCodec<EntityId> {
+        override fun decode(src: ByteBuf) = EntityId(src.readInt())
+
+        override fun encode(src: EntityId, target: ByteBuf) {
+            target.writeInt(src.id)
+        }
+    })
+
+    pushCodec(OptionalEntityId::class.java, object: TypeCodec<OptionalEntityId> {
+        override fun decode(src: ByteBuf) = OptionalEntityId(if (src.readBoolean()) EntityId(src.readInt()) else null)
+
+        override fun encode(src: OptionalEntityId, target: ByteBuf) {
+            target.writeBoolean(src.id != null)
+            src.id?.let { target.writeInt(it.id) }
+        }
+    })
+
+    pushCodec(EntityTypeKey::class.java, object: TypeCodec<EntityTypeKey> {
+        override fun decode(src: ByteBuf) = EntityTypeKey(decodeType(String::class.java, src))
+
+        override fun encode(src: EntityTypeKey, target: ByteBuf) = encodeType(String::class.java, src.key, target)
+    })
+
+    pushCodec(LocationData::class.java, object: TypeCodec<LocationData> {
+        override fun decode(src: ByteBuf) = readLocation(src)
+
+        override fun encode(src: LocationData, target: ByteBuf) = writeLocation(src, target)
+    })
+
+    pushCodec(OptionalLocationData::class.java, object: TypeCodec<OptionalLocationData> {
+        override fun decode(src: ByteBuf) = OptionalLocationData(if (src.readBoolean()) readLocation(src) else null)
+
+        override fun encode(src: OptionalLocationData, target: ByteBuf) {
+            target.writeBoolean(src.location != null)
+            src.location?.let { writeLocation(it, target) }
+        }
+    })
+
+    pushCodec(VectorData::class.java, object: TypeCodec<VectorData> {
+        override fun decode(src: ByteBuf) = VectorData(src.readDouble(), src.readDouble(), src.readDouble())
+
+        override fun encode(src: VectorData, target: ByteBuf) {
+            target.writeDouble(src.x)
+            target.writeDouble(src.y)
+            target.writeDouble(src.z)
+        }
+    })
+
+    pushCodec(EntityData::class.java, object: TypeCodec<EntityData> {
+        override fun decode(src: ByteBuf) = readEntityData(src)
+
+        override fun encode(src: EntityData, target: ByteBuf) = writeEntityData(src, target)
+    })
+
+    pushCodec(EntityDataList::class.java, object: TypeCodec<EntityDataList> {
+        override fun decode(src: ByteBuf) = EntityDataList((0 until readLength(src)).map { readEntityData(src) })
+
+        override fun encode(src: EntityDataList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { writeEntityData(it, target) }
+        }
+    })
+
+    pushCodec(EntityTypeKeyList::class.java, object: TypeCodec<EntityTypeKeyList> {
+        override fun decode(src: ByteBuf) = EntityTypeKeyList((0 until readLength(src)).map { EntityTypeKey(decodeType(String::class.java, src)) })
+
+        override fun encode(src: EntityTypeKeyList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { encodeType(String::class.java, it.key, target) }
+        }
+    })
+
+    pushCodec(EntityFilter::class.java, object: TypeCodec<EntityFilter> {
+        override fun decode(src: ByteBuf): EntityFilter {
+            val types = (0 until readLength(src)).map { EntityTypeKey(decodeType(String::class.java, src)) }
+            return EntityFilter(types, src.readByte().toInt() != 0, src.readInt())
+        }
+
+        override fun encode(src: EntityFilter, target: ByteBuf) {
+            writeLength(src.types.size, target)
+            src.types.forEach { encodeType(String::class.java, it.key, target) }
+            target.writeByte(if (src.includePlayers) 1 else 0)
+            target.writeInt(src.limit)
+        }
+    })
+}
+
+object BukrsEntities: BukrsListener, Listener {
+    /**
+     * Entity types a connection gets EntitySpawn for, every type if unset or empty
+     */
+    private val SpawnTypesKey = AttributeKey.valueOf<Set<String>>("BukrsSpawnTypesKey")
+
+    private fun update(ctx: ChannelHandlerContext, payloadId: Int, id: EntityId, action: (Entity) -> Unit) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val entity = findEntity(id)
+            entity?.let(action)
+            DefaultPackets.BukrsResEntityUpdate(entity != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun spawn(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSpawnEntity) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val type = NamespacedKey.fromString(packet.entityType.key)?.let { Registry.ENTITY_TYPE.get(it) }
+            val location = packet.location.toLocation()
+            val entity = if (type != null && location != null) location.world.spawnEntity(location, type) else null
+            DefaultPackets.BukrsResEntities(EntityDataList(listOfNotNull(entity?.toEntityData())))
+        }
+    }
+
+    @BukrsEventHandler
+    fun nearby(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqNearbyEntities) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val center = packet.location.toLocation() ?: return@respondSync DefaultPackets.BukrsResEntities(EntityDataList(emptyList()))
+            val filter = packet.filter
+            val types = filter.types.map { it.key }.toSet()
+            var entities = center.world.getNearbyEntities(center, packet.radius, packet.radius, packet.radius)
+                .filter { it.location.distance(center) <= packet.radius }
+                .filter { filter.includePlayers || it !is Player }
+                .filter { types.isEmpty() || it.type.key.toString() in types }
+                .sortedBy { it.location.distanceSquared(center) }
+            if (filter.limit > 0) entities = entities.take(filter.limit)
+            DefaultPackets.BukrsResEntities(EntityDataList(entities.map { it.toEntityData() }))
+        }
+    }
+
+    @BukrsEventHandler
+    fun byId(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqEntityById) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            DefaultPackets.BukrsResEntities(EntityDataList(listOfNotNull(findEntity(packet.entityId)?.toEntityData())))
+        }
+    }
+
+    @BukrsEventHandler
+    fun teleport(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqTeleportEntity) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val location = packet.location.toLocation()
+            val entity = location?.let { findEntity(packet.entityId) }    // An unknown world is answered like a missing entity
+            entity?.teleport(location)
+            DefaultPackets.BukrsResEntityUpdate(entity != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun velocity(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetVelocity) {
+        update(ctx, payloadId, packet.entityId) { it.velocity = Vector(packet.velocity.x, packet.velocity.y, packet.velocity.z) }
+    }
+
+    /**
+     * Players can't be removed, they are kicked or banned instead. Those requests are answered with `removed` false.
+     */
+    @BukrsEventHandler
+    fun remove(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveEntity) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val entity = findEntity(packet.entityId)
+            val removable = entity != null && entity !is Player
+            if (removable) entity?.remove()
+            DefaultPackets.BukrsResRemoveEntity(entity != null, removable)
+        }
+    }
+
+    @Suppress("DEPRECATION")
+    @BukrsEventHandler
+    fun customName(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetCustomName) {
+        update(ctx, payloadId, packet.entityId) { it.customName = packet.name.ifEmpty { null } }
+    }
+
+    @BukrsEventHandler
+    fun flags(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetEntityFlags) {
+        update(ctx, payloadId, packet.entityId) { it.applyFlags(packet.flags.toInt()) }
+    }
+
+    @BukrsEventHandler
+    fun changeFlags(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqChangeEntityFlags) {
+        update(ctx, payloadId, packet.entityId) { it.applyFlags((it.flags().toInt() or packet.set.toInt()) and packet.clear.toInt().inv()) }
+    }
+
+    @BukrsEventHandler
+    fun spawnTypes(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSpawnTypes) {
+        ctx.channel().attr(SpawnTypesKey).set(packet.types.values.map { it.key }.toSet())
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResSpawnTypes())
+    }
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onSpawn(event: EntitySpawnEvent) {
+        if (!BukrsCancellable.isSubscribed("EntitySpawn")) return
+
+        val type = event.entityType.key.toString()
+        val wants = { ctx: ChannelHandlerContext -> ctx.channel().attr(SpawnTypesKey).get().let { it.isNullOrEmpty() || type in it } }
+        val verdict = BukrsCancellable.verdict("EntitySpawn", DefaultPackets.BukrsResEntitySpawn(false), wants) {
+            DefaultPackets.BukrsSDEntitySpawn(event.entity.toEntityData())
+        }
+        event.isCancelled = verdict.cancelled
+    }
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onDeath(event: EntityDeathEvent) {
+        if (event is PlayerDeathEvent || !BukrsCancellable.isSubscribed("EntityDeath")) return    // Players fire PlayerDeath
+
+        val verdict = BukrsCancellable.verdict("EntityDeath", DefaultPackets.BukrsResEntityDeath(false, event.droppedExp)) {
+            DefaultPackets.BukrsSDEntityDeath(event.entity.toEntityData(), it.droppedExp)
+        }
+        event.isCancelled = verdict.cancelled
+        event.droppedExp = verdict.droppedExp
+    }
+}
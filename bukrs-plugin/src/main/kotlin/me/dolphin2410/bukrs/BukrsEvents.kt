@@ -13,6 +13,13 @@ object BukrsEvents {
         targets.add(l)
     }
 
+    /**
+     * @return true if some handler takes the packet
+     */
+    fun handles(packet: PacketType) = targets.any { target ->
+        target::class.java.declaredMethods.any { it.isAnnotationPresent(BukrsEventHandler::class.java) && it.parameterTypes[2].isInstance(packet) }
+    }
+
     fun dispatch(ctx: ChannelHandlerContext, p: Pair<Int, PacketType>) {
         for (target in targets) {
             for (method in target::class.java.declaredMethods) {
@@ -0,0 +1,161 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.World
+import org.bukkit.block.data.BlockData
+import org.bukkit.scheduler.BukkitRunnable
+import java.util.concurrent.ConcurrentHashMap
+
+/**
+ * Inclusive cuboid, [min] and [max] are normalized by the client
+ */
+data class Region(val world: String, val min: BlockPos, val max: BlockPos) {
+    val sizeX get() = max.x - min.x + 1
+    val sizeY get() = max.y - min.y + 1
+    val sizeZ get() = max.z - min.z + 1
+    val volume get() = sizeX.toLong() * sizeY * sizeZ
+}
+
+sealed class RegionOperation(val region: Region) {
+    class Fill(region: Region, val data: BlockState): RegionOperation(region)
+    class Replace(region: Region, val from: BlockState, val to: BlockState): RegionOperation(region)
+    class Clone(region: Region, val destination: BlockPos): RegionOperation(region)
+}
+
+internal fun readRegion(src: ByteBuf): Region {
+    val min = readBlockPos(src)
+    val max = readBlockPos(src)
+    return Region(min.world, min, max)
+}
+
+internal fun writeRegion(src: Region, target: ByteBuf) {
+    writeBlockPos(src.min, target)
+    writeBlockPos(src.max, target)
+}
+
+fun jobCodecs() {
+    pushCodec(Region::class.java, object: TypeCodec<Region> {
+        override fun decode(src: ByteBuf) = readRegion(src)
+
+        override fun encode(src: Region, target: ByteBuf) = writeRegion(src, target)
+    })
+
+    pushCodec(RegionOperation::class.java, object: TypeCodec<RegionOperation> {
+        override fun decode(src: ByteBuf): RegionOperation {
+            return when (src.readByte().toInt()) {
+                0 -> RegionOperation.Fill(readRegion(src), decodeType(BlockState::class.java, src))
+                1 -> RegionOperation.Replace(readRegion(src), decodeType(BlockState::class.java, src), decodeType(BlockState::class.java, src))
+                2 -> RegionOperation.Clone(readRegion(src), readBlockPos(src))
+                else -> throw RuntimeException("Invalid RegionOperation")
+            }
+        }
+
+        override fun encode(src: RegionOperation, target: ByteBuf) {
+            when (src) {
+                is RegionOperation.Fill -> {
+                    target.writeByte(0)
+                    writeRegion(src.region, target)
+                    encodeType(BlockState::class.java, src.data, target)
+                }
+                is RegionOperation.Replace -> {
+                    target.writeByte(1)
+                    writeRegion(src.region, target)
+                    encodeType(BlockState::class.java, src.from, target)
+                    encodeType(BlockState::class.java, src.to, target)
+                }
+                is RegionOperation.Clone -> {
+                    target.writeByte(2)
+                    writeRegion(src.region, target)
+                    writeBlockPos(src.destination, target)
+                }
+            }
+        }
+    })
+}
+
+/**
+ * Walks the region a few blocks per tick, reporting progress after every batch.
+ * Throws on construction for unknown worlds and bad states, so nothing is scheduled for them.
+ */
+class RegionJob(private val ctx: ChannelHandlerContext, private val jobId: Int, private val operation: RegionOperation, private val blocksPerTick: Int, private val physics: Boolean): BukkitRunnable() {
+    private val region = operation.region
+    private val world: World = worldNamed(region.world)
+    private val fill: BlockData? = (operation as? RegionOperation.Fill)?.let { blockData(it.data.state) }
+    private val from: BlockData? = (operation as? RegionOperation.Replace)?.let { blockData(it.from.state) }
+    private val to: BlockData? = (operation as? RegionOperation.Replace)?.let { blockData(it.to.state) }
+    private val target: World? = (operation as? RegionOperation.Clone)?.let { worldNamed(it.destination.world) }
+
+    // Clones walk away from the destination so overlapping regions copy the original blocks
+    private val reverseX = operation is RegionOperation.Clone && operation.destination.x > region.min.x
+    private val reverseY = operation is RegionOperation.Clone && operation.destination.y > region.min.y
+    private val reverseZ = operation is RegionOperation.Clone && operation.destination.z > region.min.z
+
+    private var done = 0L
+    private var changed = 0L
+    @Volatile var cancelled = false
+
+    private fun axis(offset: Int, size: Int, reverse: Boolean) = if (reverse) size - 1 - offset else offset
+
+    override fun run() {
+        val end = minOf(done + blocksPerTick, region.volume)
+        while (done < end && !cancelled) {
+            val dx = axis((done % region.sizeX).toInt(), region.sizeX, reverseX)
+            val dz = axis(((done / region.sizeX) % region.sizeZ).toInt(), region.sizeZ, reverseZ)
+            val dy = axis((done / (region.sizeX.toLong() * region.sizeZ)).toInt(), region.sizeY, reverseY)
+            val block = world.getBlockAt(region.min.x + dx, region.min.y + dy, region.min.z + dz)
+
+            when (operation) {
+                is RegionOperation.Fill -> if (block.blockData != fill) {
+                    block.setBlockData(fill!!, physics)
+                    changed++
+                }
+                is RegionOperation.Replace -> if (block.blockData == from) {
+                    block.setBlockData(to!!, physics)
+                    changed++
+                }
+                is RegionOperation.Clone -> {
+                    val destination = target!!.getBlockAt(operation.destination.x + dx, operation.destination.y + dy, operation.destination.z + dz)
+                    if (destination.blockData != block.blockData) {
+                        destination.setBlockData(block.blockData, physics)
+                        changed++
+                    }
+                }
+            }
+            done++
+        }
+
+        if (cancelled || done >= region.volume) {
+            cancel()
+            BukrsJobs.jobs.remove(ctx to jobId, this)
+            ctx.pipeline().writeAndFlush(0 to DefaultPackets.BukrsSDJobDone(jobId, changed, cancelled))
+        } else {
+            ctx.pipeline().writeAndFlush(0 to DefaultPackets.BukrsSDJobProgress(jobId, done, region.volume))
+        }
+    }
+}
+
+/**
+ * Job ids are per client, and a client's jobs are cancelled when it disconnects
+ */
+object BukrsJobs: BukrsListener {
+    val jobs = ConcurrentHashMap<Pair<ChannelHandlerContext, Int>, RegionJob>()
+
+    @BukrsEventHandler
+    fun startJob(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqStartJob) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val key = ctx to packet.jobId
+            val job = RegionJob(ctx, packet.jobId, packet.operation, packet.blocksPerTick, packet.physics)
+            if (jobs.putIfAbsent(key, job) != null) throw IllegalArgumentException("Job ${packet.jobId} is still running")
+            ctx.channel().closeFuture().addListener { if (jobs.remove(key, job)) job.cancelled = true }
+            job.runTaskTimer(BukrsMain.instance, 0L, 1L)    // First runs next tick, after the response below
+            DefaultPackets.BukrsResStartJob(packet.operation.region.volume)
+        }
+    }
+
+    @BukrsEventHandler
+    fun cancelJob(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqCancelJob) {
+        jobs[ctx to packet.jobId]?.cancelled = true
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResCancelJob())
+    }
+}
@@ -3,42 +3,136 @@ package me.dolphin2410.bukrs
 import io.netty.channel.ChannelHandlerContext
 import io.netty.util.AttributeKey
 import org.bukkit.Bukkit
+import org.bukkit.entity.Player
+import org.bukkit.event.EventHandler
+import org.bukkit.event.Listener
+import org.bukkit.event.player.PlayerJoinEvent
+import org.bukkit.event.player.PlayerQuitEvent
 import org.bukkit.plugin.java.JavaPlugin
 import java.util.Random
+import java.util.logging.Level
 
 class BukrsMain: JavaPlugin() {
     companion object {
+        /**
+         * Version of the wire format, compared in the BukrsReqAPI handshake
+         */
+        const val PROTOCOL_VERSION = 2
+
         @JvmStatic
         val BukrsClientIdKey = AttributeKey.valueOf<Int>("BukrsClientIdKey")!!
+
+        lateinit var instance: BukrsMain
+            private set
+
+        /**
+         * Runs [task] on the main thread and answers [payloadId] with its result, or with a [DefaultPackets.BukrsResError] if it throws.
+         * Already on the main thread, as in a batch, it runs right away so the batch stays within one tick.
+         */
+        fun respondSync(ctx: ChannelHandlerContext, payloadId: Int, task: () -> PacketType) {
+            val respond = Runnable {
+                val response = try {
+                    task()
+                } catch (e: Exception) {
+                    instance.logger.log(Level.WARNING, "Request $payloadId failed", e)
+                    DefaultPackets.BukrsResError(e.message ?: e.toString())
+                }
+                ctx.pipeline().writeAndFlush(payloadId to response)
+            }
+            if (Bukkit.isPrimaryThread()) {
+                respond.run()
+            } else {
+                Bukkit.getScheduler().runTask(instance, respond)
+            }
+        }
     }
 
     val clients = ArrayList<ChannelHandlerContext>()
 
     override fun onEnable() {
+        instance = this
         defaultCodecs()
+        playerCodecs()
+        commandCodecs()
+        blockCodecs()
+        worldCodecs()
+        jobCodecs()
+        entityCodecs()
+        combatCodecs()
+        batchCodecs()
+        scoreboardCodecs()
+        bossBarCodecs()
+        effectCodecs()
+        permissionCodecs()
+        persistentDataCodecs()
 
         BukrsEvents.addListener(object: BukrsListener {
             @BukrsEventHandler
             fun apiReq(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqAPI) {
                 val random = Random()
                 val id = random.nextInt(Int.MAX_VALUE)
-                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResAPI(id)).sync()
+                if (packet.protocolVersion != PROTOCOL_VERSION) {
+                    logger.warning("Refused a client speaking protocol version ${packet.protocolVersion}, expected $PROTOCOL_VERSION")
+                    ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResAPI(id, PROTOCOL_VERSION)).sync()
+                    ctx.close()
+                    return
+                }
+                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResAPI(id, PROTOCOL_VERSION)).sync()
                 clients.add(ctx)
                 ctx.channel().attr(BukrsClientIdKey).set(id)
+                Bukkit.getOnlinePlayers().forEach { ctx.pipeline().writeAndFlush(0 to DefaultPackets.BukrsSDPlayerJoin(it.toPlayerData())) }
             }
 
             @BukrsEventHandler
             fun bukrsPlayers(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqOnlinePlayers) {
-                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResOnlinePlayers(Bukkit.getOnlinePlayers().map { it.entityId }))
+                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResOnlinePlayers(PlayerIdList(Bukkit.getOnlinePlayers().map { PlayerId(it.entityId) })))
             }
 
             @BukrsEventHandler
             fun bukrsPlayerById(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqPlayerById) {
                 // TODO error handling
-                val player = Bukkit.getOnlinePlayers().find { it.entityId == packet.player }!!
-                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResPlayerData(PlayerData(player.entityId, player.name, player.uniqueId)))
+                val player = Bukkit.getOnlinePlayers().find { it.entityId == packet.playerId.id }!!
+                ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResPlayerData(player.toPlayerData()))
             }
         })
+        BukrsEvents.addListener(BukrsCommands)
+        BukrsEvents.addListener(BukrsCancellable)
+        BukrsEvents.addListener(BukrsWorlds)
+        BukrsEvents.addListener(BukrsJobs)
+        BukrsEvents.addListener(BukrsEntities)
+        BukrsEvents.addListener(BukrsMovement)
+        BukrsEvents.addListener(BukrsTicks)
+        BukrsEvents.addListener(BukrsBatches)
+        BukrsEvents.addListener(BukrsScoreboards)
+        BukrsEvents.addListener(BukrsBossBars)
+        BukrsEvents.addListener(BukrsEffects)
+        BukrsEvents.addListener(BukrsPermissions)
+        BukrsEvents.addListener(BukrsPersistentData)
+        server.pluginManager.registerEvents(BukrsChat, this)
+        server.pluginManager.registerEvents(BukrsBlocks, this)
+        server.pluginManager.registerEvents(BukrsEntities, this)
+        server.pluginManager.registerEvents(BukrsCombat, this)
+        server.pluginManager.registerEvents(BukrsMovement, this)
+        server.pluginManager.registerEvents(BukrsTicks, this)
+        server.pluginManager.registerEvents(BukrsScoreboards, this)
+        server.pluginManager.registerEvents(BukrsPermissions, this)
+        server.pluginManager.registerEvents(object: Listener {
+            @EventHandler
+            fun onJoin(event: PlayerJoinEvent) {
+                broadcast(DefaultPackets.BukrsSDPlayerJoin(event.player.toPlayerData()))
+            }
+
+            @EventHandler
+            fun onQuit(event: PlayerQuitEvent) {
+                broadcast(DefaultPackets.BukrsSDPlayerQuit(PlayerId(event.player.entityId)))
+            }
+        }, this)
         NettyServer().run()
     }
-}
\ No newline at end of file
+
+    fun broadcast(packet: PacketType) {
+        clients.forEach { it.pipeline().writeAndFlush(0 to packet) }
+    }
+}
+
+fun Player.toPlayerData() = PlayerData(entityId, name, uniqueId)
\ No newline at end of file
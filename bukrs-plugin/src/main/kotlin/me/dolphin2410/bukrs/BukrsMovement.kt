@@ -0,0 +1,98 @@
+package me.dolphin2410.bukrs
+
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Location
+import org.bukkit.event.EventHandler
+import org.bukkit.event.EventPriority
+import org.bukkit.event.Listener
+import org.bukkit.event.player.PlayerMoveEvent
+import org.bukkit.event.player.PlayerQuitEvent
+import java.util.UUID
+import java.util.concurrent.ConcurrentHashMap
+
+fun Region.contains(location: Location) = location.world.name == world
+        && location.blockX in min.x..max.x
+        && location.blockY in min.y..max.y
+        && location.blockZ in min.z..max.z
+
+/**
+ * Only block changes are looked at. Region crossings are always sent, plain moves at most once per [interval] and player.
+ * Region names are per client, and crossings are only sent to the client that defined the region.
+ */
+object BukrsMovement: BukrsListener, Listener {
+    private data class WatchedRegion(val ctx: ChannelHandlerContext, val region: Region)
+
+    private val regions = ConcurrentHashMap<Pair<ChannelHandlerContext, String>, WatchedRegion>()
+    private val lastMoves = ConcurrentHashMap<UUID, Long>()
+    @Volatile private var interval = 0L
+
+    @BukrsEventHandler
+    fun defineRegion(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqDefineRegion) {
+        val key = ctx to packet.name
+        val watched = WatchedRegion(ctx, packet.region)
+        regions[key] = watched
+        ctx.channel().closeFuture().addListener { regions.remove(key, watched) }
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResDefineRegion())
+    }
+
+    @BukrsEventHandler
+    fun removeRegion(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveRegion) {
+        val found = regions.remove(ctx to packet.name) != null
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResRemoveRegion(found))
+    }
+
+    @BukrsEventHandler
+    fun moveInterval(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqMoveInterval) {
+        interval = packet.intervalMs.toLong()
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResMoveInterval())
+    }
+
+    /**
+     * @return whether a client kept the player from crossing the border
+     */
+    private fun crossing(event: PlayerMoveEvent, owner: ChannelHandlerContext, name: String, entering: Boolean): Boolean {
+        val player = PlayerId(event.player.entityId)
+        val from = event.from.toLocationData()
+        val to = event.to.toLocationData()
+        return if (entering) {
+            if (!BukrsCancellable.isSubscribed("RegionEnter")) return false
+            BukrsCancellable.verdict("RegionEnter", DefaultPackets.BukrsResRegionEnter(false), { it == owner }) {
+                DefaultPackets.BukrsSDRegionEnter(player, name, from, to)
+            }.cancelled
+        } else {
+            if (!BukrsCancellable.isSubscribed("RegionLeave")) return false
+            BukrsCancellable.verdict("RegionLeave", DefaultPackets.BukrsResRegionLeave(false), { it == owner }) {
+                DefaultPackets.BukrsSDRegionLeave(player, name, from, to)
+            }.cancelled
+        }
+    }
+
+    @EventHandler(priority = EventPriority.HIGH, ignoreCancelled = true)
+    fun onMove(event: PlayerMoveEvent) {
+        if (!event.hasChangedBlock()) return
+
+        for ((key, watched) in regions) {
+            val wasInside = watched.region.contains(event.from)
+            val inside = watched.region.contains(event.to)
+            if (wasInside != inside && crossing(event, watched.ctx, key.second, inside)) {
+                event.isCancelled = true    // Puts the player back at `from`
+                return
+            }
+        }
+
+        if (!BukrsCancellable.isSubscribed("PlayerMove")) return
+        val now = System.currentTimeMillis()
+        if (now - (lastMoves[event.player.uniqueId] ?: 0L) < interval) return
+        lastMoves[event.player.uniqueId] = now
+
+        val verdict = BukrsCancellable.verdict("PlayerMove", DefaultPackets.BukrsResPlayerMove(false)) {
+            DefaultPackets.BukrsSDPlayerMove(PlayerId(event.player.entityId), event.from.toLocationData(), event.to.toLocationData())
+        }
+        event.isCancelled = verdict.cancelled
+    }
+
+    @EventHandler
+    fun onQuit(event: PlayerQuitEvent) {
+        lastMoves.remove(event.player.uniqueId)
+    }
+}
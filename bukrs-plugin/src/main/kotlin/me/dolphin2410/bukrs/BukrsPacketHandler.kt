@@ -5,6 +5,7 @@ import io.netty.channel.SimpleChannelInboundHandler
 
 class BukrsPacketHandler: SimpleChannelInboundHandler<Pair<Int, PacketType>>() {
     override fun channelRead0(ctx: ChannelHandlerContext, msg: Pair<Int, PacketType>) {
+        if (msg.first != 0 && BukrsRequests.complete(msg.first, msg.second)) return
         BukrsEvents.dispatch(ctx, msg)
     }
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.entity.Player
+import org.bukkit.event.EventHandler
+import org.bukkit.event.Listener
+import org.bukkit.event.player.PlayerQuitEvent
+import org.bukkit.permissions.PermissionAttachment
+import java.util.concurrent.ConcurrentHashMap
+
+data class BooleanList(val values: List<Boolean>)
+
+fun permissionCodecs() {
+    pushCodec(BooleanList::class.java, object: TypeCodec<BooleanList> {
+        override fun decode(src: ByteBuf) = BooleanList((0 until readLength(src)).map { src.readBoolean() })
+
+        override fun encode(src: BooleanList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { target.writeBoolean(it) }
+        }
+    })
+}
+
+/**
+ * Permission checks and the attachments clients add. Every change is broadcast as BukrsSDPermissionChange
+ * before the response, so clients drop their cached checks first. Attachment ids are per client, and its
+ * attachments are removed when it disconnects.
+ */
+object BukrsPermissions: BukrsListener, Listener {
+    private val attachments = ConcurrentHashMap<Pair<ChannelHandlerContext, Int>, PermissionAttachment>()
+
+    private fun findPlayer(id: PlayerId): Player? = Bukkit.getOnlinePlayers().find { it.entityId == id.id }
+
+    private fun changed(player: Player) {
+        BukrsMain.instance.broadcast(DefaultPackets.BukrsSDPermissionChange(PlayerId(player.entityId)))
+    }
+
+    private fun detach(attachment: PermissionAttachment) {
+        attachment.remove()
+        (attachment.permissible as? Player)?.let(::changed)
+    }
+
+    @BukrsEventHandler
+    fun hasPermission(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqHasPermission) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            DefaultPackets.BukrsResHasPermission(player != null, player?.hasPermission(packet.permission) == true)
+        }
+    }
+
+    @BukrsEventHandler
+    fun hasPermissions(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqHasPermissions) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            val values = player?.let { packet.permissions.values.map { permission -> it.hasPermission(permission) } } ?: emptyList()
+            DefaultPackets.BukrsResHasPermissions(player != null, BooleanList(values))
+        }
+    }
+
+    @BukrsEventHandler
+    fun addAttachment(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqAddAttachment) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            if (player != null) {
+                val key = ctx to packet.attachmentId
+                val attachment = player.addAttachment(BukrsMain.instance)
+                attachments.put(key, attachment)?.let(::detach)
+                ctx.channel().closeFuture().addListener {
+                    if (attachments.remove(key, attachment)) Bukkit.getScheduler().runTask(BukrsMain.instance, Runnable { detach(attachment) })
+                }
+            }
+            DefaultPackets.BukrsResAttachment(player != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun setAttachmentPermission(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetAttachmentPermission) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val attachment = attachments[ctx to packet.attachmentId]
+            attachment?.setPermission(packet.permission, packet.value)
+            (attachment?.permissible as? Player)?.let(::changed)
+            DefaultPackets.BukrsResAttachment(attachment != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun unsetAttachmentPermission(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqUnsetAttachmentPermission) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val attachment = attachments[ctx to packet.attachmentId]
+            attachment?.unsetPermission(packet.permission)
+            (attachment?.permissible as? Player)?.let(::changed)
+            DefaultPackets.BukrsResAttachment(attachment != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun removeAttachment(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveAttachment) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val attachment = attachments.remove(ctx to packet.attachmentId)
+            attachment?.let(::detach)
+            DefaultPackets.BukrsResAttachment(attachment != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun isOp(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqIsOp) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            DefaultPackets.BukrsResOp(player != null, player?.isOp == true)
+        }
+    }
+
+    @BukrsEventHandler
+    fun setOp(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetOp) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            player?.isOp = packet.op
+            player?.let(::changed)
+            DefaultPackets.BukrsResOp(player != null, player?.isOp == true)
+        }
+    }
+
+    @EventHandler
+    fun onQuit(event: PlayerQuitEvent) {
+        attachments.values.removeIf { it.permissible == event.player }
+    }
+}
@@ -0,0 +1,255 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.NamespacedKey
+import org.bukkit.persistence.PersistentDataContainer
+import org.bukkit.persistence.PersistentDataType
+
+data class DataKey(val key: String)
+
+data class DataKeyList(val values: List<DataKey>)
+
+/**
+ * [kind] indexes [kinds]. [value] is the matching Kotlin type: Byte to Double, String, ByteArray, IntArray, LongArray or [PersistentContainerData].
+ */
+data class PersistentValue(val kind: Byte, val value: Any)
+
+data class OptionalPersistentValue(val value: PersistentValue?)
+
+data class PersistentContainerData(val entries: Map<String, PersistentValue>)
+
+sealed class DataHolder {
+    data class OfEntity(val entityId: EntityId): DataHolder()
+    data class OfChunk(val world: String, val x: Int, val z: Int): DataHolder()
+    data class OfItem(val item: ItemData): DataHolder()
+}
+
+private val kinds: List<PersistentDataType<*, *>> = listOf(
+    PersistentDataType.BYTE, PersistentDataType.SHORT, PersistentDataType.INTEGER, PersistentDataType.LONG, PersistentDataType.FLOAT, PersistentDataType.DOUBLE,
+    PersistentDataType.STRING, PersistentDataType.BYTE_ARRAY, PersistentDataType.INTEGER_ARRAY, PersistentDataType.LONG_ARRAY, PersistentDataType.TAG_CONTAINER
+)
+
+private fun readValue(src: ByteBuf): PersistentValue {
+    val kind = src.readByte()
+    val value: Any = when (kind.toInt()) {
+        0 -> src.readByte()
+        1 -> src.readShort()
+        2 -> src.readInt()
+        3 -> src.readLong()
+        4 -> src.readFloat()
+        5 -> src.readDouble()
+        6 -> decodeType(String::class.java, src)
+        7 -> ByteArray(readLength(src)).also { src.readBytes(it) }
+        8 -> IntArray(readLength(src)) { src.readInt() }
+        9 -> LongArray(readLength(src)) { src.readLong() }
+        10 -> readContainer(src)
+        else -> throw RuntimeException("Invalid PersistentKind")
+    }
+    return PersistentValue(kind, value)
+}
+
+private fun writeValue(src: PersistentValue, target: ByteBuf) {
+    target.writeByte(src.kind.toInt())
+    when (val value = src.value) {
+        is Byte -> target.writeByte(value.toInt())
+        is Short -> target.writeShort(value.toInt())
+        is Int -> target.writeInt(value)
+        is Long -> target.writeLong(value)
+        is Float -> target.writeFloat(value)
+        is Double -> target.writeDouble(value)
+        is String -> encodeType(String::class.java, value, target)
+        is ByteArray -> {
+            writeLength(value.size, target)
+            target.writeBytes(value)
+        }
+        is IntArray -> {
+            writeLength(value.size, target)
+            value.forEach { target.writeInt(it) }
+        }
+        is LongArray -> {
+            writeLength(value.size, target)
+            value.forEach { target.writeLong(it) }
+        }
+        is PersistentContainerData -> writeContainer(value, target)
+    }
+}
+
+private fun readContainer(src: ByteBuf) = PersistentContainerData((0 until readLength(src)).associate { decodeType(String::class.java, src) to readValue(src) })
+
+private fun writeContainer(src: PersistentContainerData, target: ByteBuf) {
+    writeLength(src.entries.size, target)
+    src.entries.forEach { (key, value) ->
+        encodeType(String::class.java, key, target)
+        writeValue(value, target)
+    }
+}
+
+fun persistentDataCodecs() {
+    pushCodec(DataKey::class.java, object: TypeCodec<DataKey> {
+        override fun decode(src: ByteBuf) = DataKey(decodeType(String::class.java, src))
+
+        override fun encode(src: DataKey, target: ByteBuf) = encodeType(String::class.java, src.key, target)
+    })
+
+    pushCodec(DataKeyList::class.java, object: TypeCodec<DataKeyList> {
+        override fun decode(src: ByteBuf) = DataKeyList((0 until readLength(src)).map { DataKey(decodeType(String::class.java, src)) })
+
+        override fun encode(src: DataKeyList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { encodeType(String::class.java, it.key, target) }
+        }
+    })
+
+    pushCodec(PersistentValue::class.java, object: TypeCodec<PersistentValue> {
+        override fun decode(src: ByteBuf) = readValue(src)
+
+        override fun encode(src: PersistentValue, target: ByteBuf) = writeValue(src, target)
+    })
+
+    pushCodec(OptionalPersistentValue::class.java, object: TypeCodec<OptionalPersistentValue> {
+        override fun decode(src: ByteBuf) = OptionalPersistentValue(if (src.readBoolean()) readValue(src) else null)
+
+        override fun encode(src: OptionalPersistentValue, target: ByteBuf) {
+            target.writeBoolean(src.value != null)
+            src.value?.let { writeValue(it, target) }
+        }
+    })
+
+    pushCodec(DataHolder::class.java, object: TypeCodec<DataHolder> {
+        override fun decode(src: ByteBuf): DataHolder {
+            return when (src.readByte().toInt()) {
+                0 -> DataHolder.OfEntity(EntityId(src.readInt()))
+                1 -> DataHolder.OfChunk(decodeType(String::class.java, src), src.readInt(), src.readInt())
+                2 -> DataHolder.OfItem(decodeType(ItemData::class.java, src))
+                else -> throw RuntimeException("Invalid DataHolder")
+            }
+        }
+
+        override fun encode(src: DataHolder, target: ByteBuf) {
+            when (src) {
+                is DataHolder.OfEntity -> {
+                    target.writeByte(0)
+                    target.writeInt(src.entityId.id)
+                }
+                is DataHolder.OfChunk -> {
+                    target.writeByte(1)
+                    encodeType(String::class.java, src.world, target)
+                    target.writeInt(src.x)
+                    target.writeInt(src.z)
+                }
+                is DataHolder.OfItem -> {
+                    target.writeByte(2)
+                    encodeType(ItemData::class.java, src.item, target)
+                }
+            }
+        }
+    })
+}
+
+/**
+ * Reads and writes the persistent data containers of entities, chunks and items.
+ * Items are copies sent by the client, so changed items are sent back instead of being stored.
+ * Requests with a key Bukkit doesn't accept, also inside a container, are answered as if the holder was gone.
+ */
+object BukrsPersistentData: BukrsListener {
+    private fun read(container: PersistentDataContainer, key: NamespacedKey, kind: Int): PersistentValue? {
+        if (!container.has(key, kinds[kind])) return null
+        val value: Any = when (kind) {
+            10 -> {
+                val nested = container.get(key, PersistentDataType.TAG_CONTAINER)!!
+                PersistentContainerData(nested.keys.associate { nestedKey -> nestedKey.toString() to kinds.indices.firstNotNullOf { read(nested, nestedKey, it) } })
+            }
+            else -> container.get(key, kinds[kind])!!
+        }
+        return PersistentValue(kind.toByte(), value)
+    }
+
+    @Suppress("UNCHECKED_CAST")
+    private fun write(container: PersistentDataContainer, key: NamespacedKey, value: PersistentValue) {
+        val kind = value.kind.toInt()
+        if (kind == 10) {
+            val nested = container.adapterContext.newPersistentDataContainer()
+            (value.value as PersistentContainerData).entries.forEach { (nestedKey, nestedValue) -> write(nested, toBukkit(nestedKey)!!, nestedValue) }    // Checked by validKeys
+            container.set(key, PersistentDataType.TAG_CONTAINER, nested)
+        } else {
+            container.set(key, kinds[kind] as PersistentDataType<Any, Any>, value.value)
+        }
+    }
+
+    /**
+     * Runs [task] on the holder's container. Returns false if the holder is gone, and for items the stack with the change applied.
+     */
+    private fun <T> withContainer(holder: DataHolder, task: (PersistentDataContainer) -> T): Triple<Boolean, T?, DataHolder> {
+        return when (holder) {
+            is DataHolder.OfEntity -> {
+                val entity = findEntity(holder.entityId) ?: return Triple(false, null, holder)
+                Triple(true, task(entity.persistentDataContainer), holder)
+            }
+            is DataHolder.OfChunk -> {
+                val world = Bukkit.getWorld(holder.world) ?: return Triple(false, null, holder)
+                Triple(true, task(world.getChunkAt(holder.x, holder.z).persistentDataContainer), holder)
+            }
+            is DataHolder.OfItem -> {
+                val item = holder.item.toItemStack() ?: return Triple(false, null, holder)
+                val meta = item.itemMeta ?: return Triple(false, null, holder)
+                val result = task(meta.persistentDataContainer)
+                item.itemMeta = meta
+                Triple(true, result, DataHolder.OfItem(item.toItemData()))
+            }
+        }
+    }
+
+    private fun toBukkit(key: String): NamespacedKey? = runCatching { NamespacedKey.fromString(key) }.getOrNull()
+
+    private fun DataKey.toBukkit() = toBukkit(key)
+
+    private fun validKeys(value: PersistentValue): Boolean {
+        val container = value.value as? PersistentContainerData ?: return true
+        return container.entries.all { (key, nested) -> toBukkit(key) != null && validKeys(nested) }
+    }
+
+    @BukrsEventHandler
+    fun getData(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqGetData) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val key = packet.key.toBukkit() ?: return@respondSync DefaultPackets.BukrsResGetData(false, OptionalPersistentValue(null))
+            val (found, value) = withContainer(packet.holder) { read(it, key, packet.kind.toInt()) }
+            DefaultPackets.BukrsResGetData(found, OptionalPersistentValue(value))
+        }
+    }
+
+    @BukrsEventHandler
+    fun setData(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetData) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val key = packet.key.toBukkit()
+            if (key == null || !validKeys(packet.value)) return@respondSync DefaultPackets.BukrsResDataUpdate(false, false, packet.holder)
+            val (found, existed, holder) = withContainer(packet.holder) { container ->
+                val existed = container.keys.contains(key)
+                container.remove(key)   // The new value may be of another kind
+                write(container, key, packet.value)
+                existed
+            }
+            DefaultPackets.BukrsResDataUpdate(found, existed == true, holder)
+        }
+    }
+
+    @BukrsEventHandler
+    fun removeData(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveData) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val key = packet.key.toBukkit() ?: return@respondSync DefaultPackets.BukrsResDataUpdate(false, false, packet.holder)
+            val (found, existed, holder) = withContainer(packet.holder) { container ->
+                container.keys.contains(key).also { container.remove(key) }
+            }
+            DefaultPackets.BukrsResDataUpdate(found, existed == true, holder)
+        }
+    }
+
+    @BukrsEventHandler
+    fun dataKeys(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqDataKeys) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val (found, keys) = withContainer(packet.holder) { container -> container.keys.map { DataKey(it.toString()) } }
+            DefaultPackets.BukrsResDataKeys(found, DataKeyList(keys ?: emptyList()))
+        }
+    }
+}
@@ -0,0 +1,57 @@
+package me.dolphin2410.bukrs
+
+import io.netty.channel.ChannelHandlerContext
+import java.util.Random
+import java.util.concurrent.CompletableFuture
+import java.util.concurrent.ConcurrentHashMap
+import java.util.concurrent.TimeUnit
+
+/**
+ * Server-initiated round trips: the packet is sent with a fresh payload id and the client answers with the same id
+ */
+object BukrsRequests {
+    private val pending = ConcurrentHashMap<Int, CompletableFuture<PacketType>>()
+    private val random = Random()
+
+    fun send(ctx: ChannelHandlerContext, packet: PacketType): CompletableFuture<PacketType> {
+        var payloadId: Int
+        do {
+            payloadId = random.nextInt(Int.MAX_VALUE - 1) + 1
+        } while (pending.containsKey(payloadId))
+
+        val future = CompletableFuture<PacketType>()
+        pending[payloadId] = future
+        ctx.pipeline().writeAndFlush(payloadId to packet)
+        return future
+    }
+
+    /**
+     * Blocks for at most [timeoutMillis], returning [fallback] if the client did not answer in time
+     */
+    inline fun <reified T: PacketType> await(ctx: ChannelHandlerContext, packet: PacketType, timeoutMillis: Long, fallback: T): T {
+        return awaitOrNull(ctx, packet, timeoutMillis, T::class.java) ?: fallback
+    }
+
+    /**
+     * Blocks for at most [timeoutMillis], returning null if the client did not answer in time or answered with another packet.
+     * A late answer is then ignored.
+     */
+    fun <T: PacketType> awaitOrNull(ctx: ChannelHandlerContext, packet: PacketType, timeoutMillis: Long, type: Class<T>): T? {
+        val future = send(ctx, packet)
+        return try {
+            type.cast(future.get(timeoutMillis, TimeUnit.MILLISECONDS))
+        } catch (e: Exception) {
+            pending.values.remove(future)
+            null
+        }
+    }
+
+    /**
+     * @return true if the packet answered a pending request
+     */
+    fun complete(payloadId: Int, packet: PacketType): Boolean {
+        val future = pending.remove(payloadId) ?: return false
+        future.complete(packet)
+        return true
+    }
+}
@@ -0,0 +1,170 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.ChatColor
+import org.bukkit.entity.Player
+import org.bukkit.event.EventHandler
+import org.bukkit.event.Listener
+import org.bukkit.event.player.PlayerQuitEvent
+import org.bukkit.scoreboard.Criteria
+import org.bukkit.scoreboard.DisplaySlot
+import org.bukkit.scoreboard.Scoreboard
+import org.bukkit.scoreboard.Team
+import java.util.UUID
+
+data class SidebarLine(val index: Byte, val text: String)
+
+data class SidebarLineList(val values: List<SidebarLine>)
+
+/**
+ * [color] indexes the legacy `§0`-`§f` codes, 16 is no colour. [collision] and [nameTagVisibility] index [Team.OptionStatus].
+ */
+data class TeamSettings(val prefix: String, val suffix: String, val color: Byte, val collision: Byte, val nameTagVisibility: Byte)
+
+private fun readLine(src: ByteBuf) = SidebarLine(src.readByte(), decodeType(String::class.java, src))
+
+fun scoreboardCodecs() {
+    pushCodec(SidebarLineList::class.java, object: TypeCodec<SidebarLineList> {
+        override fun decode(src: ByteBuf) = SidebarLineList((0 until readLength(src)).map { readLine(src) })
+
+        override fun encode(src: SidebarLineList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach {
+                target.writeByte(it.index.toInt())
+                encodeType(String::class.java, it.text, target)
+            }
+        }
+    })
+
+    pushCodec(TeamSettings::class.java, object: TypeCodec<TeamSettings> {
+        override fun decode(src: ByteBuf) = TeamSettings(decodeType(String::class.java, src), decodeType(String::class.java, src), src.readByte(), src.readByte(), src.readByte())
+
+        override fun encode(src: TeamSettings, target: ByteBuf) {
+            encodeType(String::class.java, src.prefix, target)
+            encodeType(String::class.java, src.suffix, target)
+            target.writeByte(src.color.toInt())
+            target.writeByte(src.collision.toInt())
+            target.writeByte(src.nameTagVisibility.toInt())
+        }
+    })
+}
+
+/**
+ * Sidebars need a scoreboard per player, so teams are mirrored onto the main scoreboard and every private one.
+ * Sidebar lines are invisible colour-code entries whose text lives in a team prefix, which changes without flicker.
+ * Everything runs on the main thread.
+ */
+object BukrsScoreboards: BukrsListener, Listener {
+    private const val SIDEBAR = "bukrs_sidebar"
+    private const val LINE_TEAM = "bukrs_line_"
+
+    private class TeamState(var settings: TeamSettings, val members: MutableSet<String> = LinkedHashSet())
+
+    private val teams = LinkedHashMap<String, TeamState>()
+    private val boards = HashMap<UUID, Scoreboard>()
+
+    private fun lineEntry(index: Int) = ChatColor.values()[index].toString()
+
+    private fun findPlayer(id: PlayerId): Player? = Bukkit.getOnlinePlayers().find { it.entityId == id.id }
+
+    private fun allBoards() = listOf(Bukkit.getScoreboardManager().mainScoreboard) + boards.values
+
+    @Suppress("DEPRECATION")
+    private fun apply(board: Scoreboard, name: String, state: TeamState) {
+        val team = board.getTeam(name) ?: board.registerNewTeam(name)
+        val settings = state.settings
+        team.prefix = settings.prefix
+        team.suffix = settings.suffix
+        team.color = if (settings.color.toInt() in 0..15) ChatColor.values()[settings.color.toInt()] else ChatColor.RESET
+        team.setOption(Team.Option.COLLISION_RULE, Team.OptionStatus.values()[settings.collision.toInt()])
+        team.setOption(Team.Option.NAME_TAG_VISIBILITY, Team.OptionStatus.values()[settings.nameTagVisibility.toInt()])
+        team.entries.filter { it !in state.members }.forEach { team.removeEntry(it) }
+        state.members.filter { !team.hasEntry(it) }.forEach { team.addEntry(it) }
+    }
+
+    private fun board(player: Player): Scoreboard {
+        return boards.getOrPut(player.uniqueId) {
+            val board = Bukkit.getScoreboardManager().newScoreboard
+            teams.forEach { (name, state) -> apply(board, name, state) }
+            player.scoreboard = board
+            board
+        }
+    }
+
+    @Suppress("DEPRECATION")
+    @BukrsEventHandler
+    fun updateSidebar(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqUpdateSidebar) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId) ?: return@respondSync DefaultPackets.BukrsResUpdateSidebar(false)
+            val board = board(player)
+            val objective = board.getObjective(SIDEBAR) ?: board.registerNewObjective(SIDEBAR, Criteria.DUMMY, packet.title).also { it.displaySlot = DisplaySlot.SIDEBAR }
+            objective.displayName = packet.title
+
+            val count = packet.lineCount.toInt()
+            packet.changes.values.forEach { line ->
+                val index = line.index.toInt()
+                val team = board.getTeam(LINE_TEAM + index) ?: board.registerNewTeam(LINE_TEAM + index).also { it.addEntry(lineEntry(index)) }
+                team.prefix = line.text
+            }
+            for (index in 0 until 15) {
+                if (index < count) {
+                    objective.getScore(lineEntry(index)).score = count - index
+                } else {
+                    board.resetScores(lineEntry(index))
+                }
+            }
+            DefaultPackets.BukrsResUpdateSidebar(true)
+        }
+    }
+
+    @BukrsEventHandler
+    fun removeSidebar(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveSidebar) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val player = findPlayer(packet.playerId)
+            val objective = player?.let { boards[it.uniqueId] }?.getObjective(SIDEBAR)
+            objective?.unregister()
+            DefaultPackets.BukrsResScoreboardUpdate(objective != null)
+        }
+    }
+
+    @BukrsEventHandler
+    fun setTeam(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetTeam) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val state = teams.getOrPut(packet.name) { TeamState(packet.settings) }
+            state.settings = packet.settings
+            allBoards().forEach { apply(it, packet.name, state) }
+            DefaultPackets.BukrsResSetTeam()
+        }
+    }
+
+    @BukrsEventHandler
+    fun teamMembers(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqTeamMembers) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val state = teams[packet.name] ?: return@respondSync DefaultPackets.BukrsResScoreboardUpdate(false)
+            if (packet.add) {
+                teams.values.forEach { it.members.removeAll(packet.members.values.toSet()) }   // An entry is in one team at a time
+                state.members.addAll(packet.members.values)
+            } else {
+                state.members.removeAll(packet.members.values.toSet())
+            }
+            allBoards().forEach { board -> teams.forEach { (name, state) -> apply(board, name, state) } }
+            DefaultPackets.BukrsResScoreboardUpdate(true)
+        }
+    }
+
+    @BukrsEventHandler
+    fun removeTeam(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqRemoveTeam) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val found = teams.remove(packet.name) != null
+            if (found) allBoards().forEach { it.getTeam(packet.name)?.unregister() }
+            DefaultPackets.BukrsResScoreboardUpdate(found)
+        }
+    }
+
+    @EventHandler
+    fun onQuit(event: PlayerQuitEvent) {
+        boards.remove(event.player.uniqueId)
+    }
+}
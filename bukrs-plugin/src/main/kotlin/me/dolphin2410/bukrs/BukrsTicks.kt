@@ -0,0 +1,29 @@
+package me.dolphin2410.bukrs
+
+import com.destroystokyo.paper.event.server.ServerTickStartEvent
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.event.EventHandler
+import org.bukkit.event.Listener
+import java.util.concurrent.CopyOnWriteArrayList
+
+/**
+ * Sends the tick number to subscribed clients at the start of every tick, which drives their schedulers
+ */
+object BukrsTicks: BukrsListener, Listener {
+    private val subscribers = CopyOnWriteArrayList<ChannelHandlerContext>()
+
+    @BukrsEventHandler
+    fun subscribe(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSubscribeTicks) {
+        if (subscribers.addIfAbsent(ctx)) {
+            ctx.channel().closeFuture().addListener { subscribers.remove(ctx) }
+        }
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResSubscribeTicks(Bukkit.getCurrentTick().toLong()))
+    }
+
+    @EventHandler
+    fun onTick(event: ServerTickStartEvent) {
+        val packet = DefaultPackets.BukrsSDTick(event.tickNumber.toLong())
+        subscribers.forEach { it.pipeline().writeAndFlush(0 to packet) }
+    }
+}
@@ -0,0 +1,208 @@
+package me.dolphin2410.bukrs
+
+import io.netty.buffer.ByteBuf
+import io.netty.channel.ChannelHandlerContext
+import org.bukkit.Bukkit
+import org.bukkit.World
+import org.bukkit.block.data.BlockData
+import java.util.UUID
+
+data class WorldData(val name: String, val uuid: UUID, val minHeight: Int, val maxHeight: Int)
+
+data class WorldList(val values: List<WorldData>)
+
+/**
+ * Block state string, e.g. `minecraft:oak_stairs[facing=north,half=top]`
+ */
+data class BlockState(val state: String)
+
+data class BlockChange(val x: Int, val y: Int, val z: Int, val state: Int)
+
+data class BlockChanges(val world: String, val palette: List<BlockState>, val blocks: List<BlockChange>)
+
+data class BlockStateList(val values: List<BlockState>)
+
+data class IntList(val values: List<Int>)
+
+fun World.toWorldData() = WorldData(name, uid, minHeight, maxHeight)
+
+/**
+ * Throws for unknown worlds, which [BukrsMain.respondSync] answers with a [DefaultPackets.BukrsResError]
+ */
+fun worldNamed(name: String): World = Bukkit.getWorld(name) ?: throw IllegalArgumentException("No world named $name")
+
+/**
+ * Throws for states Bukkit can't parse, which [BukrsMain.respondSync] answers with a [DefaultPackets.BukrsResError]
+ */
+fun blockData(state: String): BlockData = try {
+    Bukkit.createBlockData(state)
+} catch (e: IllegalArgumentException) {
+    throw IllegalArgumentException("Invalid block state $state", e)
+}
+
+internal fun readUuid(src: ByteBuf): UUID {
+    val lsb = src.readLong()
+    val msb = src.readLong()
+    return UUID(msb, lsb)
+}
+
+internal fun writeUuid(src: UUID, target: ByteBuf) {
+    target.writeLong(src.leastSignificantBits)
+    target.writeLong(src.mostSignificantBits)
+}
+
+fun worldCodecs() {
+    pushCodec(UUID::class.java, object: TypeCodec<UUID> {
+        override fun decode(src: ByteBuf) = readUuid(src)
+
+        override fun encode(src: UUID, target: ByteBuf) = writeUuid(src, target)
+    })
+
+    pushCodec(WorldList::class.java, object: TypeCodec<WorldList> {
+        override fun decode(src: ByteBuf): WorldList {
+            return WorldList((0 until readLength(src)).map { WorldData(decodeType(String::class.java, src), readUuid(src), src.readInt(), src.readInt()) })
+        }
+
+        override fun encode(src: WorldList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach {
+                encodeType(String::class.java, it.name, target)
+                writeUuid(it.uuid, target)
+                target.writeInt(it.minHeight)
+                target.writeInt(it.maxHeight)
+            }
+        }
+    })
+
+    pushCodec(BlockState::class.java, object: TypeCodec<BlockState> {
+        override fun decode(src: ByteBuf) = BlockState(decodeType(String::class.java, src))
+
+        override fun encode(src: BlockState, target: ByteBuf) = encodeType(String::class.java, src.state, target)
+    })
+
+    pushCodec(BlockStateList::class.java, object: TypeCodec<BlockStateList> {
+        override fun decode(src: ByteBuf) = BlockStateList((0 until readLength(src)).map { BlockState(decodeType(String::class.java, src)) })
+
+        override fun encode(src: BlockStateList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { encodeType(String::class.java, it.state, target) }
+        }
+    })
+
+    pushCodec(IntList::class.java, object: TypeCodec<IntList> {
+        override fun decode(src: ByteBuf) = IntList((0 until readLength(src)).map { src.readInt() })
+
+        override fun encode(src: IntList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { target.writeInt(it) }
+        }
+    })
+
+    pushCodec(BlockChanges::class.java, object: TypeCodec<BlockChanges> {
+        override fun decode(src: ByteBuf): BlockChanges {
+            val world = decodeType(String::class.java, src)
+            val palette = (0 until readLength(src)).map { BlockState(decodeType(String::class.java, src)) }
+            val blocks = (0 until readLength(src)).map { BlockChange(src.readInt(), src.readInt(), src.readInt(), src.readInt()) }
+            return BlockChanges(world, palette, blocks)
+        }
+
+        override fun encode(src: BlockChanges, target: ByteBuf) {
+            encodeType(String::class.java, src.world, target)
+            writeLength(src.palette.size, target)
+            src.palette.forEach { encodeType(String::class.java, it.state, target) }
+            writeLength(src.blocks.size, target)
+            src.blocks.forEach {
+                target.writeInt(it.x)
+                target.writeInt(it.y)
+                target.writeInt(it.z)
+                target.writeInt(it.state)
+            }
+        }
+    })
+}
+
+object BukrsWorlds: BukrsListener {
+    /**
+     * Most blocks one BukrsReqGetBlocks may read, a cube of `COPY_SECTION` in Rust. Larger regions would stall the tick
+     * and answer with a frame over `MAX_FRAME_LEN`.
+     */
+    const val MAX_GET_BLOCKS = 64L * 64 * 64
+
+    @BukrsEventHandler
+    fun worlds(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqWorlds) {
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResWorlds(WorldList(Bukkit.getWorlds().map { it.toWorldData() })))
+    }
+
+    @BukrsEventHandler
+    fun worldByName(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqWorldByName) {
+        val worlds = listOfNotNull(Bukkit.getWorld(packet.name)?.toWorldData())
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResWorlds(WorldList(worlds)))
+    }
+
+    @BukrsEventHandler
+    fun worldByUuid(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqWorldByUuid) {
+        val worlds = listOfNotNull(Bukkit.getWorld(packet.uuid)?.toWorldData())
+        ctx.pipeline().writeAndFlush(payloadId to DefaultPackets.BukrsResWorlds(WorldList(worlds)))
+    }
+
+    @BukrsEventHandler
+    fun getBlock(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqGetBlock) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val pos = packet.pos
+            val block = worldNamed(pos.world).getBlockAt(pos.x, pos.y, pos.z)
+            DefaultPackets.BukrsResGetBlock(BlockState(block.blockData.asString))
+        }
+    }
+
+    @BukrsEventHandler
+    fun setBlock(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetBlock) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val pos = packet.pos
+            val data = blockData(packet.data.state)
+            worldNamed(pos.world).getBlockAt(pos.x, pos.y, pos.z).setBlockData(data, packet.physics)
+            DefaultPackets.BukrsResSetBlock()
+        }
+    }
+
+    @BukrsEventHandler
+    fun setBlocks(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqSetBlocks) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val changes = packet.changes
+            val world = worldNamed(changes.world)
+            val palette = changes.palette.map { blockData(it.state) }
+            changes.blocks.find { it.state !in palette.indices }?.let {    // Checked up front so a bad change doesn't leave the others half applied
+                throw IllegalArgumentException("State ${it.state} at ${it.x} ${it.y} ${it.z} is outside the palette of ${palette.size}")
+            }
+            var changed = 0
+            for (change in changes.blocks) {
+                val block = world.getBlockAt(change.x, change.y, change.z)
+                val data = palette[change.state]
+                if (block.blockData != data) {
+                    block.setBlockData(data, packet.physics)
+                    changed++
+                }
+            }
+            DefaultPackets.BukrsResSetBlocks(changed)
+        }
+    }
+
+    @BukrsEventHandler
+    fun getBlocks(ctx: ChannelHandlerContext, payloadId: Int, packet: DefaultPackets.BukrsReqGetBlocks) {
+        BukrsMain.respondSync(ctx, payloadId) {
+            val region = packet.region
+            if (region.volume > MAX_GET_BLOCKS) throw IllegalArgumentException("Region of ${region.volume} blocks is larger than $MAX_GET_BLOCKS")
+            val world = worldNamed(region.world)
+            val palette = LinkedHashMap<String, Int>()
+            val blocks = ArrayList<Int>(region.volume.toInt())
+            for (y in region.min.y..region.max.y) {
+                for (z in region.min.z..region.max.z) {
+                    for (x in region.min.x..region.max.x) {
+                        blocks.add(palette.getOrPut(world.getBlockAt(x, y, z).blockData.asString) { palette.size })
+                    }
+                }
+            }
+            @Suppress("DEPRECATION")
+            DefaultPackets.BukrsResGetBlocks(Bukkit.getUnsafe().dataVersion, BlockStateList(palette.keys.map { BlockState(it) }), IntList(blocks))
+        }
+    }
+}
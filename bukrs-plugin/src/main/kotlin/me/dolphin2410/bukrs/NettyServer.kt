@@ -24,6 +24,7 @@ class NettyServer {
                         )
 
                         ch.pipeline().addLast(BukrsEncoder())
+                        ch.pipeline().addLast(BatchInterceptor())   // Outbound runs from the tail, so batched responses never reach the encoder
                     }
                 }).option(ChannelOption.SO_BACKLOG, 128)
                 .childOption(ChannelOption.SO_KEEPALIVE, true)
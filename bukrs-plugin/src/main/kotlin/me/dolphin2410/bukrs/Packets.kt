@@ -1,5 +1,7 @@
 package me.dolphin2410.bukrs
 
+import java.util.UUID
+
 @Target(AnnotationTarget.CLASS)
 @Retention(AnnotationRetention.RUNTIME)
 annotation class Packet {
@@ -8,30 +10,43 @@ annotation class Packet {
 
 interface PacketType
 
+/**
+ * Answer of a client to a cancellable event
+ */
+interface EventVerdict: PacketType {
+    val cancelled: Boolean
+}
+
 interface PacketGroup
 
 class DefaultPackets: PacketGroup {
     @Packet
-    class BukrsReqAPI: PacketType
+    data class BukrsReqAPI(val protocolVersion: Int): PacketType
 
     @Packet
-    data class BukrsResAPI(val apiId: Int): PacketType
+    data class BukrsResAPI(val apiId: Int, val protocolVersion: Int): PacketType   // The server's version. On a mismatch the server closes the connection after answering.
 
     @Packet
     class BukrsReqOnlinePlayers: PacketType
 
     @Packet
-    data class BukrsResOnlinePlayers(val players: List<Int>): PacketType
+    data class BukrsResOnlinePlayers(val players: PlayerIdList): PacketType
 
     @Packet
-    data class BukrsReqPlayerById(val player: Int): PacketType
+    data class BukrsReqPlayerById(val playerId: PlayerId): PacketType
 
     @Packet
-    data class BukrsReqPlayerByName(val player: String): PacketType
+    data class BukrsReqPlayerByName(val playerName: String): PacketType
 
     @Packet
     data class BukrsResPlayerData(val data: PlayerData): PacketType
 
+    @Packet
+    data class BukrsSDPlayerJoin(val data: PlayerData): PacketType  // Also sent for every online player right after BukrsResAPI
+
+    @Packet
+    data class BukrsSDPlayerQuit(val playerId: PlayerId): PacketType
+
     @Packet
     data class BukrsReqCreateInventory(val name: String, val size: InventorySize) // Request creation invfx
 
@@ -48,13 +63,13 @@ class DefaultPackets: PacketGroup {
     data class BukrsSDInvClose(val playerId: PlayerId)
 
     @Packet
-    data class BukrsReqPlayerInvOpen(val inv_id: InvfxId, val playerId: PlayerId)
+    data class BukrsReqPlayerInvOpen(val invId: InvfxId, val playerId: PlayerId)
 
     @Packet
     class BukrsResPlayerInvOpen
 
     @Packet
-    data class BukrsReqCreateInvList(val inv_id: InvfxId, val list: InvListWrapper)
+    data class BukrsReqCreateInvList(val invId: InvfxId, val list: InvListWrapper)
 
     @Packet
     class BukrsResCreateInvList
@@ -64,4 +79,340 @@ class DefaultPackets: PacketGroup {
 
     @Packet
     class BukrsResModifyInvList
+
+    @Packet
+    data class BukrsReqRegisterCommand(val command: CommandSpec): PacketType
+
+    @Packet
+    class BukrsResRegisterCommand: PacketType
+
+    @Packet
+    data class BukrsSDCommand(val sender: CommandSender, val label: String, val args: StringList): PacketType  // Sent on every invocation of a registered command
+
+    @Packet
+    data class BukrsReqSendMessage(val target: CommandSender, val message: String): PacketType
+
+    @Packet
+    class BukrsResSendMessage: PacketType
+
+    @Packet
+    data class BukrsSDTabComplete(val sender: CommandSender, val label: String, val args: StringList): PacketType  // Carries a payload id, answered with BukrsResTabComplete
+
+    @Packet
+    data class BukrsResTabComplete(val suggestions: StringList): PacketType
+
+    @Packet
+    data class BukrsReqBatch(val requests: PacketList): PacketType   // The server handles every request within one tick, under negative payload ids
+
+    @Packet
+    data class BukrsResBatch(val responses: PacketList): PacketType  // In the order of the requests
+
+    @Packet
+    data class BukrsResError(val message: String): PacketType    // Stands in for the response of a request the server couldn't handle
+
+    @Packet
+    data class BukrsReqSubscribeEvent(val event: String, val deadlineMs: Int, val blocking: Boolean): PacketType  // Blocking, the server holds the event back for at most `deadlineMs` awaiting the verdict. Otherwise it is sent without a payload id once decided.
+
+    @Packet
+    class BukrsResSubscribeEvent: PacketType
+
+    @Packet
+    data class BukrsSDEventDropped(val event: String): PacketType  // The server stopped sending the event after the client missed a deadline
+
+    @Packet
+    data class BukrsSDPlayerChat(val playerId: PlayerId, val message: String, val format: String, val recipients: PlayerIdList): PacketType  // Carries a payload id, answered with BukrsResPlayerChat
+
+    @Packet
+    data class BukrsResPlayerChat(override val cancelled: Boolean, val message: String, val format: String, val recipients: PlayerIdList): EventVerdict
+
+    @Packet
+    data class BukrsSDBlockBreak(val playerId: PlayerId, val block: BlockPos, val material: Material, val dropItems: Boolean): PacketType
+
+    @Packet
+    data class BukrsResBlockBreak(override val cancelled: Boolean, val dropItems: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsSDBlockPlace(val playerId: PlayerId, val block: BlockPos, val material: Material, val against: BlockPos, val hand: Hand): PacketType
+
+    @Packet
+    data class BukrsResBlockPlace(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsSDPlayerInteract(val playerId: PlayerId, val action: InteractAction, val target: InteractTarget, val hand: Hand, val item: Material): PacketType
+
+    @Packet
+    data class BukrsResPlayerInteract(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    class BukrsReqWorlds: PacketType
+
+    @Packet
+    data class BukrsReqWorldByName(val name: String): PacketType
+
+    @Packet
+    data class BukrsReqWorldByUuid(val uuid: UUID): PacketType
+
+    @Packet
+    data class BukrsResWorlds(val worlds: WorldList): PacketType  // Empty if a lookup found nothing
+
+    @Packet
+    data class BukrsReqGetBlock(val pos: BlockPos): PacketType
+
+    @Packet
+    data class BukrsResGetBlock(val data: BlockState): PacketType
+
+    @Packet
+    data class BukrsReqSetBlock(val pos: BlockPos, val data: BlockState, val physics: Boolean): PacketType
+
+    @Packet
+    class BukrsResSetBlock: PacketType
+
+    @Packet
+    data class BukrsReqSetBlocks(val changes: BlockChanges, val physics: Boolean): PacketType
+
+    @Packet
+    data class BukrsResSetBlocks(val changed: Int): PacketType
+
+    @Packet
+    data class BukrsReqGetBlocks(val region: Region): PacketType   // At most MAX_GET_BLOCKS blocks
+
+    @Packet
+    data class BukrsResGetBlocks(val dataVersion: Int, val palette: BlockStateList, val blocks: IntList): PacketType  // Ordered x first, then z, then y, like Sponge schematics
+
+    @Packet
+    data class BukrsReqSpawnEntity(val entityType: EntityTypeKey, val location: LocationData): PacketType
+
+    @Packet
+    data class BukrsReqNearbyEntities(val location: LocationData, val radius: Double, val filter: EntityFilter): PacketType
+
+    @Packet
+    data class BukrsReqEntityById(val entityId: EntityId): PacketType
+
+    @Packet
+    data class BukrsResEntities(val entities: EntityDataList): PacketType  // Empty if nothing was spawned or found
+
+    @Packet
+    data class BukrsReqTeleportEntity(val entityId: EntityId, val location: LocationData): PacketType
+
+    @Packet
+    data class BukrsReqSetVelocity(val entityId: EntityId, val velocity: VectorData): PacketType
+
+    @Packet
+    data class BukrsReqRemoveEntity(val entityId: EntityId): PacketType
+
+    @Packet
+    data class BukrsReqSetCustomName(val entityId: EntityId, val name: String): PacketType
+
+    @Packet
+    data class BukrsReqSetEntityFlags(val entityId: EntityId, val flags: Byte): PacketType
+
+    @Packet
+    data class BukrsReqChangeEntityFlags(val entityId: EntityId, val set: Byte, val clear: Byte): PacketType  // Applied to the current flags in one step
+
+    @Packet
+    data class BukrsResEntityUpdate(val found: Boolean): PacketType
+
+    @Packet
+    data class BukrsResRemoveEntity(val found: Boolean, val removed: Boolean): PacketType   // Players are found but not removed
+
+    @Packet
+    data class BukrsReqSpawnTypes(val types: EntityTypeKeyList): PacketType   // Only these types fire BukrsSDEntitySpawn, every type if empty
+
+    @Packet
+    class BukrsResSpawnTypes: PacketType
+
+    @Packet
+    data class BukrsSDEntitySpawn(val entity: EntityData): PacketType
+
+    @Packet
+    data class BukrsResEntitySpawn(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsSDEntityDeath(val entity: EntityData, val droppedExp: Int): PacketType
+
+    @Packet
+    data class BukrsResEntityDeath(override val cancelled: Boolean, val droppedExp: Int): EventVerdict
+
+    @Packet
+    data class BukrsSDEntityDamage(val victim: EntityId, val attacker: OptionalEntityId, val cause: String, val rawDamage: Double, val finalDamage: Double): PacketType
+
+    @Packet
+    data class BukrsResEntityDamage(override val cancelled: Boolean, val finalDamage: Double): EventVerdict
+
+    @Packet
+    data class BukrsSDPlayerDeath(val playerId: PlayerId, val drops: ItemDataList, val droppedExp: Int, val deathMessage: String, val keepInventory: Boolean): PacketType  // An empty message is hidden
+
+    @Packet
+    data class BukrsResPlayerDeath(override val cancelled: Boolean, val drops: ItemDataList, val droppedExp: Int, val deathMessage: String, val keepInventory: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsReqDefineRegion(val name: String, val region: Region): PacketType  // Replaces a region of the same name defined by the same client
+
+    @Packet
+    class BukrsResDefineRegion: PacketType
+
+    @Packet
+    data class BukrsReqRemoveRegion(val name: String): PacketType
+
+    @Packet
+    data class BukrsResRemoveRegion(val found: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqMoveInterval(val intervalMs: Int): PacketType    // Minimum time between two BukrsSDPlayerMove of one player
+
+    @Packet
+    class BukrsResMoveInterval: PacketType
+
+    @Packet
+    data class BukrsSDPlayerMove(val playerId: PlayerId, val from: LocationData, val to: LocationData): PacketType  // Only sent when the block changes
+
+    @Packet
+    data class BukrsResPlayerMove(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsSDRegionEnter(val playerId: PlayerId, val region: String, val from: LocationData, val to: LocationData): PacketType
+
+    @Packet
+    data class BukrsResRegionEnter(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsSDRegionLeave(val playerId: PlayerId, val region: String, val from: LocationData, val to: LocationData): PacketType
+
+    @Packet
+    data class BukrsResRegionLeave(override val cancelled: Boolean): EventVerdict
+
+    @Packet
+    data class BukrsReqUpdateSidebar(val playerId: PlayerId, val title: String, val lineCount: Byte, val changes: SidebarLineList): PacketType  // Shows the sidebar if it isn't already
+
+    @Packet
+    data class BukrsResUpdateSidebar(val found: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqRemoveSidebar(val playerId: PlayerId): PacketType
+
+    @Packet
+    data class BukrsReqSetTeam(val name: String, val settings: TeamSettings): PacketType  // Creates the team if it doesn't exist
+
+    @Packet
+    class BukrsResSetTeam: PacketType
+
+    @Packet
+    data class BukrsReqTeamMembers(val name: String, val add: Boolean, val members: StringList): PacketType    // Removes the members if `add` is false
+
+    @Packet
+    data class BukrsReqRemoveTeam(val name: String): PacketType
+
+    @Packet
+    data class BukrsResScoreboardUpdate(val found: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqGetData(val holder: DataHolder, val key: DataKey, val kind: Byte): PacketType
+
+    @Packet
+    data class BukrsResGetData(val found: Boolean, val value: OptionalPersistentValue): PacketType  // Null if no value of that kind is stored
+
+    @Packet
+    data class BukrsReqSetData(val holder: DataHolder, val key: DataKey, val value: PersistentValue): PacketType
+
+    @Packet
+    data class BukrsReqRemoveData(val holder: DataHolder, val key: DataKey): PacketType
+
+    @Packet
+    data class BukrsResDataUpdate(val found: Boolean, val existed: Boolean, val holder: DataHolder): PacketType  // Items come back with the change applied
+
+    @Packet
+    data class BukrsReqDataKeys(val holder: DataHolder): PacketType
+
+    @Packet
+    data class BukrsResDataKeys(val found: Boolean, val keys: DataKeyList): PacketType
+
+    @Packet
+    data class BukrsReqHasPermission(val playerId: PlayerId, val permission: String): PacketType
+
+    @Packet
+    data class BukrsResHasPermission(val found: Boolean, val value: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqHasPermissions(val playerId: PlayerId, val permissions: StringList): PacketType
+
+    @Packet
+    data class BukrsResHasPermissions(val found: Boolean, val values: BooleanList): PacketType    // In the order of the request
+
+    @Packet
+    data class BukrsReqAddAttachment(val attachmentId: Int, val playerId: PlayerId): PacketType  // The client picks the id
+
+    @Packet
+    data class BukrsReqSetAttachmentPermission(val attachmentId: Int, val permission: String, val value: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqUnsetAttachmentPermission(val attachmentId: Int, val permission: String): PacketType
+
+    @Packet
+    data class BukrsReqRemoveAttachment(val attachmentId: Int): PacketType
+
+    @Packet
+    data class BukrsResAttachment(val found: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqIsOp(val playerId: PlayerId): PacketType
+
+    @Packet
+    data class BukrsReqSetOp(val playerId: PlayerId, val op: Boolean): PacketType
+
+    @Packet
+    data class BukrsResOp(val found: Boolean, val op: Boolean): PacketType
+
+    @Packet
+    data class BukrsSDPermissionChange(val playerId: PlayerId): PacketType  // Sent before the response to the change that caused it
+
+    @Packet
+    data class BukrsReqPlaySound(val target: EffectTarget, val sound: String, val category: Byte, val volume: Float, val pitch: Float, val location: OptionalLocationData): PacketType   // Null plays the sound at each player
+
+    @Packet
+    data class BukrsReqSpawnParticle(val target: EffectTarget, val particle: String, val location: LocationData, val count: Int, val offset: VectorData, val speed: Double, val data: ParticleData): PacketType
+
+    @Packet
+    data class BukrsReqSendTitle(val target: EffectTarget, val title: String, val subtitle: String, val fadeIn: Int, val stay: Int, val fadeOut: Int): PacketType
+
+    @Packet
+    data class BukrsResEffect(val players: Int): PacketType // Players the effect was sent to
+
+    @Packet
+    data class BukrsReqCreateBossBar(val barId: Int, val title: String, val color: Byte, val style: Byte): PacketType  // The client picks the id
+
+    @Packet
+    data class BukrsReqUpdateBossBar(val barId: Int, val changes: BossBarChangeList): PacketType
+
+    @Packet
+    data class BukrsReqRemoveBossBar(val barId: Int): PacketType
+
+    @Packet
+    data class BukrsResBossBar(val found: Boolean): PacketType
+
+    @Packet
+    class BukrsReqSubscribeTicks: PacketType
+
+    @Packet
+    data class BukrsResSubscribeTicks(val tick: Long): PacketType
+
+    @Packet
+    data class BukrsSDTick(val tick: Long): PacketType  // Sent at the start of every server tick once subscribed
+
+    @Packet
+    data class BukrsReqStartJob(val jobId: Int, val operation: RegionOperation, val blocksPerTick: Int, val physics: Boolean): PacketType  // The client picks the id so reports can't outrun the response, and may not reuse the id of a running job
+
+    @Packet
+    data class BukrsResStartJob(val total: Long): PacketType
+
+    @Packet
+    data class BukrsSDJobProgress(val jobId: Int, val done: Long, val total: Long): PacketType
+
+    @Packet
+    data class BukrsSDJobDone(val jobId: Int, val changed: Long, val cancelled: Boolean): PacketType
+
+    @Packet
+    data class BukrsReqCancelJob(val jobId: Int): PacketType
+
+    @Packet
+    class BukrsResCancelJob: PacketType
 }
\ No newline at end of file
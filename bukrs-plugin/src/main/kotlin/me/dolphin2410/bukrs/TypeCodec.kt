@@ -44,6 +44,16 @@ fun defaultCodecs() {
         }
     })
 
+    pushCodec(Boolean::class.java, object: TypeCodec<Boolean> {
+        override fun decode(src: ByteBuf): Boolean {
+            return src.readByte().toInt() != 0
+        }
+
+        override fun encode(src: Boolean, target: ByteBuf) {
+            target.writeByte(if (src) 1 else 0)
+        }
+    })
+
     pushCodec(Short::class.java, object: TypeCodec<Short> {
         override fun decode(src: ByteBuf): Short {
             return src.readShort()
@@ -136,7 +146,7 @@ fun defaultCodecs() {
 
     pushCodec(String::class.java, object: TypeCodec<String> {
         override fun decode(src: ByteBuf): String {
-            val size = src.readInt()
+            val size = readLength(src)
             val buf = ByteArray(size)
             src.readBytes(buf)
             return String(buf)
@@ -144,7 +154,7 @@ fun defaultCodecs() {
 
         override fun encode(src: String, target: ByteBuf) {
             val array = src.toByteArray()
-            target.writeInt(array.size)
+            writeLength(array.size, target)
             target.writeBytes(array)
         }
     })
@@ -7,6 +7,8 @@ data class PlayerData(val id: Int, val name: String, val uniqueId: UUID)
 
 data class PlayerId(val id: Int)
 
+data class PlayerIdList(val values: List<PlayerId>)
+
 data class InvfxId(val id: Int)
 
 data class ItemStackWrapper(val name: String, val material: String)
@@ -51,4 +53,40 @@ enum class InventorySize {
             }
         })
     }
-}
\ No newline at end of file
+}
+
+fun playerCodecs() {
+    pushCodec(PlayerId::class.java, object: TypeCodec<PlayerId> {
+        override fun decode(src: ByteBuf) = PlayerId(src.readInt())
+
+        override fun encode(src: PlayerId, target: ByteBuf) {
+            target.writeInt(src.id)
+        }
+    })
+
+    pushCodec(PlayerIdList::class.java, object: TypeCodec<PlayerIdList> {
+        override fun decode(src: ByteBuf) = PlayerIdList((0 until readLength(src)).map { PlayerId(src.readInt()) })
+
+        override fun encode(src: PlayerIdList, target: ByteBuf) {
+            writeLength(src.values.size, target)
+            src.values.forEach { target.writeInt(it.id) }
+        }
+    })
+
+    pushCodec(PlayerData::class.java, object: TypeCodec<PlayerData> {
+        override fun decode(src: ByteBuf): PlayerData {
+            val id = src.readInt()
+            val name = decodeType(String::class.java, src)
+            val lsb = src.readLong()
+            val msb = src.readLong()
+            return PlayerData(id, name, UUID(msb, lsb))
+        }
+
+        override fun encode(src: PlayerData, target: ByteBuf) {
+            target.writeInt(src.id)
+            encodeType(String::class.java, src.name, target)
+            target.writeLong(src.uniqueId.leastSignificantBits)
+            target.writeLong(src.uniqueId.mostSignificantBits)
+        }
+    })
+}
@@ -16,7 +16,7 @@ fun writeVarInt(value: Int, buffer: ByteBuf) {
 
         buffer.writeByte((latestValue and SEGMENT_BITS) or CONTINUE_BIT)   // value can be coerced to a u8 type
 
-        latestValue = (value shr 7); // 'unsigned shift right' ( >>> )
+        latestValue = (latestValue ushr 7); // 'unsigned shift right' ( >>> )
     }
 }
 
@@ -27,7 +27,7 @@ fun readVarInt(src: ByteBuf): Result<Int> {
 
     while(true) {
         currentByte = src.readByte().toInt();
-        value = value or (currentByte and SEGMENT_BITS) shl position;
+        value = value or ((currentByte and SEGMENT_BITS) shl position);
 
         if ((currentByte and CONTINUE_BIT) == 0) { break; }
 
@@ -39,4 +39,11 @@ fun readVarInt(src: ByteBuf): Result<Int> {
     }
 
     return Result.success(value)
-}
\ No newline at end of file
+}
+
+/**
+ * Length of a string or collection, written as an unsigned varint
+ */
+fun readLength(src: ByteBuf) = readVarInt(src).getOrThrow()
+
+fun writeLength(size: Int, target: ByteBuf) = writeVarInt(size, target)
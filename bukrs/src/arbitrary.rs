@@ -0,0 +1,161 @@
+//! `Arbitrary` impls for the types with hand-written encodings, limited to values that survive a round trip.
+//! Derived types and packets get theirs from `bukrs-derive`.
+
+use bukrs_core::proptest::{prelude::*, collection::{vec, btree_map}};
+
+use crate::{core::{block::{BlockPos, Material}, combat::DamageCause, command::{ArgSpec, CommandSpec}, effect::{Sound, Particle},
+    persistent::{NamespacedKey, PersistentValue, PersistentContainer}, region::Region, world::{BlockData, BlockChanges}},
+    net::{Packet, PacketList, BukrsReqOnlinePlayers, BukrsSDPlayerJoin, BukrsSDPlayerQuit}};
+
+/// Implements `Arbitrary` with a boxed strategy
+macro_rules! arbitrary {
+    ($($ty:ty => $strategy:expr;)*) => {
+        $(
+            impl Arbitrary for $ty {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<$ty>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    $strategy.boxed()
+                }
+            }
+        )*
+    };
+}
+
+/// Block states are sent as strings, so materials and properties can't contain the state syntax
+fn block_data() -> impl Strategy<Value = BlockData> {
+    ("[a-z]{1,8}:[a-z_]{1,12}", btree_map("[a-z_]{1,8}", "[a-z0-9_]{1,8}", 0..3))
+        .prop_map(|(material, properties)| BlockData { material: Material(material), properties })
+}
+
+/// Subcommands without subcommands of their own, as `CommandSpec` contains itself
+pub(crate) fn subcommands() -> impl Strategy<Value = Vec<CommandSpec>> {
+    let command = (any::<String>(), any::<String>(), any::<String>(), vec(any::<ArgSpec>(), 0..3))
+        .prop_map(|(name, permission, usage, args)| CommandSpec { name, permission, usage, args, subcommands: vec![] });
+    vec(command, 0..3)
+}
+
+/// A container one level deep, as `PersistentValue` contains itself
+pub(crate) fn nested_container() -> impl Strategy<Value = PersistentContainer> {
+    let value = prop_oneof![any::<i32>().prop_map(PersistentValue::Int), any::<String>().prop_map(PersistentValue::String), vec(any::<i64>(), 0..8).prop_map(PersistentValue::LongArray)];
+    btree_map(any::<NamespacedKey>(), value, 0..4).prop_map(PersistentContainer)
+}
+
+/// A few plain packets, for batches. Batches aren't nested, so the recursion ends here.
+fn packet() -> impl Strategy<Value = Box<dyn Packet>> {
+    prop_oneof![
+        any::<BukrsReqOnlinePlayers>().prop_map(|packet| Box::new(packet) as Box<dyn Packet>),
+        any::<BukrsSDPlayerJoin>().prop_map(|packet| Box::new(packet) as Box<dyn Packet>),
+        any::<BukrsSDPlayerQuit>().prop_map(|packet| Box::new(packet) as Box<dyn Packet>)
+    ]
+}
+
+arbitrary! {
+    // Names go through `from_name`, so `Other` never holds a known name
+    Sound => prop_oneof![Just(Sound::UiButtonClick), Just(Sound::EntityPlayerLevelup), any::<String>().prop_map(|name| Sound::from_name(&name))];
+    Particle => prop_oneof![Just(Particle::Flame), Just(Particle::Smoke), any::<String>().prop_map(|name| Particle::from_name(&name))];
+    DamageCause => prop_oneof![Just(DamageCause::Fall), Just(DamageCause::EntityAttack), any::<String>().prop_map(|name| DamageCause::from_name(&name))];
+
+    BlockData => block_data();
+    // Built with `set` to keep the palette index in sync
+    BlockChanges => (any::<String>(), vec((any::<i32>(), any::<i32>(), any::<i32>(), block_data()), 0..8)).prop_map(|(world, blocks)| {
+        let mut changes = BlockChanges::new(&world);
+        for (x, y, z, data) in blocks.iter() {
+            changes.set(*x, *y, *z, data);
+        }
+        changes
+    });
+    PersistentContainer => btree_map(any::<NamespacedKey>(), any::<PersistentValue>(), 0..4).prop_map(PersistentContainer);
+    // Corners are normalized on decode, so any two corners of one world
+    Region => (any::<String>(), any::<(i32, i32, i32)>(), any::<(i32, i32, i32)>())
+        .prop_map(|(world, a, b)| Region::new(&BlockPos { world: world.clone(), x: a.0, y: a.1, z: a.2 }, &BlockPos { world, x: b.0, y: b.1, z: b.2 }));
+
+    PacketList => vec(packet(), 0..4).prop_map(PacketList);
+}
+
+#[cfg(test)]
+mod tests {
+    use bukrs_core::proptest::{prelude::*, collection::vec, test_runner::{TestRunner, Config}};
+    use bytes::BytesMut;
+    use tokio_util::codec::{Encoder, Decoder};
+
+    use crate::{net::{Codec, BukrsPacketData, Packet, BukrsReqSetBlocks, STRATEGIES}, core::world::{BlockChanges, BlockData}};
+
+    const PAYLOAD_ID: u32 = 0x01020304;
+
+    fn fields(packet: &dyn Packet) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        packet.encode(&mut bytes);
+        bytes
+    }
+
+    /// Writes `packet` as two frames, then hands them to the codec once byte by byte and once in chunks of `chunks` sizes
+    fn check_round_trip(packet: Box<dyn Packet>, chunks: Vec<usize>) -> Result<(), TestCaseError> {
+        let mut frames = BytesMut::new();
+        Codec.encode(BukrsPacketData { payload_id: Some(PAYLOAD_ID), event: packet.clone_box() }, &mut frames).unwrap();
+        let frame_len = frames.len();
+        Codec.encode(BukrsPacketData { payload_id: None, event: packet.clone_box() }, &mut frames).unwrap();
+
+        let mut buffer = BytesMut::new();
+        for (index, byte) in frames[..frame_len].iter().enumerate() {
+            buffer.extend_from_slice(&[*byte]);
+            let decoded = Codec.decode(&mut buffer).unwrap();
+            prop_assert_eq!(decoded.is_some(), index == frame_len - 1, "decoded after {} of {} bytes", index + 1, frame_len);
+        }
+        prop_assert!(buffer.is_empty());
+
+        let mut decoded = vec![];
+        let mut rest = &frames[..];
+        for size in chunks.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, remaining) = rest.split_at((*size).min(rest.len()));
+            buffer.extend_from_slice(chunk);
+            rest = remaining;
+            while let Some(data) = Codec.decode(&mut buffer).unwrap() {
+                decoded.push(data);
+            }
+        }
+        prop_assert!(buffer.is_empty());
+        prop_assert_eq!(decoded.iter().map(|data| data.payload_id).collect::<Vec<Option<u32>>>(), [Some(PAYLOAD_ID), None]);
+
+        // Floats make `PartialEq` unreliable, so packets are compared by their JSON and re-encoded bytes
+        let expected = serde_json::to_value(&packet).unwrap();
+        for data in decoded.iter() {
+            prop_assert_eq!(data.event.id(), packet.id());
+            prop_assert_eq!(&serde_json::to_value(&data.event).unwrap(), &expected);
+            prop_assert_eq!(fields(data.event.as_ref()), fields(packet.as_ref()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_every_packet_round_trips() {
+        let mut strategies = STRATEGIES.lock().unwrap().iter().map(|(name, strategy)| (name.clone(), *strategy)).collect::<Vec<_>>();
+        strategies.sort_by(|a, b| a.0.cmp(&b.0));
+        assert!(strategies.len() > 100);
+
+        for (name, strategy) in strategies {
+            let mut runner = TestRunner::new(Config { cases: 32, failure_persistence: None, ..Config::default() });
+            runner.run(&(strategy(), vec(1..32usize, 1..8)), |(packet, chunks)| check_round_trip(packet, chunks))
+                .unwrap_or_else(|error| panic!("{}: {}", name, error));
+        }
+    }
+
+    /// Frames of 16 KiB and up have a three byte length, so even the header arrives split
+    #[test]
+    fn test_large_frame_round_trips() {
+        let states = [BlockData::parse("minecraft:stone").unwrap(), BlockData::parse("minecraft:oak_stairs[facing=north,half=top]").unwrap()];
+        let mut changes = BlockChanges::new("world");
+        for index in 0..5000 {
+            changes.set(index % 16, index / 256, index / 16 % 16, &states[index as usize % 2]);
+        }
+        let packet: Box<dyn Packet> = Box::new(BukrsReqSetBlocks { changes, physics: false });
+        assert!(fields(packet.as_ref()).len() >= 16 * 1024);
+
+        let mut runner = TestRunner::new(Config { cases: 8, failure_persistence: None, ..Config::default() });
+        runner.run(&vec(1..4096usize, 1..8), |chunks| check_round_trip(packet.clone_box(), chunks)).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+//! Prints the JSON schema of the protocol, see `bukrs::schema`
+
+fn main() {
+    println!("{}", bukrs::schema::protocol_schema_json());
+}
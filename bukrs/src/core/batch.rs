@@ -0,0 +1,181 @@
+use std::{marker::PhantomData, time::Duration};
+
+use anyhow::{anyhow, bail};
+
+use crate::{API, net::{Packet, PacketList, BukrsReqBatch, BukrsResBatch, BukrsResError, cast_packet}};
+
+/// How long [`Batch::send`] waits for the responses unless [`Batch::timeout`] says otherwise
+pub const BATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests the server applies together within one tick, so no other packet or tick lands in between.
+/// Requests in a batch can't use each other's responses.
+pub struct Batch {
+    api: API,
+    requests: Vec<Box<dyn Packet>>,
+    timeout: Duration
+}
+
+impl Batch {
+    /// Adds a request whose response isn't needed
+    pub fn request(&mut self, request: impl Packet) {
+        self.requests.push(Box::new(request));
+    }
+
+    /// Adds a request answered with a `T`, which the handle takes out of the [`BatchResponses`]
+    pub fn add<T: Packet + Clone>(&mut self, request: impl Packet) -> BatchHandle<T> {
+        self.requests.push(Box::new(request));
+        BatchHandle { index: self.requests.len() - 1, response: PhantomData }
+    }
+
+    /// Fails [`Batch::send`] if the responses take longer than `timeout`
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Sends every request in one frame and waits for all responses, at most for the timeout
+    pub async fn send(mut self) -> anyhow::Result<BatchResponses> {
+        if self.requests.is_empty() {
+            return Ok(BatchResponses(vec![]));
+        }
+
+        let count = self.requests.len();
+        let response = self.api.send_packet_await(BukrsReqBatch { requests: PacketList(self.requests) });
+        let BukrsResBatch { responses } = tokio::time::timeout(self.timeout, response).await
+            .map_err(|_| anyhow!("No response to a batch of {} requests within {:?}", count, self.timeout))??;
+        if responses.0.len() != count {
+            bail!("Sent {} requests but got {} responses", count, responses.0.len());
+        }
+        Ok(BatchResponses(responses.0))
+    }
+}
+
+/// The response to one request of a [`Batch`], of type `T`
+#[derive(Debug)]
+pub struct BatchHandle<T> {
+    index: usize,
+    response: PhantomData<fn() -> T>
+}
+
+impl<T> Clone for BatchHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BatchHandle<T> {}
+
+/// Responses of a [`Batch`], in the order of the requests
+#[derive(Debug)]
+pub struct BatchResponses(Vec<Box<dyn Packet>>);
+
+impl BatchResponses {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The response to the request `handle` was given for, failing if the server couldn't handle the request or answered with another type
+    pub fn get<T: Packet + Clone>(&self, handle: BatchHandle<T>) -> anyhow::Result<T> {
+        let response = self.0.get(handle.index).ok_or_else(|| anyhow!("No response at {}", handle.index))?;
+        if let Some(BukrsResError { message }) = cast_packet(response) {
+            bail!("Request {} failed: {}", handle.index, message);
+        }
+        cast_packet(response).ok_or_else(|| anyhow!("Unexpected response at {}: {}", handle.index, response))
+    }
+
+    /// Every response, for batches of requests of one kind
+    pub fn all<T: Packet + Clone>(&self) -> anyhow::Result<Vec<T>> {
+        (0..self.0.len()).map(|index| self.get(BatchHandle { index, response: PhantomData })).collect()
+    }
+}
+
+impl API {
+    pub fn batch(&self) -> Batch {
+        Batch { api: self.clone(), requests: vec![], timeout: BATCH_TIMEOUT }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+
+    use crate::{core::{invfx::{InvfxId, InvList}, player::PlayerId}, net::{BukrsPacketData, BukrsReqBatch, BukrsResBatch, BukrsResError, BukrsReqCreateInvList, BukrsResCreateInvList, BukrsReqPlayerInvOpen, BukrsResPlayerInvOpen, PacketList, cast_packet}, tests::loopback};
+
+    #[tokio::test]
+    async fn test_batch_round_trip() {
+        let (api, mut server) = loopback().await;
+
+        let mut batch = api.batch();
+        let create = batch.add::<BukrsResCreateInvList>(BukrsReqCreateInvList { inv_id: InvfxId(7), list: InvList { id: InvfxId(7), data: vec![] } });
+        let wrong_type = batch.add::<BukrsResCreateInvList>(BukrsReqPlayerInvOpen { inv_id: InvfxId(7), player_id: PlayerId(1) });
+        batch.request(BukrsReqPlayerInvOpen { inv_id: InvfxId(7), player_id: PlayerId(2) });
+        assert_eq!(batch.len(), 3);
+
+        let accept = async {
+            let request = server.next().await.unwrap().unwrap();
+            assert!(request.payload_id.unwrap() <= i32::MAX as u32, "Negative ids as an i32 are the server's");
+            let BukrsReqBatch { requests } = cast_packet(&request.event).unwrap();
+            let names = requests.0.iter().map(|request| request.id()).collect::<Vec<String>>();
+            assert_eq!(names, ["BukrsReqCreateInvList", "BukrsReqPlayerInvOpen", "BukrsReqPlayerInvOpen"]);
+            let BukrsReqPlayerInvOpen { player_id, .. } = cast_packet(&requests.0[2]).unwrap();
+            assert_eq!(player_id, PlayerId(2));
+
+            let responses = PacketList(vec![Box::new(BukrsResCreateInvList {  }), Box::new(BukrsResPlayerInvOpen {  }), Box::new(BukrsResPlayerInvOpen {  })]);
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResBatch { responses }) }).await.unwrap();
+        };
+        let (responses, _) = tokio::join!(batch.send(), accept);
+        let responses = responses.unwrap();
+
+        let BukrsResCreateInvList {  } = responses.get(create).unwrap();
+        assert!(responses.get(wrong_type).is_err());
+        assert!(responses.all::<BukrsResPlayerInvOpen>().is_err());
+        assert_eq!(responses.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_failed_request() {
+        let (api, mut server) = loopback().await;
+
+        let mut batch = api.batch();
+        let opened = batch.add::<BukrsResPlayerInvOpen>(BukrsReqPlayerInvOpen { inv_id: InvfxId(7), player_id: PlayerId(1) });
+        let failed = batch.add::<BukrsResPlayerInvOpen>(BukrsReqPlayerInvOpen { inv_id: InvfxId(8), player_id: PlayerId(1) });
+
+        let accept = async {
+            let request = server.next().await.unwrap().unwrap();
+            let responses = PacketList(vec![Box::new(BukrsResPlayerInvOpen {  }), Box::new(BukrsResError { message: "No inventory 8".to_string() })]);
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResBatch { responses }) }).await.unwrap();
+        };
+        let (responses, _) = tokio::join!(batch.send(), accept);
+        let responses = responses.unwrap();
+
+        assert!(responses.get(opened).is_ok());
+        let error = responses.get(failed).unwrap_err().to_string();
+        assert!(error.contains("No inventory 8"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_batch_timeout() {
+        let (api, mut server) = loopback().await;
+
+        let mut batch = api.batch();
+        batch.request(BukrsReqPlayerInvOpen { inv_id: InvfxId(7), player_id: PlayerId(1) });
+        batch.timeout(Duration::from_millis(50));
+        let (responses, request) = tokio::join!(batch.send(), server.next());
+        assert!(request.is_some());
+        assert!(responses.is_err());
+        assert!(api.payload_listeners.lock().unwrap().is_empty());
+    }
+}
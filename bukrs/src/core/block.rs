@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use bukrs_derive::BukrsType;
+use serde::{Serialize, Deserialize};
+
+use crate::net::{BukrsSDBlockBreak, BukrsResBlockBreak, BukrsSDBlockPlace, BukrsResBlockPlace, BukrsSDPlayerInteract, BukrsResPlayerInteract};
+
+use super::{player::PlayerId, event::{CancellableEvent, MAIN_THREAD_DEADLINE}};
+
+/// Position of a block in a world, by world name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct BlockPos {
+    pub world: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32
+}
+
+impl BlockPos {
+    pub fn new(world: &str, x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos { world: world.to_string(), x, y, z }
+    }
+
+    /// The position moved by the given amounts, `None` if a coordinate overflows
+    pub fn offset(&self, x: i32, y: i32, z: i32) -> Option<BlockPos> {
+        Some(BlockPos { world: self.world.clone(), x: self.x.checked_add(x)?, y: self.y.checked_add(y)?, z: self.z.checked_add(z)? })
+    }
+}
+
+/// Namespaced material key, e.g. `minecraft:stone`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct Material(pub String);
+
+impl Material {
+    /// Adds the `minecraft:` namespace if none is given
+    pub fn new(key: &str) -> Material {
+        if key.contains(':') {
+            Material(key.to_string())
+        } else {
+            Material(format!("minecraft:{}", key))
+        }
+    }
+
+    pub fn is_air(&self) -> bool {
+        matches!(self.0.as_str(), "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air")
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BukrsType)]
+pub enum BlockFace {
+    North,
+    East,
+    South,
+    West,
+    Up,
+    Down,
+    Self_
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BukrsType)]
+pub enum Hand {
+    MainHand,
+    OffHand
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BukrsType)]
+pub enum InteractAction {
+    LeftClickBlock,
+    RightClickBlock,
+    LeftClickAir,
+    RightClickAir,
+    /// Stepping on pressure plates, trampling farmland
+    Physical
+}
+
+/// What a player interacted with
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub enum InteractTarget {
+    Air,
+    Block { pos: BlockPos, material: Material, face: BlockFace }
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockBreak {
+    pub player_id: PlayerId,
+    pub block: BlockPos,
+    pub material: Material,
+    pub drop_items: bool,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for BlockBreak {
+    const NAME: &'static str = "BlockBreak";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDBlockBreak;
+    type Verdict = BukrsResBlockBreak;
+
+    fn from_packet(packet: BukrsSDBlockBreak) -> Self {
+        let BukrsSDBlockBreak { player_id, block, material, drop_items } = packet;
+        BlockBreak { player_id, block, material, drop_items, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResBlockBreak {
+        BukrsResBlockBreak { cancelled: self.cancelled, drop_items: self.drop_items }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockPlace {
+    pub player_id: PlayerId,
+    pub block: BlockPos,
+    pub material: Material,
+    /// The block the new one was placed against
+    pub against: BlockPos,
+    pub hand: Hand,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for BlockPlace {
+    const NAME: &'static str = "BlockPlace";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDBlockPlace;
+    type Verdict = BukrsResBlockPlace;
+
+    fn from_packet(packet: BukrsSDBlockPlace) -> Self {
+        let BukrsSDBlockPlace { player_id, block, material, against, hand } = packet;
+        BlockPlace { player_id, block, material, against, hand, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResBlockPlace {
+        BukrsResBlockPlace { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerInteract {
+    pub player_id: PlayerId,
+    pub action: InteractAction,
+    pub target: InteractTarget,
+    pub hand: Hand,
+    /// Material of the item in `hand`, air if empty
+    pub item: Material,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for PlayerInteract {
+    const NAME: &'static str = "PlayerInteract";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDPlayerInteract;
+    type Verdict = BukrsResPlayerInteract;
+
+    fn from_packet(packet: BukrsSDPlayerInteract) -> Self {
+        let BukrsSDPlayerInteract { player_id, action, target, hand, item } = packet;
+        PlayerInteract { player_id, action, target, hand, item, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResPlayerInteract {
+        BukrsResPlayerInteract { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::{core::player::PlayerId, net::{Codec, BukrsPacketData, BukrsSDPlayerInteract, cast_packet}};
+
+    use super::{BlockPos, Material, BlockFace, Hand, InteractAction, InteractTarget};
+
+    #[test]
+    fn test_interact_codec() {
+        let target = InteractTarget::Block { pos: BlockPos::new("world", -12, 64, 300), material: Material::new("oak_door"), face: BlockFace::West };
+        let packet = BukrsSDPlayerInteract { player_id: PlayerId(7), action: InteractAction::RightClickBlock, target: target.clone(), hand: Hand::OffHand, item: Material::new("minecraft:air") };
+
+        let mut buf = BytesMut::new();
+        Codec.encode(BukrsPacketData { payload_id: Some(1024), event: Box::new(packet) }, &mut buf).unwrap();
+        let decoded = Codec.decode(&mut buf).unwrap().unwrap();
+        let BukrsSDPlayerInteract { player_id, action, target: decoded_target, hand, item } = cast_packet(&decoded.event).unwrap();
+
+        assert_eq!(player_id, PlayerId(7));
+        assert_eq!(action, InteractAction::RightClickBlock);
+        assert_eq!(decoded_target, target);
+        assert_eq!(hand, Hand::OffHand);
+        assert!(item.is_air());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_offset() {
+        let pos = BlockPos::new("world", -12, 64, 300);
+        assert_eq!(pos.offset(12, -1, 1), Some(BlockPos::new("world", 0, 63, 301)));
+        assert_eq!(pos.offset(0, i32::MAX, 0), None);
+        assert_eq!(BlockPos::new("world", i32::MIN, 0, 0).offset(-1, 0, 0), None);
+    }
+}
@@ -0,0 +1,302 @@
+use std::{ops::BitOr, sync::{Arc, Mutex}};
+
+use anyhow::bail;
+use bukrs_derive::BukrsType;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqCreateBossBar, BukrsReqUpdateBossBar, BukrsReqRemoveBossBar, BukrsResBossBar}};
+
+use super::{player::PlayerId, scheduler::TaskHandle};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub enum BarColor {
+    Pink,
+    Blue,
+    Red,
+    Green,
+    Yellow,
+    #[default]
+    Purple,
+    White
+}
+
+/// Solid, or split into that many segments
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub enum BarStyle {
+    #[default]
+    Solid,
+    Segmented6,
+    Segmented10,
+    Segmented12,
+    Segmented20
+}
+
+/// Effects a boss bar has on the players seeing it, sent as a bit set
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub struct BarFlags(pub u8);
+
+impl BarFlags {
+    pub const DARKEN_SKY: BarFlags = BarFlags(1);
+    pub const PLAY_BOSS_MUSIC: BarFlags = BarFlags(1 << 1);
+    pub const CREATE_FOG: BarFlags = BarFlags(1 << 2);
+
+    pub fn contains(&self, flags: BarFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl BitOr for BarFlags {
+    type Output = BarFlags;
+
+    fn bitor(self, rhs: BarFlags) -> BarFlags {
+        BarFlags(self.0 | rhs.0)
+    }
+}
+
+/// Id the client picks for a boss bar
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct BossBarId(pub u32);
+
+/// One changed property of a boss bar
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BukrsType)]
+pub enum BossBarChange {
+    /// May use `§` colour codes
+    Title(String),
+    Progress(f64),
+    Color(BarColor),
+    Style(BarStyle),
+    Flags(BarFlags),
+    AddPlayers(Vec<PlayerId>),
+    RemovePlayers(Vec<PlayerId>)
+}
+
+struct BossBarState {
+    title: String,
+    progress: f64,
+    color: BarColor,
+    style: BarStyle,
+    flags: BarFlags,
+    players: Vec<PlayerId>,
+    animation: Option<TaskHandle>
+}
+
+impl BossBarState {
+    fn apply(&mut self, change: BossBarChange) {
+        match change {
+            BossBarChange::Title(title) => self.title = title,
+            BossBarChange::Progress(progress) => self.progress = progress,
+            BossBarChange::Color(color) => self.color = color,
+            BossBarChange::Style(style) => self.style = style,
+            BossBarChange::Flags(flags) => self.flags = flags,
+            BossBarChange::AddPlayers(players) => self.players.extend(players.into_iter().filter(|player| !self.players.contains(player)).collect::<Vec<PlayerId>>()),
+            BossBarChange::RemovePlayers(players) => self.players.retain(|player| !players.contains(player))
+        }
+    }
+}
+
+/// A boss bar shown to the players added to it. The state is kept here and only changes are sent,
+/// so setting a property to its current value costs nothing. Clones share the bar.
+#[derive(Clone)]
+pub struct BossBar {
+    pub id: BossBarId,
+    api: API,
+    state: Arc<Mutex<BossBarState>>
+}
+
+impl BossBar {
+    pub fn title(&self) -> String {
+        self.state.lock().unwrap().title.clone()
+    }
+
+    pub fn progress(&self) -> f64 {
+        self.state.lock().unwrap().progress
+    }
+
+    pub fn color(&self) -> BarColor {
+        self.state.lock().unwrap().color
+    }
+
+    pub fn style(&self) -> BarStyle {
+        self.state.lock().unwrap().style
+    }
+
+    pub fn flags(&self) -> BarFlags {
+        self.state.lock().unwrap().flags
+    }
+
+    pub fn players(&self) -> Vec<PlayerId> {
+        self.state.lock().unwrap().players.clone()
+    }
+
+    async fn send(&self, changes: Vec<BossBarChange>) -> anyhow::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let BukrsResBossBar { found } = self.api.clone().send_packet_await(BukrsReqUpdateBossBar { bar_id: self.id, changes }).await?;
+        if !found {
+            bail!("Boss bar {} was removed", self.id.0);
+        }
+        Ok(())
+    }
+
+    /// Sends the change `update` derives from the state, if any, and applies it once the server took it
+    async fn update(&self, update: impl FnOnce(&BossBarState) -> Option<BossBarChange>) -> anyhow::Result<()> {
+        let Some(change) = update(&self.state.lock().unwrap()) else {
+            return Ok(());
+        };
+        self.send(vec![change.clone()]).await?;
+        self.state.lock().unwrap().apply(change);
+        Ok(())
+    }
+
+    pub async fn set_title(&self, title: &str) -> anyhow::Result<()> {
+        self.update(|state| (state.title != title).then(|| BossBarChange::Title(title.to_string()))).await
+    }
+
+    /// Clamped to `0.0..=1.0`. Stops a running animation.
+    pub async fn set_progress(&self, progress: f64) -> anyhow::Result<()> {
+        if let Some(animation) = self.state.lock().unwrap().animation.take() {
+            animation.cancel();
+        }
+        self.step_progress(progress).await
+    }
+
+    async fn step_progress(&self, progress: f64) -> anyhow::Result<()> {
+        let progress = progress.clamp(0.0, 1.0);
+        self.update(|state| (state.progress != progress).then_some(BossBarChange::Progress(progress))).await
+    }
+
+    pub async fn set_color(&self, color: BarColor) -> anyhow::Result<()> {
+        self.update(|state| (state.color != color).then_some(BossBarChange::Color(color))).await
+    }
+
+    pub async fn set_style(&self, style: BarStyle) -> anyhow::Result<()> {
+        self.update(|state| (state.style != style).then_some(BossBarChange::Style(style))).await
+    }
+
+    pub async fn set_flags(&self, flags: BarFlags) -> anyhow::Result<()> {
+        self.update(|state| (state.flags != flags).then_some(BossBarChange::Flags(flags))).await
+    }
+
+    /// Players already seeing the bar are skipped
+    pub async fn add_players(&self, players: &[PlayerId]) -> anyhow::Result<()> {
+        self.update(|state| {
+            let added = players.iter().filter(|player| !state.players.contains(player)).cloned().collect::<Vec<PlayerId>>();
+            (!added.is_empty()).then_some(BossBarChange::AddPlayers(added))
+        }).await
+    }
+
+    pub async fn remove_players(&self, players: &[PlayerId]) -> anyhow::Result<()> {
+        self.update(|state| {
+            let removed = players.iter().filter(|player| state.players.contains(player)).cloned().collect::<Vec<PlayerId>>();
+            (!removed.is_empty()).then_some(BossBarChange::RemovePlayers(removed))
+        }).await
+    }
+
+    /// Moves the progress to `target` over `ticks` server ticks, one step per tick.
+    /// Replaces a running animation; [`BossBar::set_progress`] stops it.
+    pub async fn animate_progress(&self, target: f64, ticks: u64) -> anyhow::Result<TaskHandle> {
+        let start = self.progress();
+        let ticks = ticks.max(1);
+        let step = Arc::new(Mutex::new(0));
+        let bar = self.clone();
+        let animation = self.api.scheduler().run_timer(1, 1, move || {
+            let bar = bar.clone();
+            let step = step.clone();
+            async move {
+                let step = {
+                    let mut step = step.lock().unwrap();
+                    *step += 1;
+                    *step
+                };
+                if step >= ticks {
+                    if let Some(animation) = bar.state.lock().unwrap().animation.take() {
+                        animation.cancel();
+                    }
+                }
+                let _ = bar.step_progress(start + (target - start) * step.min(ticks) as f64 / ticks as f64).await;
+            }
+        }).await?;
+
+        if let Some(previous) = self.state.lock().unwrap().animation.replace(animation.clone()) {
+            previous.cancel();
+        }
+        Ok(animation)
+    }
+
+    /// Hides the bar from everyone and forgets it on the server
+    pub async fn remove(self) -> anyhow::Result<()> {
+        if let Some(animation) = self.state.lock().unwrap().animation.take() {
+            animation.cancel();
+        }
+        let BukrsResBossBar { .. } = self.api.clone().send_packet_await(BukrsReqRemoveBossBar { bar_id: self.id }).await?;
+        Ok(())
+    }
+}
+
+impl API {
+    /// Creates a boss bar nobody sees yet
+    pub async fn create_boss_bar(&mut self, title: &str, color: BarColor, style: BarStyle) -> anyhow::Result<BossBar> {
+        let id = BossBarId(rand::thread_rng().gen_range(1..u32::MAX));
+        let BukrsResBossBar { .. } = self.send_packet_await(BukrsReqCreateBossBar { bar_id: id, title: title.to_string(), color, style }).await?;
+        let state = BossBarState { title: title.to_string(), progress: 1.0, color, style, flags: BarFlags::default(), players: vec![], animation: None };
+        Ok(BossBar { id, api: self.clone(), state: Arc::new(Mutex::new(state)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::SinkExt;
+
+    use crate::{core::player::PlayerId, net::{BukrsPacketData, BukrsReqCreateBossBar, BukrsReqUpdateBossBar, BukrsResBossBar, BukrsReqSubscribeTicks, BukrsResSubscribeTicks, BukrsSDTick}, tests::{loopback, respond}};
+
+    use super::{BarColor, BarStyle, BossBarChange};
+
+    #[tokio::test]
+    async fn test_deltas_and_animation() {
+        let (mut api, mut server) = loopback().await;
+
+        let (bar, request) = tokio::join!(api.create_boss_bar("Round 1", BarColor::Red, BarStyle::Segmented10), respond::<BukrsReqCreateBossBar>(&mut server, BukrsResBossBar { found: true }));
+        let bar = bar.unwrap();
+        assert_eq!(request.title, "Round 1");
+
+        bar.set_color(BarColor::Red).await.unwrap();   // Unchanged, nothing is sent
+        let (added, request) = tokio::join!(bar.add_players(&[PlayerId(1), PlayerId(2)]), respond::<BukrsReqUpdateBossBar>(&mut server, BukrsResBossBar { found: true }));
+        added.unwrap();
+        assert_eq!(request.changes, vec![BossBarChange::AddPlayers(vec![PlayerId(1), PlayerId(2)])]);
+        let (added, request) = tokio::join!(bar.add_players(&[PlayerId(2), PlayerId(3)]), respond::<BukrsReqUpdateBossBar>(&mut server, BukrsResBossBar { found: true }));
+        added.unwrap();
+        assert_eq!(request.changes, vec![BossBarChange::AddPlayers(vec![PlayerId(3)])]);
+
+        let (animation, _) = tokio::join!(bar.animate_progress(0.0, 4), respond::<BukrsReqSubscribeTicks>(&mut server, BukrsResSubscribeTicks { tick: 10 }));
+        let animation = animation.unwrap();
+
+        let mut steps = vec![];
+        for tick in 11..=16 {
+            server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDTick { tick }) }).await.unwrap();
+            if tick <= 14 {
+                let request = respond::<BukrsReqUpdateBossBar>(&mut server, BukrsResBossBar { found: true }).await;
+                steps.extend(request.changes);
+            }
+        }
+        assert_eq!(steps, [0.75, 0.5, 0.25, 0.0].map(BossBarChange::Progress));
+
+        // The last step is applied once its response reached the animation's task
+        let finished = tokio::time::timeout(Duration::from_secs(1), async {
+            while animation.is_pending() || bar.progress() != 0.0 {
+                tokio::task::yield_now().await;
+            }
+        });
+        finished.await.expect("The animation didn't finish");
+
+        let (set, _) = tokio::join!(bar.set_title("Round 2"), respond::<BukrsReqUpdateBossBar>(&mut server, BukrsResBossBar { found: false }));
+        assert!(set.is_err());
+        assert_eq!(bar.title(), "Round 1");
+        let (removed, _) = tokio::join!(bar.remove_players(&[PlayerId(1)]), respond::<BukrsReqUpdateBossBar>(&mut server, BukrsResBossBar { found: false }));
+        assert!(removed.is_err());
+        assert_eq!(bar.players(), vec![PlayerId(1), PlayerId(2), PlayerId(3)]);
+    }
+}
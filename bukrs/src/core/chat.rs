@@ -0,0 +1,84 @@
+use std::{future::Future, time::Duration};
+
+use crate::{API, net::{BukrsSDPlayerChat, BukrsResPlayerChat}};
+
+use super::{player::PlayerId, event::CancellableEvent};
+
+/// A chat message on its way to the recipients. `format` is Bukkit's format string, e.g. `<%1$s> %2$s`.
+#[derive(Clone, Debug)]
+pub struct PlayerChat {
+    pub player_id: PlayerId,
+    pub message: String,
+    pub format: String,
+    pub recipients: Vec<PlayerId>,
+    pub cancelled: bool
+}
+
+impl PlayerChat {
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl CancellableEvent for PlayerChat {
+    const NAME: &'static str = "PlayerChat";
+    const DEADLINE: Duration = Duration::from_millis(500);   // Chat is handled off the main thread, so this only delays the message
+
+    type Packet = BukrsSDPlayerChat;
+    type Verdict = BukrsResPlayerChat;
+
+    fn from_packet(packet: BukrsSDPlayerChat) -> Self {
+        let BukrsSDPlayerChat { player_id, message, format, recipients } = packet;
+        PlayerChat { player_id, message, format, recipients, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResPlayerChat {
+        BukrsResPlayerChat { cancelled: self.cancelled, message: self.message.clone(), format: self.format.clone(), recipients: self.recipients.clone() }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+impl API {
+    /// Shorthand for a `Normal` priority [`PlayerChat`] listener.
+    /// The server delivers the original message if no verdict arrives within the deadline.
+    pub async fn on_chat<F, Fut>(&mut self, handler: F) -> anyhow::Result<()> where F: Fn(PlayerChat) -> Fut + Send + Sync + 'static, Fut: Future<Output = PlayerChat> + Send + 'static {
+        self.listen::<PlayerChat>().handle(handler).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+
+    use crate::{core::player::PlayerId, net::{BukrsPacketData, BukrsSDPlayerChat, BukrsResPlayerChat, cast_packet}, tests::{loopback, accept_subscription}};
+
+    #[tokio::test]
+    async fn test_chat_verdict() {
+        let (mut api, mut server) = loopback().await;
+
+        let subscribe = api.on_chat(|mut event| async move {
+            event.message = event.message.replace("heck", "****");
+            event.recipients.retain(|recipient| recipient != &PlayerId(2));
+            event
+        });
+        let (subscribed, _) = tokio::join!(subscribe, accept_subscription(&mut server));
+        subscribed.unwrap();
+
+        let chat = BukrsSDPlayerChat { player_id: PlayerId(1), message: "what the heck".to_string(), format: "<%1$s> %2$s".to_string(), recipients: vec![PlayerId(1), PlayerId(2)] };
+        server.send(BukrsPacketData { payload_id: Some(1024), event: Box::new(chat) }).await.unwrap();
+
+        let response = server.next().await.unwrap().unwrap();
+        assert_eq!(response.payload_id, Some(1024));
+        let BukrsResPlayerChat { cancelled, message, recipients, .. } = cast_packet(&response.event).unwrap();
+        assert!(!cancelled);
+        assert_eq!(message, "what the ****");
+        assert_eq!(recipients, vec![PlayerId(1)]);
+    }
+}
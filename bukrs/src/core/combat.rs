@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError, schema::TypeSchema};
+use bytes::BytesMut;
+use serde::{Serialize, Deserialize};
+
+use crate::net::{BukrsSDEntityDamage, BukrsResEntityDamage, BukrsSDPlayerDeath, BukrsResPlayerDeath};
+
+use super::{entity::EntityId, event::{CancellableEvent, MAIN_THREAD_DEADLINE}, item::Item, player::PlayerId};
+
+macro_rules! damage_causes {
+    ($($variant:ident => $name:literal),* $(,)?) => {
+        /// Bukkit's `DamageCause`, sent by name so causes added by newer servers arrive as `Other`
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum DamageCause {
+            $($variant,)*
+            Other(String)
+        }
+
+        impl DamageCause {
+            pub fn name(&self) -> &str {
+                match self {
+                    $(DamageCause::$variant => $name,)*
+                    DamageCause::Other(name) => name
+                }
+            }
+
+            pub fn from_name(name: &str) -> DamageCause {
+                match name {
+                    $($name => DamageCause::$variant,)*
+                    _ => DamageCause::Other(name.to_string())
+                }
+            }
+        }
+    };
+}
+
+damage_causes! {
+    Contact => "CONTACT",
+    EntityAttack => "ENTITY_ATTACK",
+    EntitySweepAttack => "ENTITY_SWEEP_ATTACK",
+    Projectile => "PROJECTILE",
+    Suffocation => "SUFFOCATION",
+    Fall => "FALL",
+    Fire => "FIRE",
+    FireTick => "FIRE_TICK",
+    Melting => "MELTING",
+    Lava => "LAVA",
+    Drowning => "DROWNING",
+    BlockExplosion => "BLOCK_EXPLOSION",
+    EntityExplosion => "ENTITY_EXPLOSION",
+    Void => "VOID",
+    Lightning => "LIGHTNING",
+    Suicide => "SUICIDE",
+    Starvation => "STARVATION",
+    Poison => "POISON",
+    Magic => "MAGIC",
+    Wither => "WITHER",
+    FallingBlock => "FALLING_BLOCK",
+    Thorns => "THORNS",
+    DragonBreath => "DRAGON_BREATH",
+    Custom => "CUSTOM",
+    FlyIntoWall => "FLY_INTO_WALL",
+    HotFloor => "HOT_FLOOR",
+    Cramming => "CRAMMING",
+    Dryout => "DRYOUT",
+    Freeze => "FREEZE",
+    SonicBoom => "SONIC_BOOM",
+}
+
+impl BukrsType for DamageCause {
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(DamageCause::from_name(&String::decode(bytes)?))
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.name().to_string().encode(bytes);
+    }
+
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::STRING
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::STRING)
+    }
+}
+
+/// Damage about to be dealt. Only `final_damage` and `cancelled` are sent back; `raw_damage` is
+/// the damage before armor, potions and enchantments.
+#[derive(Clone, Debug)]
+pub struct EntityDamage {
+    pub victim: EntityId,
+    pub attacker: Option<EntityId>,
+    pub cause: DamageCause,
+    pub raw_damage: f64,
+    pub final_damage: f64,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for EntityDamage {
+    const NAME: &'static str = "EntityDamage";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDEntityDamage;
+    type Verdict = BukrsResEntityDamage;
+
+    fn from_packet(packet: BukrsSDEntityDamage) -> Self {
+        let BukrsSDEntityDamage { victim, attacker, cause, raw_damage, final_damage } = packet;
+        EntityDamage { victim, attacker, cause, raw_damage, final_damage, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResEntityDamage {
+        BukrsResEntityDamage { cancelled: self.cancelled, final_damage: self.final_damage.max(0.0) }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+/// A player died. Cancelling keeps them alive, as Paper allows.
+#[derive(Clone, Debug)]
+pub struct PlayerDeath {
+    pub player_id: PlayerId,
+    /// If any item has a material the server doesn't know, the server keeps the original drops
+    pub drops: Vec<Item>,
+    pub dropped_exp: u32,
+    /// `None` hides the message
+    pub death_message: Option<String>,
+    pub keep_inventory: bool,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for PlayerDeath {
+    const NAME: &'static str = "PlayerDeath";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDPlayerDeath;
+    type Verdict = BukrsResPlayerDeath;
+
+    fn from_packet(packet: BukrsSDPlayerDeath) -> Self {
+        let BukrsSDPlayerDeath { player_id, drops, dropped_exp, death_message, keep_inventory } = packet;
+        let death_message = if death_message.is_empty() { None } else { Some(death_message) };
+        PlayerDeath { player_id, drops, dropped_exp, death_message, keep_inventory, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResPlayerDeath {
+        BukrsResPlayerDeath {
+            cancelled: self.cancelled,
+            drops: self.drops.clone(),
+            dropped_exp: self.dropped_exp,
+            death_message: self.death_message.clone().unwrap_or_default(),
+            keep_inventory: self.keep_inventory
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+
+    use crate::{core::{block::Material, entity::EntityId, item::Item, player::PlayerId}, net::{BukrsPacketData, BukrsSDEntityDamage, BukrsResEntityDamage, BukrsSDPlayerDeath, BukrsResPlayerDeath, cast_packet}, tests::{loopback, accept_subscription}};
+
+    use super::{DamageCause, EntityDamage, PlayerDeath};
+
+    #[tokio::test]
+    async fn test_damage_and_death_verdicts() {
+        let (mut api, mut server) = loopback().await;
+
+        let halve_melee = api.listen::<EntityDamage>().handle(|mut event: EntityDamage| async move {
+            if event.attacker.is_some() && event.cause == DamageCause::EntityAttack {
+                event.final_damage /= 2.0;
+            }
+            event
+        });
+        let (subscribed, _) = tokio::join!(halve_melee, accept_subscription(&mut server));
+        subscribed.unwrap();
+
+        let strip_diamonds = api.listen::<PlayerDeath>().handle(|mut event: PlayerDeath| async move {
+            event.drops.retain(|item| item.material != Material::new("diamond"));
+            event.death_message = None;
+            event
+        });
+        let (subscribed, _) = tokio::join!(strip_diamonds, accept_subscription(&mut server));
+        subscribed.unwrap();
+
+        let damage = BukrsSDEntityDamage { victim: EntityId(1), attacker: Some(EntityId(2)), cause: DamageCause::from_name("ENTITY_ATTACK"), raw_damage: 10.0, final_damage: 8.0 };
+        server.send(BukrsPacketData { payload_id: Some(1024), event: Box::new(damage) }).await.unwrap();
+        let response = server.next().await.unwrap().unwrap();
+        let BukrsResEntityDamage { cancelled, final_damage } = cast_packet(&response.event).unwrap();
+        assert_eq!((cancelled, final_damage), (false, 4.0));
+
+        let drops = vec![Item::new(Material::new("diamond"), 3), Item { material: Material::new("dirt"), amount: 64, data: vec![1, 2, 3] }];
+        let death = BukrsSDPlayerDeath { player_id: PlayerId(1), drops, dropped_exp: 7, death_message: "Steve was slain".to_string(), keep_inventory: false };
+        server.send(BukrsPacketData { payload_id: Some(1025), event: Box::new(death) }).await.unwrap();
+        let response = server.next().await.unwrap().unwrap();
+        let BukrsResPlayerDeath { drops, death_message, dropped_exp, .. } = cast_packet(&response.event).unwrap();
+        assert_eq!(drops, vec![Item { material: Material::new("dirt"), amount: 64, data: vec![1, 2, 3] }]);
+        assert_eq!((death_message.as_str(), dropped_exp), ("", 7));
+
+        assert_eq!(DamageCause::from_name("KILL"), DamageCause::Other("KILL".to_string()));
+    }
+}
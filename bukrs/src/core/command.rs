@@ -0,0 +1,508 @@
+use std::{any::Any, collections::HashMap, future::Future, sync::Arc, time::Duration};
+
+use bukrs_derive::BukrsType;
+use futures::future::BoxFuture;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqRegisterCommand, BukrsResRegisterCommand, BukrsReqSendMessage, BukrsResSendMessage, BukrsSDCommand, BukrsSDTabComplete, BukrsResTabComplete}};
+
+use super::player::PlayerId;
+
+/// Whoever ran the command
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub enum CommandSender {
+    Console,
+    Player(PlayerId)
+}
+
+/// How the server should treat an argument (completion, player name resolution)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BukrsType)]
+pub enum ArgKind {
+    String,
+    Integer,
+    Decimal,
+    Boolean,
+    Player
+}
+
+/// Wire representation of a single argument
+#[derive(Serialize, Deserialize, Clone, Debug, BukrsType)]
+pub struct ArgSpec {
+    pub name: String,
+    pub kind: ArgKind,
+    pub optional: bool
+}
+
+/// Wire representation of a command tree. An empty `permission` means no permission is required.
+#[derive(Serialize, Deserialize, Clone, Debug, BukrsType)]
+pub struct CommandSpec {
+    pub name: String,
+    pub permission: String,
+    pub usage: String,
+    pub args: Vec<ArgSpec>,
+    #[bukrs(strategy = "crate::arbitrary::subcommands")]
+    pub subcommands: Vec<CommandSpec>
+}
+
+/// A type that can be parsed from a raw command argument
+pub trait CommandArg: Sized + Send + Sync + 'static {
+    fn kind() -> ArgKind;
+
+    fn parse(raw: &str) -> Result<Self, String>;
+}
+
+impl CommandArg for String {
+    fn kind() -> ArgKind {
+        ArgKind::String
+    }
+
+    fn parse(raw: &str) -> Result<Self, String> {
+        Ok(raw.to_string())
+    }
+}
+
+macro_rules! number_arg {
+    ($kind:ident, $expected:literal, $($typ:ty),*) => {
+        $(
+            impl CommandArg for $typ {
+                fn kind() -> ArgKind {
+                    ArgKind::$kind
+                }
+
+                fn parse(raw: &str) -> Result<Self, String> {
+                    raw.parse().map_err(|_| format!("`{}` is not {}", raw, $expected))
+                }
+            }
+        )*
+    };
+}
+
+number_arg!(Integer, "an integer", u8, u16, u32, u64, i8, i16, i32, i64);
+number_arg!(Decimal, "a number", f32, f64);
+
+impl CommandArg for bool {
+    fn kind() -> ArgKind {
+        ArgKind::Boolean
+    }
+
+    fn parse(raw: &str) -> Result<Self, String> {
+        match raw.to_ascii_lowercase().as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(format!("`{}` is not true or false", raw))
+        }
+    }
+}
+
+/// The plugin replaces names of online players with `#` and their entity id before forwarding the invocation.
+/// Anything else is a name it couldn't resolve, even if it looks like an id.
+impl CommandArg for PlayerId {
+    fn kind() -> ArgKind {
+        ArgKind::Player
+    }
+
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.strip_prefix('#').and_then(|id| id.parse().ok()).map(PlayerId).ok_or_else(|| format!("Player `{}` is not online", raw))
+    }
+}
+
+pub type ArgValue = Box<dyn Any + Send + Sync>;
+
+pub type CommandHandler = Arc<dyn Fn(CommandContext) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+fn parse_boxed<T: CommandArg>(raw: &str) -> Result<ArgValue, String> {
+    T::parse(raw).map(|value| Box::new(value) as ArgValue)
+}
+
+/// How long completion providers get before they are cut off. The server waits 50ms for suggestions before showing none,
+/// the rest of that is left for the round trip.
+pub const COMPLETION_BUDGET: Duration = Duration::from_millis(40);
+
+pub type CompletionProvider = Arc<dyn Fn(CompletionContext) -> BoxFuture<'static, Vec<String>> + Send + Sync>;
+
+/// Source of tab-completion suggestions for an argument
+#[derive(Clone)]
+pub enum Completion {
+    Static(Vec<String>),
+    /// Names of online players, taken from the player cache
+    Players,
+    Provider(CompletionProvider)
+}
+
+impl Completion {
+    pub fn list(values: &[&str]) -> Completion {
+        Completion::Static(values.iter().map(|value| value.to_string()).collect())
+    }
+
+    pub fn from_fn<F, Fut>(provider: F) -> Completion where F: Fn(CompletionContext) -> Fut + Send + Sync + 'static, Fut: Future<Output = Vec<String>> + Send + 'static {
+        Completion::Provider(Arc::new(move |ctx| Box::pin(provider(ctx))))
+    }
+
+    fn default_for(kind: ArgKind) -> Option<Completion> {
+        match kind {
+            ArgKind::Boolean => Some(Completion::list(&["true", "false"])),
+            ArgKind::Player => Some(Completion::Players),
+            _ => None
+        }
+    }
+}
+
+/// Passed to a completion provider. `args` are the arguments before the one being completed.
+pub struct CompletionContext {
+    pub api: API,
+    pub sender: CommandSender,
+    pub args: Vec<String>,
+    pub current: String
+}
+
+pub struct CommandArgument {
+    pub name: String,
+    pub kind: ArgKind,
+    pub optional: bool,
+    pub completion: Option<Completion>,
+    parser: fn(&str) -> Result<ArgValue, String>
+}
+
+/// A command node: its typed arguments, subcommands and the handler run on invocation
+pub struct Command {
+    pub name: String,
+    pub permission: Option<String>,
+    pub args: Vec<CommandArgument>,
+    pub subcommands: Vec<Command>,
+    pub(crate) handler: Option<CommandHandler>
+}
+
+/// A resolved invocation: the executed node, its full label and the parsed arguments
+pub(crate) struct Invocation<'a> {
+    pub(crate) command: &'a Command,
+    pub(crate) label: String,
+    pub(crate) args: HashMap<String, ArgValue>
+}
+
+impl Command {
+    pub fn new(name: &str) -> Command {
+        Command { name: name.to_string(), permission: None, args: vec![], subcommands: vec![], handler: None }
+    }
+
+    fn push_arg<T: CommandArg>(mut self, name: &str, optional: bool) -> Command {
+        if !optional && self.args.iter().any(|arg| arg.optional) {
+            panic!("Required argument `{}` cannot follow an optional argument", name);
+        }
+        self.args.push(CommandArgument { name: name.to_string(), kind: T::kind(), optional, completion: None, parser: parse_boxed::<T> });
+        self
+    }
+
+    pub fn arg<T: CommandArg>(self, name: &str) -> Command {
+        self.push_arg::<T>(name, false)
+    }
+
+    pub fn arg_opt<T: CommandArg>(self, name: &str) -> Command {
+        self.push_arg::<T>(name, true)
+    }
+
+    /// Overrides the suggestions for a previously declared argument
+    pub fn complete_with(mut self, name: &str, completion: Completion) -> Command {
+        let arg = self.args.iter_mut().find(|arg| arg.name == name).unwrap_or_else(|| panic!("Unknown argument `{}`", name));
+        arg.completion = Some(completion);
+        self
+    }
+
+    pub fn permission(mut self, permission: &str) -> Command {
+        self.permission = Some(permission.to_string());
+        self
+    }
+
+    pub fn subcommand(mut self, subcommand: Command) -> Command {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    pub fn executes<F, Fut>(mut self, handler: F) -> Command where F: Fn(CommandContext) -> Fut + Send + Sync + 'static, Fut: Future<Output = anyhow::Result<()>> + Send + 'static {
+        self.handler = Some(Arc::new(move |ctx| Box::pin(handler(ctx))));
+        self
+    }
+
+    /// Usage line shown to the sender, e.g. `/warp <name> [target]`
+    pub fn usage(&self, label: &str) -> String {
+        let mut usage = format!("/{}", label);
+        if self.handler.is_none() && !self.subcommands.is_empty() {
+            let names = self.subcommands.iter().map(|sub| sub.name.as_str()).collect::<Vec<&str>>();
+            usage.push_str(&format!(" <{}>", names.join("|")));
+        }
+        for arg in self.args.iter() {
+            if arg.optional {
+                usage.push_str(&format!(" [{}]", arg.name));
+            } else {
+                usage.push_str(&format!(" <{}>", arg.name));
+            }
+        }
+        usage
+    }
+
+    pub fn spec(&self) -> CommandSpec {
+        self.spec_with_label(&self.name)
+    }
+
+    fn spec_with_label(&self, label: &str) -> CommandSpec {
+        CommandSpec {
+            name: self.name.clone(),
+            permission: self.permission.clone().unwrap_or_default(),
+            usage: self.usage(label),
+            args: self.args.iter().map(|arg| ArgSpec { name: arg.name.clone(), kind: arg.kind, optional: arg.optional }).collect(),
+            subcommands: self.subcommands.iter().map(|sub| sub.spec_with_label(&format!("{} {}", label, sub.name))).collect()
+        }
+    }
+
+    /// Walks subcommands and parses the remaining arguments. `Err` holds the message for the sender.
+    pub(crate) fn resolve(&self, label: String, args: &[String]) -> Result<Invocation<'_>, String> {
+        if let Some((first, rest)) = args.split_first() {
+            if let Some(sub) = self.subcommands.iter().find(|sub| sub.name.eq_ignore_ascii_case(first)) {
+                return sub.resolve(format!("{} {}", label, sub.name), rest);
+            }
+        }
+
+        let required = self.args.iter().filter(|arg| !arg.optional).count();
+        if self.handler.is_none() || args.len() < required || args.len() > self.args.len() {
+            return Err(format!("Usage: {}", self.usage(&label)));
+        }
+
+        let mut parsed = HashMap::new();
+        for (arg, raw) in self.args.iter().zip(args.iter()) {
+            let value = (arg.parser)(raw).map_err(|err| format!("{}\nUsage: {}", err, self.usage(&label)))?;
+            parsed.insert(arg.name.clone(), value);
+        }
+
+        Ok(Invocation { command: self, label, args: parsed })
+    }
+}
+
+impl Command {
+    /// Descends into subcommands for every argument but the one being completed
+    pub(crate) fn completion_node<'b>(&self, args: &'b [String]) -> (&Command, &'b [String]) {
+        match args.split_first() {
+            Some((first, rest)) if !rest.is_empty() => match self.subcommands.iter().find(|sub| sub.name.eq_ignore_ascii_case(first)) {
+                Some(sub) => sub.completion_node(rest),
+                None => (self, args)
+            },
+            _ => (self, args)
+        }
+    }
+
+    pub(crate) async fn suggest(&self, api: &API, sender: CommandSender, args: &[String]) -> Vec<String> {
+        let (node, args) = self.completion_node(args);
+        let Some((current, preceding)) = args.split_last() else { return vec![] };
+
+        let mut suggestions = vec![];
+        if preceding.is_empty() {
+            suggestions.extend(node.subcommands.iter().map(|sub| sub.name.clone()));
+        }
+        if let Some(arg) = node.args.get(preceding.len()) {
+            match arg.completion.clone().or_else(|| Completion::default_for(arg.kind)) {
+                Some(Completion::Static(values)) => suggestions.extend(values),
+                Some(Completion::Players) => suggestions.extend(api.cached_players().into_iter().map(|player| player.name)),
+                Some(Completion::Provider(provider)) => {
+                    let ctx = CompletionContext { api: api.clone(), sender, args: preceding.to_vec(), current: current.clone() };
+                    suggestions.extend(provider(ctx).await);
+                }
+                None => {}
+            }
+        }
+
+        let current = current.to_lowercase();
+        suggestions.retain(|suggestion| suggestion.to_lowercase().starts_with(&current));
+        suggestions
+    }
+}
+
+/// Passed to the command handler on every invocation
+pub struct CommandContext {
+    pub api: API,
+    pub sender: CommandSender,
+    pub label: String,
+    pub(crate) args: HashMap<String, ArgValue>
+}
+
+impl CommandContext {
+    /// Parsed argument by name. `None` if an optional argument was omitted.
+    pub fn get<T: CommandArg + Clone>(&self, name: &str) -> Option<T> {
+        self.args.get(name)?.downcast_ref::<T>().cloned()
+    }
+
+    pub async fn reply(&mut self, message: &str) -> anyhow::Result<()> {
+        send_message(&mut self.api, self.sender.clone(), message).await
+    }
+}
+
+async fn send_message(api: &mut API, target: CommandSender, message: &str) -> anyhow::Result<()> {
+    let BukrsResSendMessage {  } = api.send_packet_await(BukrsReqSendMessage { target, message: message.to_string() }).await?;
+    Ok(())
+}
+
+/// Builder returned by [`API::register_command`]
+pub struct RegisterCommand<'a> {
+    api: &'a mut API,
+    command: Command
+}
+
+impl<'a> RegisterCommand<'a> {
+    pub fn arg<T: CommandArg>(mut self, name: &str) -> RegisterCommand<'a> {
+        self.command = self.command.arg::<T>(name);
+        self
+    }
+
+    pub fn arg_opt<T: CommandArg>(mut self, name: &str) -> RegisterCommand<'a> {
+        self.command = self.command.arg_opt::<T>(name);
+        self
+    }
+
+    pub fn complete_with(mut self, name: &str, completion: Completion) -> RegisterCommand<'a> {
+        self.command = self.command.complete_with(name, completion);
+        self
+    }
+
+    pub fn permission(mut self, permission: &str) -> RegisterCommand<'a> {
+        self.command = self.command.permission(permission);
+        self
+    }
+
+    pub fn subcommand(mut self, subcommand: Command) -> RegisterCommand<'a> {
+        self.command = self.command.subcommand(subcommand);
+        self
+    }
+
+    /// Sets the handler and registers the command on the server
+    pub async fn executes<F, Fut>(mut self, handler: F) -> anyhow::Result<()> where F: Fn(CommandContext) -> Fut + Send + Sync + 'static, Fut: Future<Output = anyhow::Result<()>> + Send + 'static {
+        self.command = self.command.executes(handler);
+        self.register().await
+    }
+
+    /// Registers a command without a handler of its own, e.g. one that only groups subcommands
+    pub async fn register(self) -> anyhow::Result<()> {
+        let BukrsResRegisterCommand {  } = self.api.send_packet_await(BukrsReqRegisterCommand { command: self.command.spec() }).await?;
+        self.api.commands.lock().unwrap().insert(self.command.name.to_lowercase(), Arc::new(self.command));
+        Ok(())
+    }
+}
+
+impl API {
+    pub fn register_command(&mut self, name: &str) -> RegisterCommand<'_> {
+        RegisterCommand { api: self, command: Command::new(name) }
+    }
+}
+
+/// Runs the handler for an incoming invocation, or sends the usage message back to the sender
+pub(crate) fn dispatch(api: &API, invocation: BukrsSDCommand) {
+    let command = api.commands.lock().unwrap().get(&invocation.label.to_lowercase()).cloned();
+    let Some(command) = command else { return };
+    let mut api = api.clone();
+
+    tokio::spawn(async move {
+        let BukrsSDCommand { sender, label, args } = invocation;
+        let resolved = command.resolve(label, &args).map(|Invocation { command, label, args }| (command.handler.clone().unwrap(), label, args));
+        match resolved {
+            Ok((handler, label, args)) => {
+                let ctx = CommandContext { api: api.clone(), sender: sender.clone(), label, args };
+                if let Err(err) = handler(ctx).await {
+                    send_message(&mut api, sender, &err.to_string()).await
+                } else {
+                    Ok(())
+                }
+            }
+            Err(usage) => send_message(&mut api, sender, &usage).await
+        }
+    });
+}
+
+/// Answers a tab-completion request, with no suggestions if the budget runs out
+pub(crate) fn complete(api: &API, payload_id: Option<u32>, request: BukrsSDTabComplete) {
+    let command = api.commands.lock().unwrap().get(&request.label.to_lowercase()).cloned();
+    let mut api = api.clone();
+
+    tokio::spawn(async move {
+        let suggestions = match command {
+            Some(command) => tokio::time::timeout(COMPLETION_BUDGET, command.suggest(&api, request.sender, &request.args)).await.unwrap_or_default(),
+            None => vec![]
+        };
+        api.send_packet(BukrsResTabComplete { suggestions }, payload_id).await
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::{Duration, Instant}};
+
+    use futures::{SinkExt, StreamExt};
+
+    use crate::{core::player::PlayerId, net::{BukrsPacketData, BukrsSDTabComplete, BukrsResTabComplete, cast_packet}, tests::loopback};
+
+    use super::{Command, CommandSender, Completion, COMPLETION_BUDGET};
+
+    fn warp() -> Command {
+        Command::new("warp")
+            .subcommand(Command::new("set").arg::<String>("name").executes(|_| async { Ok(()) }))
+            .subcommand(Command::new("go").arg::<String>("name").arg_opt::<PlayerId>("target").arg_opt::<u32>("delay").executes(|_| async { Ok(()) }))
+    }
+
+    fn args(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve_subcommand() {
+        let warp = warp();
+        let invocation = warp.resolve("warp".to_string(), &args(&["go", "spawn", "#1024"])).unwrap();
+        assert_eq!(invocation.label, "warp go");
+        assert_eq!(invocation.args["name"].downcast_ref::<String>().unwrap(), "spawn");
+        assert_eq!(invocation.args["target"].downcast_ref::<PlayerId>().unwrap(), &PlayerId(1024));
+        assert!(!invocation.args.contains_key("delay"));
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let warp = warp();
+        assert_eq!(warp.resolve("warp".to_string(), &args(&[])).err().unwrap(), "Usage: /warp <set|go>");
+        assert_eq!(warp.resolve("warp".to_string(), &args(&["set"])).err().unwrap(), "Usage: /warp set <name>");
+        assert_eq!(warp.resolve("warp".to_string(), &args(&["go", "spawn", "#1024", "soon"])).err().unwrap(), "`soon` is not an integer\nUsage: /warp go <name> [target] [delay]");
+        assert_eq!(warp.resolve("warp".to_string(), &args(&["go", "spawn", "42"])).err().unwrap(), "Player `42` is not online\nUsage: /warp go <name> [target] [delay]");
+    }
+
+    #[tokio::test]
+    async fn test_tab_complete() {
+        let (api, mut server) = loopback().await;
+        let warp = Command::new("warp")
+            .subcommand(Command::new("set").arg::<String>("name").executes(|_| async { Ok(()) }))
+            .subcommand(Command::new("go").arg::<String>("name").complete_with("name", Completion::list(&["spawn", "shop", "arena"])).executes(|_| async { Ok(()) }));
+        api.commands.lock().unwrap().insert("warp".to_string(), Arc::new(warp));
+
+        for (args, expected) in [(vec!["go", "s"], vec!["spawn", "shop"]), (vec![""], vec!["set", "go"]), (vec!["go", "spawn", "x"], vec![])] {
+            let request = BukrsSDTabComplete { sender: CommandSender::Console, label: "warp".to_string(), args: args.iter().map(|arg| arg.to_string()).collect() };
+            server.send(BukrsPacketData { payload_id: Some(1024), event: Box::new(request) }).await.unwrap();
+
+            let response = server.next().await.unwrap().unwrap();
+            assert_eq!(response.payload_id, Some(1024));
+            let BukrsResTabComplete { suggestions } = cast_packet(&response.event).unwrap();
+            assert_eq!(suggestions, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completion_budget() {
+        let (api, mut server) = loopback().await;
+        let slow = Completion::from_fn(|_| async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            vec!["too late".to_string()]
+        });
+        let warp = Command::new("warp").arg::<String>("name").complete_with("name", slow).executes(|_| async { Ok(()) });
+        api.commands.lock().unwrap().insert("warp".to_string(), Arc::new(warp));
+
+        let request = BukrsSDTabComplete { sender: CommandSender::Console, label: "warp".to_string(), args: vec!["s".to_string()] };
+        let start = Instant::now();
+        server.send(BukrsPacketData { payload_id: Some(1024), event: Box::new(request) }).await.unwrap();
+
+        let response = server.next().await.unwrap().unwrap();
+        let elapsed = start.elapsed();
+        let BukrsResTabComplete { suggestions } = cast_packet(&response.event).unwrap();
+        assert!(suggestions.is_empty());
+        assert!(elapsed >= COMPLETION_BUDGET && elapsed < Duration::from_millis(50), "Answered after {:?}", elapsed);
+    }
+}
@@ -0,0 +1,233 @@
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError, schema::TypeSchema};
+use bukrs_derive::BukrsType;
+use bytes::BytesMut;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqPlaySound, BukrsReqSpawnParticle, BukrsReqSendTitle, BukrsResEffect}};
+
+use super::{block::Material, player::PlayerId, world::{BlockData, Location, Vector}};
+
+/// Enum sent by name, so names the client doesn't know arrive as `Other`
+macro_rules! named_enum {
+    ($(#[$meta:meta])* $enum:ident { $($(#[$variant_meta:meta])* $variant:ident => $name:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum $enum {
+            $($(#[$variant_meta])* $variant,)*
+            Other(String)
+        }
+
+        impl $enum {
+            pub fn name(&self) -> &str {
+                match self {
+                    $($enum::$variant => $name,)*
+                    $enum::Other(name) => name
+                }
+            }
+
+            pub fn from_name(name: &str) -> $enum {
+                match name {
+                    $($name => $enum::$variant,)*
+                    _ => $enum::Other(name.to_string())
+                }
+            }
+        }
+
+        impl BukrsType for $enum {
+            fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+                Ok($enum::from_name(&String::decode(bytes)?))
+            }
+
+            fn encode(&self, bytes: &mut BytesMut) {
+                self.name().to_string().encode(bytes);
+            }
+
+            fn ty(&self) -> BukrsNativeType {
+                BukrsNativeType::STRING
+            }
+
+            fn schema() -> TypeSchema {
+                TypeSchema::native(BukrsNativeType::STRING)
+            }
+        }
+    };
+}
+
+named_enum! {
+    /// Sound by its namespaced key. `Other` also plays sounds from resource packs.
+    Sound {
+        UiButtonClick => "minecraft:ui.button.click",
+        UiToastChallengeComplete => "minecraft:ui.toast.challenge_complete",
+        EntityExperienceOrbPickup => "minecraft:entity.experience_orb.pickup",
+        EntityPlayerLevelup => "minecraft:entity.player.levelup",
+        EntityPlayerHurt => "minecraft:entity.player.hurt",
+        EntityPlayerAttackCrit => "minecraft:entity.player.attack.crit",
+        EntityItemPickup => "minecraft:entity.item.pickup",
+        EntityArrowHitPlayer => "minecraft:entity.arrow.hit_player",
+        EntityEndermanTeleport => "minecraft:entity.enderman.teleport",
+        EntityGenericExplode => "minecraft:entity.generic.explode",
+        EntityVillagerYes => "minecraft:entity.villager.yes",
+        EntityVillagerNo => "minecraft:entity.villager.no",
+        EntityWitherSpawn => "minecraft:entity.wither.spawn",
+        EntityEnderDragonGrowl => "minecraft:entity.ender_dragon.growl",
+        EntityFireworkRocketLaunch => "minecraft:entity.firework_rocket.launch",
+        EntityFireworkRocketBlast => "minecraft:entity.firework_rocket.blast",
+        EntityLightningBoltThunder => "minecraft:entity.lightning_bolt.thunder",
+        ItemTotemUse => "minecraft:item.totem.use",
+        BlockNoteBlockHarp => "minecraft:block.note_block.harp",
+        BlockNoteBlockBass => "minecraft:block.note_block.bass",
+        BlockNoteBlockBell => "minecraft:block.note_block.bell",
+        BlockNoteBlockChime => "minecraft:block.note_block.chime",
+        BlockNoteBlockPling => "minecraft:block.note_block.pling",
+        BlockAnvilLand => "minecraft:block.anvil.land",
+        BlockAnvilUse => "minecraft:block.anvil.use",
+        BlockChestOpen => "minecraft:block.chest.open",
+        BlockChestClose => "minecraft:block.chest.close",
+        BlockLeverClick => "minecraft:block.lever.click",
+        BlockStoneButtonClickOn => "minecraft:block.stone_button.click_on",
+        BlockPortalTravel => "minecraft:block.portal.travel",
+        BlockBeaconActivate => "minecraft:block.beacon.activate",
+        BlockBeaconDeactivate => "minecraft:block.beacon.deactivate",
+    }
+}
+
+named_enum! {
+    /// Bukkit's `Particle`, by the name of the constant
+    Particle {
+        Flame => "FLAME",
+        SoulFireFlame => "SOUL_FIRE_FLAME",
+        Smoke => "SMOKE_NORMAL",
+        LargeSmoke => "SMOKE_LARGE",
+        CampfireSmoke => "CAMPFIRE_COSY_SMOKE",
+        Cloud => "CLOUD",
+        Poof => "EXPLOSION_NORMAL",
+        Explosion => "EXPLOSION_LARGE",
+        ExplosionEmitter => "EXPLOSION_HUGE",
+        Heart => "HEART",
+        Crit => "CRIT",
+        EnchantedHit => "CRIT_MAGIC",
+        Enchant => "ENCHANTMENT_TABLE",
+        Portal => "PORTAL",
+        Firework => "FIREWORKS_SPARK",
+        HappyVillager => "VILLAGER_HAPPY",
+        AngryVillager => "VILLAGER_ANGRY",
+        Note => "NOTE",
+        Witch => "SPELL_WITCH",
+        Totem => "TOTEM",
+        EndRod => "END_ROD",
+        DragonBreath => "DRAGON_BREATH",
+        Snowflake => "SNOWFLAKE",
+        Bubble => "WATER_BUBBLE",
+        DripWater => "DRIP_WATER",
+        DripLava => "DRIP_LAVA",
+        Glow => "GLOW",
+        ElectricSpark => "ELECTRIC_SPARK",
+        Soul => "SOUL",
+        SonicBoom => "SONIC_BOOM",
+        /// Needs [`ParticleData::Dust`]
+        Dust => "REDSTONE",
+        /// Needs [`ParticleData::DustTransition`]
+        DustTransition => "DUST_COLOR_TRANSITION",
+        /// Needs [`ParticleData::Block`]
+        Block => "BLOCK_CRACK",
+        /// Needs [`ParticleData::Block`]
+        BlockMarker => "BLOCK_MARKER",
+        /// Needs [`ParticleData::Block`]
+        FallingDust => "FALLING_DUST",
+        /// Needs [`ParticleData::Item`]
+        Item => "ITEM_CRACK",
+    }
+}
+
+/// Mixer channel a sound plays on, in the order of Bukkit's `SoundCategory`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub enum SoundCategory {
+    #[default]
+    Master,
+    Music,
+    Records,
+    Weather,
+    Blocks,
+    Hostile,
+    Neutral,
+    Players,
+    Ambient,
+    Voice
+}
+
+/// Extra data some particles need. Colours are `0xRRGGBB`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, BukrsType)]
+pub enum ParticleData {
+    #[default]
+    None,
+    Dust { color: u32, size: f32 },
+    DustTransition { from: u32, to: u32, size: f32 },
+    Block(BlockData),
+    Item(Material)
+}
+
+/// Who sees or hears an effect
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub enum EffectTarget {
+    Player(PlayerId),
+    Players(Vec<PlayerId>),
+    /// Everyone in the world of that name
+    World(String)
+}
+
+impl API {
+    /// Plays `sound` at `location`, or where each player stands if `None`. Returns how many players were reached, none if the location's world doesn't exist.
+    pub async fn play_sound(&mut self, target: &EffectTarget, sound: &Sound, category: SoundCategory, volume: f32, pitch: f32, location: Option<&Location>) -> anyhow::Result<u32> {
+        let BukrsResEffect { players } = self.send_packet_await(BukrsReqPlaySound { target: target.clone(), sound: sound.clone(), category, volume, pitch, location: location.cloned() }).await?;
+        Ok(players)
+    }
+
+    /// Spawns `count` particles spread around `location` by up to `offset` on each axis.
+    /// Players outside the location's world don't see them, so none are reached if that world doesn't exist.
+    /// Fails if `data` names a material or block state the server doesn't know.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn_particle(&mut self, target: &EffectTarget, particle: &Particle, location: &Location, count: u32, offset: &Vector, speed: f64, data: &ParticleData) -> anyhow::Result<u32> {
+        let request = BukrsReqSpawnParticle { target: target.clone(), particle: particle.clone(), location: location.clone(), count, offset: *offset, speed, data: data.clone() };
+        let BukrsResEffect { players } = self.send_packet_await(request).await?;
+        Ok(players)
+    }
+
+    /// Times are in ticks. Text may use `§` colour codes; an empty title shows only the subtitle.
+    pub async fn send_title(&mut self, target: &EffectTarget, title: &str, subtitle: &str, fade_in: u32, stay: u32, fade_out: u32) -> anyhow::Result<u32> {
+        let request = BukrsReqSendTitle { target: target.clone(), title: title.to_string(), subtitle: subtitle.to_string(), fade_in, stay, fade_out };
+        let BukrsResEffect { players } = self.send_packet_await(request).await?;
+        Ok(players)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::{block::Material, player::PlayerId, world::{Location, Vector}}, net::{BukrsReqPlaySound, BukrsReqSpawnParticle, BukrsResEffect}, tests::{loopback, respond}};
+
+    use super::{EffectTarget, Particle, ParticleData, Sound, SoundCategory};
+
+    #[tokio::test]
+    async fn test_effect_requests() {
+        let (mut api, mut server) = loopback().await;
+
+        let target = EffectTarget::Players(vec![PlayerId(1), PlayerId(2)]);
+        let play = api.play_sound(&target, &Sound::EntityPlayerLevelup, SoundCategory::Players, 1.0, 1.5, None);
+        let (played, request) = tokio::join!(play, respond::<BukrsReqPlaySound>(&mut server, BukrsResEffect { players: 2 }));
+        assert_eq!(played.unwrap(), 2);
+        let BukrsReqPlaySound { target, sound, category, pitch, location, .. } = request;
+        assert_eq!(target, EffectTarget::Players(vec![PlayerId(1), PlayerId(2)]));
+        assert_eq!((sound.name(), category, pitch), ("minecraft:entity.player.levelup", SoundCategory::Players, 1.5));
+        assert_eq!(location, None);
+
+        let (target, location, offset) = (EffectTarget::World("world".to_string()), Location::new("world", 0.5, 65.0, 0.5), Vector::new(0.2, 0.2, 0.2));
+        let data = ParticleData::Item(Material::new("diamond"));
+        let spawn = api.spawn_particle(&target, &Particle::Item, &location, 20, &offset, 0.05, &data);
+        let (spawned, request) = tokio::join!(spawn, respond::<BukrsReqSpawnParticle>(&mut server, BukrsResEffect { players: 0 }));
+        assert_eq!(spawned.unwrap(), 0);
+        let BukrsReqSpawnParticle { particle, count, data, .. } = request;
+        assert_eq!((particle, count, data), (Particle::Item, 20, ParticleData::Item(Material::new("minecraft:diamond"))));
+
+        assert_eq!(Sound::from_name("mypack:boom"), Sound::Other("mypack:boom".to_string()));
+        assert_eq!(Particle::from_name("REDSTONE"), Particle::Dust);
+    }
+}
@@ -0,0 +1,316 @@
+use std::{ops::BitOr, time::Duration};
+
+use anyhow::bail;
+use bukrs_derive::BukrsType;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqSpawnEntity, BukrsReqNearbyEntities, BukrsReqEntityById, BukrsResEntities, BukrsReqTeleportEntity, BukrsReqSetVelocity, BukrsReqRemoveEntity, BukrsReqSetCustomName, BukrsReqSetEntityFlags, BukrsReqChangeEntityFlags, BukrsResEntityUpdate, BukrsResRemoveEntity, BukrsReqSpawnTypes, BukrsResSpawnTypes, BukrsSDEntitySpawn, BukrsResEntitySpawn, BukrsSDEntityDeath, BukrsResEntityDeath}};
+
+use super::{player::{PlayerId, UUID}, world::{Location, Vector}, event::{CancellableEvent, MAIN_THREAD_DEADLINE}, persistent::{PersistentData, DataHolder}};
+
+/// Bukkit's entity id. Only valid while the entity is loaded and reused after it is gone.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct EntityId(pub u32);
+
+impl From<PlayerId> for EntityId {
+    /// Players are entities too
+    fn from(player_id: PlayerId) -> Self {
+        EntityId(player_id.0)
+    }
+}
+
+/// Namespaced entity type key, e.g. `minecraft:zombie`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct EntityType(pub String);
+
+impl EntityType {
+    /// Adds the `minecraft:` namespace if none is given
+    pub fn new(key: &str) -> EntityType {
+        if key.contains(':') {
+            EntityType(key.to_string())
+        } else {
+            EntityType(format!("minecraft:{}", key))
+        }
+    }
+}
+
+/// Entity metadata toggles, sent as a bit set
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub struct EntityFlags(pub u8);
+
+impl EntityFlags {
+    pub const GLOWING: EntityFlags = EntityFlags(1);
+    pub const INVISIBLE: EntityFlags = EntityFlags(1 << 1);
+    pub const SILENT: EntityFlags = EntityFlags(1 << 2);
+    pub const NO_GRAVITY: EntityFlags = EntityFlags(1 << 3);
+    pub const INVULNERABLE: EntityFlags = EntityFlags(1 << 4);
+    /// Only affects mobs
+    pub const NO_AI: EntityFlags = EntityFlags(1 << 5);
+    pub const CUSTOM_NAME_VISIBLE: EntityFlags = EntityFlags(1 << 6);
+
+    pub fn contains(&self, flags: EntityFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn set(&mut self, flags: EntityFlags, enabled: bool) {
+        if enabled {
+            self.0 |= flags.0;
+        } else {
+            self.0 &= !flags.0;
+        }
+    }
+}
+
+impl BitOr for EntityFlags {
+    type Output = EntityFlags;
+
+    fn bitor(self, rhs: EntityFlags) -> EntityFlags {
+        EntityFlags(self.0 | rhs.0)
+    }
+}
+
+/// Snapshot of an entity at the time the server sent it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BukrsType)]
+pub struct EntityData {
+    pub id: EntityId,
+    pub entity_type: EntityType,
+    pub uuid: UUID,
+    pub location: Location,
+    /// Empty if the entity has no custom name
+    pub custom_name: String,
+    pub flags: EntityFlags
+}
+
+/// Narrows [`API::nearby`] down on the server
+#[derive(Serialize, Deserialize, Clone, Debug, Default, BukrsType)]
+pub struct EntityFilter {
+    /// Any type if empty
+    pub types: Vec<EntityType>,
+    pub include_players: bool,
+    /// No limit if zero
+    pub limit: u32
+}
+
+impl EntityFilter {
+    /// Every entity except players
+    pub fn any() -> EntityFilter {
+        EntityFilter::default()
+    }
+
+    pub fn of_type(mut self, entity_type: EntityType) -> EntityFilter {
+        self.types.push(entity_type);
+        self
+    }
+
+    pub fn include_players(mut self) -> EntityFilter {
+        self.include_players = true;
+        self
+    }
+
+    /// Keeps only the `limit` closest entities
+    pub fn limit(mut self, limit: u32) -> EntityFilter {
+        self.limit = limit;
+        self
+    }
+}
+
+/// Handle to a live entity. Every call is a round trip and fails once the entity is gone.
+#[derive(Clone)]
+pub struct Entity {
+    pub id: EntityId,
+    api: API
+}
+
+impl Entity {
+    fn check(&self, found: bool) -> anyhow::Result<()> {
+        if !found {
+            bail!("Entity {} no longer exists", self.id.0);
+        }
+        Ok(())
+    }
+
+    /// A fresh snapshot, `None` once the entity is gone
+    pub async fn data(&mut self) -> anyhow::Result<Option<EntityData>> {
+        let BukrsResEntities { entities } = self.api.send_packet_await(BukrsReqEntityById { entity_id: self.id }).await?;
+        Ok(entities.into_iter().next())
+    }
+
+    /// Fails if the entity is gone or the location's world doesn't exist
+    pub async fn teleport(&mut self, location: &Location) -> anyhow::Result<()> {
+        let BukrsResEntityUpdate { found } = self.api.send_packet_await(BukrsReqTeleportEntity { entity_id: self.id, location: location.clone() }).await?;
+        self.check(found)
+    }
+
+    pub async fn set_velocity(&mut self, velocity: Vector) -> anyhow::Result<()> {
+        let BukrsResEntityUpdate { found } = self.api.send_packet_await(BukrsReqSetVelocity { entity_id: self.id, velocity }).await?;
+        self.check(found)
+    }
+
+    /// Fails for players, which can't be removed
+    pub async fn remove(self) -> anyhow::Result<()> {
+        let mut api = self.api.clone();
+        let BukrsResRemoveEntity { found, removed } = api.send_packet_await(BukrsReqRemoveEntity { entity_id: self.id }).await?;
+        self.check(found)?;
+        if !removed {
+            bail!("Entity {} is a player, which can't be removed", self.id.0);
+        }
+        Ok(())
+    }
+
+    /// `None` clears the name
+    pub async fn set_custom_name(&mut self, name: Option<&str>) -> anyhow::Result<()> {
+        let BukrsResEntityUpdate { found } = self.api.send_packet_await(BukrsReqSetCustomName { entity_id: self.id, name: name.unwrap_or_default().to_string() }).await?;
+        self.check(found)
+    }
+
+    /// Replaces all flags at once
+    pub async fn set_flags(&mut self, flags: EntityFlags) -> anyhow::Result<()> {
+        let BukrsResEntityUpdate { found } = self.api.send_packet_await(BukrsReqSetEntityFlags { entity_id: self.id, flags }).await?;
+        self.check(found)
+    }
+
+    /// Changes one flag, leaving the others as the server has them
+    pub async fn set_flag(&mut self, flag: EntityFlags, enabled: bool) -> anyhow::Result<()> {
+        let (set, clear) = if enabled { (flag, EntityFlags::default()) } else { (EntityFlags::default(), flag) };
+        let BukrsResEntityUpdate { found } = self.api.send_packet_await(BukrsReqChangeEntityFlags { entity_id: self.id, set, clear }).await?;
+        self.check(found)
+    }
+
+    pub async fn set_glowing(&mut self, glowing: bool) -> anyhow::Result<()> {
+        self.set_flag(EntityFlags::GLOWING, glowing).await
+    }
+
+    pub fn persistent_data(&self) -> PersistentData {
+        self.api.persistent_data(DataHolder::Entity(self.id))
+    }
+}
+
+impl API {
+    /// Handle to an entity by id, without checking that it exists
+    pub fn entity(&self, entity_id: impl Into<EntityId>) -> Entity {
+        Entity { id: entity_id.into(), api: self.clone() }
+    }
+
+    pub async fn spawn(&mut self, entity_type: &EntityType, location: &Location) -> anyhow::Result<Entity> {
+        let BukrsResEntities { entities } = self.send_packet_await(BukrsReqSpawnEntity { entity_type: entity_type.clone(), location: location.clone() }).await?;
+        match entities.into_iter().next() {
+            Some(data) => Ok(self.entity(data.id)),
+            None => bail!("Could not spawn {} at {:?}", entity_type.0, location)
+        }
+    }
+
+    /// Only fires [`EntitySpawn`] for these types, so spawns of other mobs, items and projectiles don't hold up the server.
+    /// An empty list sends every spawn again. Other clients keep their own setting.
+    pub async fn set_spawn_types(&mut self, types: &[EntityType]) -> anyhow::Result<()> {
+        let BukrsResSpawnTypes {  } = self.send_packet_await(BukrsReqSpawnTypes { types: types.to_vec() }).await?;
+        Ok(())
+    }
+
+    /// Entities within `radius` blocks, closest first. Empty if the world doesn't exist.
+    pub async fn nearby(&mut self, location: &Location, radius: f64, filter: EntityFilter) -> anyhow::Result<Vec<EntityData>> {
+        let BukrsResEntities { entities } = self.send_packet_await(BukrsReqNearbyEntities { location: location.clone(), radius, filter }).await?;
+        Ok(entities)
+    }
+}
+
+/// Every mob, item and projectile fires this. Cancelling handlers hold up each spawn, so narrow the types with
+/// [`API::set_spawn_types`] first.
+#[derive(Clone, Debug)]
+pub struct EntitySpawn {
+    pub entity: EntityData,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for EntitySpawn {
+    const NAME: &'static str = "EntitySpawn";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDEntitySpawn;
+    type Verdict = BukrsResEntitySpawn;
+
+    fn from_packet(packet: BukrsSDEntitySpawn) -> Self {
+        EntitySpawn { entity: packet.entity, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResEntitySpawn {
+        BukrsResEntitySpawn { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+/// Cancelling keeps the entity alive, as Paper allows. Players fire [`PlayerDeath`](super::combat::PlayerDeath) instead.
+#[derive(Clone, Debug)]
+pub struct EntityDeath {
+    pub entity: EntityData,
+    pub dropped_exp: u32,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for EntityDeath {
+    const NAME: &'static str = "EntityDeath";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDEntityDeath;
+    type Verdict = BukrsResEntityDeath;
+
+    fn from_packet(packet: BukrsSDEntityDeath) -> Self {
+        let BukrsSDEntityDeath { entity, dropped_exp } = packet;
+        EntityDeath { entity, dropped_exp, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResEntityDeath {
+        BukrsResEntityDeath { cancelled: self.cancelled, dropped_exp: self.dropped_exp }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::{player::{PlayerId, UUID}, world::Location}, net::{BukrsReqSpawnEntity, BukrsResEntities, BukrsReqTeleportEntity, BukrsReqChangeEntityFlags, BukrsResEntityUpdate, BukrsReqRemoveEntity, BukrsResRemoveEntity}, tests::{loopback, respond}};
+
+    use super::{EntityId, EntityType, EntityData, EntityFlags};
+
+    #[tokio::test]
+    async fn test_spawn_and_teleport() {
+        let (mut api, mut server) = loopback().await;
+        let location = Location::new("world", 0.5, 64.0, -3.5).with_rotation(90.0, 0.0);
+        let zombie = EntityType::new("zombie");
+
+        let data = EntityData { id: EntityId(42), entity_type: EntityType("minecraft:zombie".to_string()), uuid: UUID::from_u128(42), location: location.clone(), custom_name: String::new(), flags: EntityFlags::GLOWING | EntityFlags::SILENT };
+        let (spawned, request) = tokio::join!(api.spawn(&zombie, &location), respond::<BukrsReqSpawnEntity>(&mut server, BukrsResEntities { entities: vec![data] }));
+        let mut zombie = spawned.unwrap();
+        assert_eq!(zombie.id, EntityId(42));
+        assert_eq!((request.entity_type, request.location), (EntityType("minecraft:zombie".to_string()), location));
+
+        let target = Location::new("world", 10.0, 70.0, 10.0);
+        let (teleported, request) = tokio::join!(zombie.teleport(&target), respond::<BukrsReqTeleportEntity>(&mut server, BukrsResEntityUpdate { found: false }));
+        assert!(teleported.is_err());
+        assert_eq!((request.entity_id, request.location), (EntityId(42), target));
+
+        let (changed, request) = tokio::join!(zombie.set_glowing(false), respond::<BukrsReqChangeEntityFlags>(&mut server, BukrsResEntityUpdate { found: true }));
+        changed.unwrap();
+        let BukrsReqChangeEntityFlags { entity_id, set, clear } = request;
+        assert_eq!((entity_id, set, clear), (EntityId(42), EntityFlags::default(), EntityFlags::GLOWING));
+
+        let (removed, request) = tokio::join!(api.entity(PlayerId(7)).remove(), respond::<BukrsReqRemoveEntity>(&mut server, BukrsResRemoveEntity { found: true, removed: false }));
+        assert_eq!(request.entity_id, EntityId(7));
+        assert!(removed.unwrap_err().to_string().contains("is a player"));
+
+        let (removed, _) = tokio::join!(zombie.remove(), respond::<BukrsReqRemoveEntity>(&mut server, BukrsResRemoveEntity { found: true, removed: true }));
+        removed.unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+use std::{any::Any, collections::HashMap, future::Future, marker::PhantomData, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+
+use crate::{API, Warning, net::{Packet, cast_packet, BukrsReqSubscribeEvent, BukrsResSubscribeEvent, BukrsSDEventDropped}};
+
+/// Order handlers run in, mirroring Bukkit's `EventPriority`. `Monitor` handlers only observe the final outcome.
+///
+/// As long as all handlers of an event are `Monitor` handlers, the server doesn't wait for this client. It sends
+/// the event once the other clients decided it, and only if none of them cancelled it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventPriority {
+    Lowest,
+    Low,
+    Normal,
+    High,
+    Highest,
+    Monitor
+}
+
+/// Deadline of events fired on the main thread. The server can't run the tick while it waits for the verdict,
+/// so it waits at most one tick.
+pub const MAIN_THREAD_DEADLINE: Duration = Duration::from_millis(50);
+
+/// A server event the plugin holds back until the client sends a verdict or the deadline passes.
+/// The event arrives as `Packet` with a payload id and is answered with `Verdict` under the same id.
+///
+/// Clients subscribed to the same event get it in turn, all within the longest of their deadlines. A client that
+/// misses its deadline is unsubscribed and its handlers for the event are dropped; listen again to resubscribe.
+///
+/// Handlers of events fired on the main thread must not await requests the server answers on the main thread,
+/// such as block, entity or world changes. Those wait for the tick the event is holding up, so the handler misses
+/// its deadline. Spawn such work onto its own task instead.
+pub trait CancellableEvent: Clone + Send + Sync + 'static {
+    /// Name the plugin knows the event by
+    const NAME: &'static str;
+    /// How long the server waits for a verdict, unless the listener asks otherwise
+    const DEADLINE: Duration;
+
+    type Packet: Packet + Clone;
+    type Verdict: Packet;
+
+    fn from_packet(packet: Self::Packet) -> Self;
+
+    fn verdict(&self) -> Self::Verdict;
+
+    fn is_cancelled(&self) -> bool;
+
+    fn set_cancelled(&mut self, cancelled: bool);
+}
+
+pub type EventHandler<E> = Arc<dyn Fn(E) -> BoxFuture<'static, E> + Send + Sync>;
+
+struct RegisteredHandler<E> {
+    priority: EventPriority,
+    ignore_cancelled: bool,
+    handler: EventHandler<E>
+}
+
+impl<E> Clone for RegisteredHandler<E> {
+    fn clone(&self) -> Self {
+        RegisteredHandler { priority: self.priority, ignore_cancelled: self.ignore_cancelled, handler: self.handler.clone() }
+    }
+}
+
+type Dispatcher = fn(&API, Option<u32>, &Box<dyn Packet>) -> bool;
+
+/// Handlers of one event type, kept sorted by priority
+pub(crate) struct EventRegistry {
+    dispatcher: Dispatcher,
+    handlers: Box<dyn Any + Send + Sync>    // Vec<RegisteredHandler<E>>
+}
+
+/// Builder returned by [`API::listen`]
+pub struct EventListener<'a, E> {
+    api: &'a mut API,
+    priority: EventPriority,
+    ignore_cancelled: bool,
+    deadline: Duration,
+    _event: PhantomData<E>
+}
+
+impl<'a, E: CancellableEvent> EventListener<'a, E> {
+    pub fn priority(mut self, priority: EventPriority) -> EventListener<'a, E> {
+        self.priority = priority;
+        self
+    }
+
+    /// Skip this handler if an earlier one cancelled the event
+    pub fn ignore_cancelled(mut self) -> EventListener<'a, E> {
+        self.ignore_cancelled = true;
+        self
+    }
+
+    /// How long the server waits for the verdict of this event type. The last registered listener wins.
+    pub fn deadline(mut self, deadline: Duration) -> EventListener<'a, E> {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Adds the handler and subscribes to the event on the server. If subscribing fails, the handler is removed again.
+    pub async fn handle<F, Fut>(self, handler: F) -> anyhow::Result<()> where F: Fn(E) -> Fut + Send + Sync + 'static, Fut: Future<Output = E> + Send + 'static {
+        let deadline_ms = u32::try_from(self.deadline.as_millis())?;
+        let handler: EventHandler<E> = Arc::new(move |event| Box::pin(handler(event)));
+        let blocking = {     // Registered first so no event sent right after the response is missed
+            let mut events = self.api.events.lock().unwrap();
+            let registry = events.entry(E::NAME).or_insert_with(|| EventRegistry { dispatcher: dispatch::<E>, handlers: Box::new(Vec::<RegisteredHandler<E>>::new()) });
+            let handlers = registry.handlers.downcast_mut::<Vec<RegisteredHandler<E>>>().unwrap();
+            handlers.push(RegisteredHandler { priority: self.priority, ignore_cancelled: self.ignore_cancelled, handler: handler.clone() });
+            handlers.sort_by_key(|handler| handler.priority);
+            handlers.iter().any(|handler| handler.priority != EventPriority::Monitor)
+        };
+
+        let response = self.api.send_packet_await::<BukrsResSubscribeEvent>(BukrsReqSubscribeEvent { event: E::NAME.to_string(), deadline_ms, blocking }).await;
+        if response.is_err() {
+            unregister::<E>(self.api, &handler);
+        }
+        response.map(|BukrsResSubscribeEvent {  }| ())
+    }
+}
+
+/// Removes a handler, and the event type with its last handler
+fn unregister<E: CancellableEvent>(api: &API, handler: &EventHandler<E>) {
+    let mut events = api.events.lock().unwrap();
+    let Some(handlers) = events.get_mut(E::NAME).and_then(|registry| registry.handlers.downcast_mut::<Vec<RegisteredHandler<E>>>()) else { return };
+    handlers.retain(|registered| !Arc::ptr_eq(&registered.handler, handler));
+    if handlers.is_empty() {
+        events.remove(E::NAME);
+    }
+}
+
+impl API {
+    pub fn listen<E: CancellableEvent>(&mut self) -> EventListener<'_, E> {
+        EventListener { api: self, priority: EventPriority::Normal, ignore_cancelled: false, deadline: E::DEADLINE, _event: PhantomData }
+    }
+}
+
+/// Offers the packet to every registered event type until one accepts it
+#[allow(clippy::borrowed_box)]
+pub(crate) fn dispatch_any(api: &API, payload_id: Option<u32>, packet: &Box<dyn Packet>) {
+    if let Some(BukrsSDEventDropped { event }) = cast_packet(packet) {
+        api.events.lock().unwrap().remove(event.as_str());
+        api.warn(Warning::EventDropped(event));
+        return;
+    }
+
+    let dispatchers = api.events.lock().unwrap().values().map(|registry| registry.dispatcher).collect::<Vec<Dispatcher>>();
+    for dispatcher in dispatchers {
+        if dispatcher(api, payload_id, packet) {
+            break;
+        }
+    }
+}
+
+/// Runs the handlers in priority order, answers with the verdict and then lets `Monitor` handlers observe it.
+/// Events sent without a payload id are only observed, so no verdict is sent.
+#[allow(clippy::borrowed_box)]
+fn dispatch<E: CancellableEvent>(api: &API, payload_id: Option<u32>, packet: &Box<dyn Packet>) -> bool {
+    let Some(packet) = cast_packet::<E::Packet>(packet) else { return false };
+    let handlers = api.events.lock().unwrap().get(E::NAME)
+        .and_then(|registry| registry.handlers.downcast_ref::<Vec<RegisteredHandler<E>>>())
+        .cloned()
+        .unwrap_or_default();
+    let mut api = api.clone();
+
+    tokio::spawn(async move {
+        let (monitors, handlers): (Vec<RegisteredHandler<E>>, Vec<RegisteredHandler<E>>) = handlers.into_iter().partition(|handler| handler.priority == EventPriority::Monitor);
+
+        let mut event = E::from_packet(packet);
+        for handler in handlers.iter() {
+            if !(event.is_cancelled() && handler.ignore_cancelled) {
+                event = (handler.handler)(event).await;
+            }
+        }
+        if payload_id.is_some() {
+            api.send_packet(event.verdict(), payload_id).await?;
+        }
+
+        for monitor in monitors.iter() {
+            if !(event.is_cancelled() && monitor.ignore_cancelled) {
+                (monitor.handler)(event.clone()).await;
+            }
+        }
+        anyhow::Ok(())
+    });
+    true
+}
+
+pub(crate) type Events = HashMap<&'static str, EventRegistry>;
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::Framed;
+    use tokio::{net::TcpStream, sync::mpsc};
+
+    use crate::{API, Warning, core::{chat::PlayerChat, player::PlayerId}, net::{BukrsPacketData, Codec, BukrsReqSubscribeEvent, BukrsResSubscribeEvent, BukrsResError, BukrsSDEventDropped, BukrsSDPlayerChat, BukrsResPlayerChat, cast_packet}, tests::{loopback, respond, accept_subscription}};
+
+    use super::{EventPriority, CancellableEvent};
+
+    /// Subscribes a handler that sends `name` to `log` whenever it runs
+    async fn listen(api: &mut API, server: &mut Framed<TcpStream, Codec>, priority: EventPriority, ignore_cancelled: bool, name: &'static str, cancel: bool, log: mpsc::UnboundedSender<&'static str>) -> BukrsReqSubscribeEvent {
+        let mut listener = api.listen::<PlayerChat>().priority(priority);
+        if ignore_cancelled {
+            listener = listener.ignore_cancelled();
+        }
+        let handle = listener.handle(move |mut event: PlayerChat| {
+            let log = log.clone();
+            async move {
+                log.send(name).unwrap();
+                if cancel {
+                    event.set_cancelled(true);
+                }
+                event
+            }
+        });
+        let (handled, request) = tokio::join!(handle, accept_subscription(server));
+        handled.unwrap();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let (mut api, mut server) = loopback().await;
+        let (log, mut ran) = mpsc::unbounded_channel();
+
+        let request = listen(&mut api, &mut server, EventPriority::Monitor, false, "monitor", false, log.clone()).await;
+        assert_eq!(request.event, "PlayerChat");
+        assert_eq!(request.deadline_ms, PlayerChat::DEADLINE.as_millis() as u32);
+        assert!(!request.blocking);
+        let request = listen(&mut api, &mut server, EventPriority::High, true, "high", false, log.clone()).await;
+        assert!(request.blocking);
+        listen(&mut api, &mut server, EventPriority::Normal, false, "normal", false, log.clone()).await;
+        listen(&mut api, &mut server, EventPriority::Lowest, false, "lowest", true, log.clone()).await;
+
+        let chat = BukrsSDPlayerChat { player_id: PlayerId(1), message: "hi".to_string(), format: "%2$s".to_string(), recipients: vec![] };
+        server.send(BukrsPacketData { payload_id: Some(1024), event: Box::new(chat) }).await.unwrap();
+
+        let response = server.next().await.unwrap().unwrap();
+        assert_eq!(response.payload_id, Some(1024));
+        let BukrsResPlayerChat { cancelled, .. } = cast_packet(&response.event).unwrap();
+        assert!(cancelled);
+
+        for name in ["lowest", "normal", "monitor"] {   // "high" ignores the cancelled event, or it would come before "monitor"
+            assert_eq!(ran.recv().await, Some(name));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observed_events_get_no_verdict() {
+        let (mut api, mut server) = loopback().await;
+        let (log, mut ran) = mpsc::unbounded_channel();
+        listen(&mut api, &mut server, EventPriority::Monitor, false, "monitor", false, log).await;
+
+        let chat = BukrsSDPlayerChat { player_id: PlayerId(1), message: "hi".to_string(), format: "%2$s".to_string(), recipients: vec![] };
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(chat) }).await.unwrap();
+        assert_eq!(ran.recv().await, Some("monitor"));
+
+        api.send_packet(BukrsResSubscribeEvent {  }, None).await.unwrap();    // Arrives first unless a verdict was sent
+        assert!(cast_packet::<BukrsResSubscribeEvent>(&server.next().await.unwrap().unwrap().event).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_subscription() {
+        let (mut api, mut server) = loopback().await;
+        listen(&mut api, &mut server, EventPriority::Normal, false, "normal", false, mpsc::unbounded_channel().0).await;
+        assert!(api.events.lock().unwrap().contains_key("PlayerChat"));
+
+        let mut warnings = api.warnings();
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDEventDropped { event: "PlayerChat".to_string() }) }).await.unwrap();
+        assert_eq!(warnings.recv().await.unwrap(), Warning::EventDropped("PlayerChat".to_string()));
+        assert!(!api.events.lock().unwrap().contains_key("PlayerChat"));
+    }
+    #[tokio::test]
+    async fn test_failed_subscription() {
+        let (mut api, mut server) = loopback().await;
+        let (log, mut ran) = mpsc::unbounded_channel();
+        listen(&mut api, &mut server, EventPriority::Monitor, false, "monitor", false, log.clone()).await;
+
+        let failing = api.listen::<PlayerChat>().handle(move |event: PlayerChat| {
+            let log = log.clone();
+            async move {
+                log.send("failed").unwrap();
+                event
+            }
+        });
+        let (handled, _) = tokio::join!(failing, respond::<BukrsReqSubscribeEvent>(&mut server, BukrsResError { message: "Shutting down".to_string() }));
+        assert!(handled.is_err());
+
+        let chat = BukrsSDPlayerChat { player_id: PlayerId(1), message: "hi".to_string(), format: "%2$s".to_string(), recipients: vec![] };
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(chat) }).await.unwrap();
+        assert_eq!(ran.recv().await, Some("monitor"));
+        assert!(ran.try_recv().is_err(), "The handler of the failed subscription ran");
+    }
+}
@@ -0,0 +1,21 @@
+use bukrs_derive::BukrsType;
+use serde::{Serialize, Deserialize};
+
+use super::block::Material;
+
+/// An item stack as the server has it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub struct Item {
+    pub material: Material,
+    pub amount: u8,
+    /// The server's serialized stack, keeping enchantments, names and other meta.
+    /// Empty for plain items; `material` and `amount` always win over it.
+    pub data: Vec<u8>
+}
+
+impl Item {
+    pub fn new(material: Material, amount: u8) -> Item {
+        Item { material, amount, data: vec![] }
+    }
+}
+
@@ -1,2 +1,20 @@
+pub mod batch;
+pub mod block;
+pub mod bossbar;
+pub mod chat;
+pub mod combat;
+pub mod command;
+pub mod effect;
+pub mod entity;
+pub mod event;
 pub mod invfx;
-pub mod player;
\ No newline at end of file
+pub mod item;
+pub mod movement;
+pub mod permission;
+pub mod persistent;
+pub mod player;
+pub mod region;
+pub mod scheduler;
+pub mod scoreboard;
+pub mod schematic;
+pub mod world;
\ No newline at end of file
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use crate::{API, net::{BukrsReqDefineRegion, BukrsResDefineRegion, BukrsReqRemoveRegion, BukrsResRemoveRegion, BukrsReqMoveInterval, BukrsResMoveInterval, BukrsSDPlayerMove, BukrsResPlayerMove, BukrsSDRegionEnter, BukrsResRegionEnter, BukrsSDRegionLeave, BukrsResRegionLeave}};
+
+use super::{player::PlayerId, region::Region, world::Location, event::{CancellableEvent, MAIN_THREAD_DEADLINE}};
+
+/// A player moved into another block. Turning the head or moving within a block is never sent.
+/// Cancelling pushes the player back to `from`. Moves listened to only at [`EventPriority::Monitor`](super::event::EventPriority::Monitor) don't hold up the server.
+#[derive(Clone, Debug)]
+pub struct PlayerMove {
+    pub player_id: PlayerId,
+    pub from: Location,
+    pub to: Location,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for PlayerMove {
+    const NAME: &'static str = "PlayerMove";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDPlayerMove;
+    type Verdict = BukrsResPlayerMove;
+
+    fn from_packet(packet: BukrsSDPlayerMove) -> Self {
+        let BukrsSDPlayerMove { player_id, from, to } = packet;
+        PlayerMove { player_id, from, to, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResPlayerMove {
+        BukrsResPlayerMove { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+/// A player is about to enter a region defined with [`API::define_region`]. Cancelling keeps them out.
+#[derive(Clone, Debug)]
+pub struct RegionEnter {
+    pub player_id: PlayerId,
+    pub region: String,
+    pub from: Location,
+    pub to: Location,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for RegionEnter {
+    const NAME: &'static str = "RegionEnter";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDRegionEnter;
+    type Verdict = BukrsResRegionEnter;
+
+    fn from_packet(packet: BukrsSDRegionEnter) -> Self {
+        let BukrsSDRegionEnter { player_id, region, from, to } = packet;
+        RegionEnter { player_id, region, from, to, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResRegionEnter {
+        BukrsResRegionEnter { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+/// A player is about to leave a region defined with [`API::define_region`]. Cancelling keeps them in.
+#[derive(Clone, Debug)]
+pub struct RegionLeave {
+    pub player_id: PlayerId,
+    pub region: String,
+    pub from: Location,
+    pub to: Location,
+    pub cancelled: bool
+}
+
+impl CancellableEvent for RegionLeave {
+    const NAME: &'static str = "RegionLeave";
+    const DEADLINE: Duration = MAIN_THREAD_DEADLINE;
+
+    type Packet = BukrsSDRegionLeave;
+    type Verdict = BukrsResRegionLeave;
+
+    fn from_packet(packet: BukrsSDRegionLeave) -> Self {
+        let BukrsSDRegionLeave { player_id, region, from, to } = packet;
+        RegionLeave { player_id, region, from, to, cancelled: false }
+    }
+
+    fn verdict(&self) -> BukrsResRegionLeave {
+        BukrsResRegionLeave { cancelled: self.cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+
+impl API {
+    /// Watches `region` under `name`, replacing any region of the same name this client defined.
+    /// The server only reports crossings of its border, as [`RegionEnter`] and [`RegionLeave`], and only to this client.
+    pub async fn define_region(&mut self, name: &str, region: &Region) -> anyhow::Result<()> {
+        let BukrsResDefineRegion {  } = self.send_packet_await(BukrsReqDefineRegion { name: name.to_string(), region: region.clone() }).await?;
+        Ok(())
+    }
+
+    /// Returns whether a region of that name was defined
+    pub async fn remove_region(&mut self, name: &str) -> anyhow::Result<bool> {
+        let BukrsResRemoveRegion { found } = self.send_packet_await(BukrsReqRemoveRegion { name: name.to_string() }).await?;
+        Ok(found)
+    }
+
+    /// Sends at most one [`PlayerMove`] per player every `interval`; moves in between are let through unseen.
+    /// Region crossings are never throttled. The setting is shared by every client of the server.
+    pub async fn set_move_interval(&mut self, interval: Duration) -> anyhow::Result<()> {
+        let interval_ms = u32::try_from(interval.as_millis())?;
+        let BukrsResMoveInterval {  } = self.send_packet_await(BukrsReqMoveInterval { interval_ms }).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+
+    use crate::{core::{block::BlockPos, player::PlayerId, region::Region, world::Location}, net::{BukrsPacketData, BukrsReqDefineRegion, BukrsResDefineRegion, BukrsReqMoveInterval, BukrsResMoveInterval, BukrsSDRegionEnter, BukrsResRegionEnter, cast_packet}, tests::{loopback, accept_subscription}};
+
+    use super::RegionEnter;
+
+    #[tokio::test]
+    async fn test_region_enter_push_back() {
+        let (mut api, mut server) = loopback().await;
+        let spawn = Region::new(&BlockPos::new("world", -8, 0, -8), &BlockPos::new("world", 8, 255, 8));
+
+        let define = api.define_region("spawn", &spawn);
+        let accept = async {
+            let request = server.next().await.unwrap().unwrap();
+            let BukrsReqDefineRegion { name, region } = cast_packet(&request.event).unwrap();
+            assert_eq!((name.as_str(), region), ("spawn", spawn.clone()));
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResDefineRegion {  }) }).await.unwrap();
+        };
+        let (defined, _) = tokio::join!(define, accept);
+        defined.unwrap();
+
+        let throttle = api.set_move_interval(Duration::from_millis(250));
+        let accept = async {
+            let request = server.next().await.unwrap().unwrap();
+            let BukrsReqMoveInterval { interval_ms } = cast_packet(&request.event).unwrap();
+            assert_eq!(interval_ms, 250);
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResMoveInterval {  }) }).await.unwrap();
+        };
+        let (throttled, _) = tokio::join!(throttle, accept);
+        throttled.unwrap();
+
+        let keep_out = api.listen::<RegionEnter>().handle(|mut event: RegionEnter| async move {
+            event.cancelled = event.region == "spawn" && event.player_id != PlayerId(1);
+            event
+        });
+        let (subscribed, _) = tokio::join!(keep_out, accept_subscription(&mut server));
+        subscribed.unwrap();
+
+        for (payload_id, player_id, cancelled) in [(1024, PlayerId(1), false), (1025, PlayerId(2), true)] {
+            let enter = BukrsSDRegionEnter { player_id, region: "spawn".to_string(), from: Location::new("world", 9.5, 64.0, 0.5), to: Location::new("world", 8.5, 64.0, 0.5) };
+            server.send(BukrsPacketData { payload_id: Some(payload_id), event: Box::new(enter) }).await.unwrap();
+            let response = server.next().await.unwrap().unwrap();
+            assert_eq!(response.payload_id, Some(payload_id));
+            let BukrsResRegionEnter { cancelled: verdict } = cast_packet(&response.event).unwrap();
+            assert_eq!(verdict, cancelled);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use anyhow::bail;
+use rand::Rng;
+use tokio::sync::broadcast;
+
+use crate::{API, net::{Packet, cast_packet, BukrsSDPlayerQuit, BukrsReqHasPermission, BukrsResHasPermission, BukrsReqHasPermissions, BukrsResHasPermissions, BukrsReqAddAttachment, BukrsReqSetAttachmentPermission, BukrsReqUnsetAttachmentPermission, BukrsReqRemoveAttachment, BukrsResAttachment, BukrsReqIsOp, BukrsReqSetOp, BukrsResOp, BukrsSDPermissionChange}};
+
+use super::player::PlayerId;
+
+/// How long a permission check is reused before the server is asked again
+pub const DEFAULT_PERMISSION_TTL: Duration = Duration::from_secs(1);
+
+/// A player's permissions changed through bukrs, by any client: an attachment was changed or removed, or op status was set.
+/// Changes made by other plugins are not reported, so those are only seen once the cached checks expire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermissionChange {
+    pub player_id: PlayerId
+}
+
+/// Checked permissions of one player, with when each was checked
+type CachedChecks = HashMap<String, (bool, Instant)>;
+
+/// Permission checks cached per player, shared by every clone of the [`API`]
+pub(crate) struct PermissionState {
+    ttl: Mutex<Duration>,
+    cache: Mutex<HashMap<PlayerId, CachedChecks>>,
+    changes: broadcast::Sender<PermissionChange>
+}
+
+impl PermissionState {
+    pub(crate) fn new() -> PermissionState {
+        PermissionState { ttl: Mutex::new(DEFAULT_PERMISSION_TTL), cache: Mutex::new(HashMap::new()), changes: broadcast::channel(64).0 }
+    }
+
+    fn cached(&self, player_id: &PlayerId, permission: &str) -> Option<bool> {
+        let ttl = *self.ttl.lock().unwrap();
+        self.cache.lock().unwrap().get(player_id)
+            .and_then(|permissions| permissions.get(permission))
+            .filter(|(_, checked)| checked.elapsed() < ttl)
+            .map(|(value, _)| *value)
+    }
+
+    fn store(&self, player_id: &PlayerId, permission: &str, value: bool) {
+        self.cache.lock().unwrap().entry(player_id.clone()).or_default().insert(permission.to_string(), (value, Instant::now()));
+    }
+
+    fn invalidate(&self, player_id: &PlayerId) {
+        self.cache.lock().unwrap().remove(player_id);
+    }
+}
+
+/// Permissions set on top of a player's own, like Bukkit's `PermissionAttachment`.
+/// The server drops the attachment when the player quits or this client disconnects.
+pub struct PermissionAttachment {
+    pub id: u32,
+    pub player_id: PlayerId,
+    api: API
+}
+
+impl PermissionAttachment {
+    async fn send(&mut self, request: impl Packet) -> anyhow::Result<()> {
+        let BukrsResAttachment { found } = self.api.send_packet_await(request).await?;
+        self.api.permissions.invalidate(&self.player_id);
+        if !found {
+            bail!("Permission attachment {} was removed", self.id);
+        }
+        Ok(())
+    }
+
+    /// Grants the permission, or revokes it if `value` is false
+    pub async fn set(&mut self, permission: &str, value: bool) -> anyhow::Result<()> {
+        self.send(BukrsReqSetAttachmentPermission { attachment_id: self.id, permission: permission.to_string(), value }).await
+    }
+
+    /// Leaves the permission to the player's other attachments
+    pub async fn unset(&mut self, permission: &str) -> anyhow::Result<()> {
+        self.send(BukrsReqUnsetAttachmentPermission { attachment_id: self.id, permission: permission.to_string() }).await
+    }
+
+    /// Returns whether the attachment was still in place
+    pub async fn remove(mut self) -> anyhow::Result<bool> {
+        let BukrsResAttachment { found } = self.api.send_packet_await(BukrsReqRemoveAttachment { attachment_id: self.id }).await?;
+        self.api.permissions.invalidate(&self.player_id);
+        Ok(found)
+    }
+}
+
+impl API {
+    /// Checks a permission, reusing a check younger than the cache TTL
+    pub async fn has_permission(&mut self, player_id: &PlayerId, permission: &str) -> anyhow::Result<bool> {
+        if let Some(value) = self.permissions.cached(player_id, permission) {
+            return Ok(value);
+        }
+
+        let BukrsResHasPermission { found, value } = self.send_packet_await(BukrsReqHasPermission { player_id: player_id.clone(), permission: permission.to_string() }).await?;
+        if !found {
+            bail!("Player {} is offline", player_id.0);
+        }
+        self.permissions.store(player_id, permission, value);
+        Ok(value)
+    }
+
+    /// Checks several permissions at once. Only those missing from the cache are sent, in a single request.
+    pub async fn has_permissions(&mut self, player_id: &PlayerId, permissions: &[&str]) -> anyhow::Result<Vec<bool>> {
+        let mut values = permissions.iter().map(|permission| self.permissions.cached(player_id, permission)).collect::<Vec<Option<bool>>>();
+        let missing = permissions.iter().zip(values.iter())
+            .filter(|(_, value)| value.is_none())
+            .map(|(permission, _)| permission.to_string())
+            .collect::<Vec<String>>();
+
+        if !missing.is_empty() {
+            let BukrsResHasPermissions { found, values: checked } = self.send_packet_await(BukrsReqHasPermissions { player_id: player_id.clone(), permissions: missing.clone() }).await?;
+            if !found {
+                bail!("Player {} is offline", player_id.0);
+            }
+
+            let mut checked = missing.iter().zip(checked);
+            for value in values.iter_mut().filter(|value| value.is_none()) {
+                let Some((permission, checked)) = checked.next() else { bail!("Server answered fewer permissions than asked") };
+                self.permissions.store(player_id, permission, checked);
+                *value = Some(checked);
+            }
+        }
+        Ok(values.into_iter().map(|value| value.unwrap_or_default()).collect())
+    }
+
+    /// How long permission checks are reused. [`Duration::ZERO`] asks the server every time.
+    pub fn set_permission_cache_ttl(&self, ttl: Duration) {
+        *self.permissions.ttl.lock().unwrap() = ttl;
+    }
+
+    pub async fn add_permission_attachment(&mut self, player_id: &PlayerId) -> anyhow::Result<PermissionAttachment> {
+        let attachment_id = rand::thread_rng().gen_range(1..u32::MAX);
+        let BukrsResAttachment { found } = self.send_packet_await(BukrsReqAddAttachment { attachment_id, player_id: player_id.clone() }).await?;
+        if !found {
+            bail!("Player {} is offline", player_id.0);
+        }
+        Ok(PermissionAttachment { id: attachment_id, player_id: player_id.clone(), api: self.clone() })
+    }
+
+    pub async fn is_op(&mut self, player_id: &PlayerId) -> anyhow::Result<bool> {
+        let BukrsResOp { found, op } = self.send_packet_await(BukrsReqIsOp { player_id: player_id.clone() }).await?;
+        if !found {
+            bail!("Player {} is offline", player_id.0);
+        }
+        Ok(op)
+    }
+
+    pub async fn set_op(&mut self, player_id: &PlayerId, op: bool) -> anyhow::Result<()> {
+        let BukrsResOp { found, .. } = self.send_packet_await(BukrsReqSetOp { player_id: player_id.clone(), op }).await?;
+        self.permissions.invalidate(player_id);
+        if !found {
+            bail!("Player {} is offline", player_id.0);
+        }
+        Ok(())
+    }
+
+    /// Receives every [`PermissionChange`] from now on
+    pub fn permission_changes(&self) -> broadcast::Receiver<PermissionChange> {
+        self.permissions.changes.subscribe()
+    }
+}
+
+/// Drops cached checks of players whose permissions changed or who quit
+#[allow(clippy::borrowed_box)]
+pub(crate) fn update_cache(api: &API, packet: &Box<dyn Packet>) {
+    if let Some(BukrsSDPermissionChange { player_id }) = cast_packet(packet) {
+        api.permissions.invalidate(&player_id);
+        let _ = api.permissions.changes.send(PermissionChange { player_id });   // Fails only when nobody listens
+    } else if let Some(BukrsSDPlayerQuit { player_id }) = cast_packet(packet) {
+        api.permissions.invalidate(&player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+
+    use crate::{core::player::PlayerId, net::{BukrsPacketData, BukrsReqHasPermission, BukrsResHasPermission, BukrsReqHasPermissions, BukrsResHasPermissions, BukrsSDPermissionChange}, tests::{loopback, respond}};
+
+    use super::PermissionChange;
+
+    #[tokio::test]
+    async fn test_permission_cache() {
+        let (mut api, mut server) = loopback().await;
+        let player_id = PlayerId(1);
+
+        let (allowed, request) = tokio::join!(api.has_permission(&player_id, "warps.use"), respond::<BukrsReqHasPermission>(&mut server, BukrsResHasPermission { found: true, value: true }));
+        assert!(allowed.unwrap());
+        assert_eq!(request.permission, "warps.use");
+        assert!(api.has_permission(&player_id, "warps.use").await.unwrap());  // Cached, nothing is sent
+
+        let (allowed, request) = tokio::join!(api.has_permissions(&player_id, &["warps.set", "warps.use", "warps.delete"]), respond::<BukrsReqHasPermissions>(&mut server, BukrsResHasPermissions { found: true, values: vec![false, true] }));
+        assert_eq!(allowed.unwrap(), vec![false, true, true]);
+        assert_eq!(request.permissions, vec!["warps.set", "warps.delete"]);
+
+        let mut changes = api.permission_changes();
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDPermissionChange { player_id: player_id.clone() }) }).await.unwrap();
+        assert_eq!(changes.recv().await.unwrap(), PermissionChange { player_id: player_id.clone() });
+
+        let (allowed, _) = tokio::join!(api.has_permission(&player_id, "warps.use"), respond::<BukrsReqHasPermission>(&mut server, BukrsResHasPermission { found: true, value: false }));
+        assert!(!allowed.unwrap());
+
+        let (allowed, _) = tokio::join!(api.has_permission(&PlayerId(2), "warps.use"), respond::<BukrsReqHasPermission>(&mut server, BukrsResHasPermission { found: false, value: false }));
+        assert!(allowed.is_err());
+    }
+}
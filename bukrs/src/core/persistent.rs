@@ -0,0 +1,345 @@
+use std::{cell::Cell, collections::BTreeMap};
+
+use anyhow::bail;
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError, schema::{self, TypeSchema, FieldSchema}};
+use bukrs_derive::BukrsType;
+use bytes::BytesMut;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqGetData, BukrsResGetData, BukrsReqSetData, BukrsReqRemoveData, BukrsResDataUpdate, BukrsReqDataKeys, BukrsResDataKeys}};
+
+use super::{block::BlockPos, entity::EntityId, item::Item};
+
+/// Key of a stored value, e.g. `warps:home`. Bukkit only accepts `[a-z0-9._-]` in the namespace and `[a-z0-9/._-]` in the key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, BukrsType)]
+pub struct NamespacedKey(pub String);
+
+impl NamespacedKey {
+    pub fn new(namespace: &str, key: &str) -> anyhow::Result<NamespacedKey> {
+        let key = NamespacedKey(format!("{}:{}", namespace, key));
+        key.validate()?;
+        Ok(key)
+    }
+
+    /// Fails unless Bukkit accepts the key, which it checks for keys built without [`NamespacedKey::new`] too
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let Some((namespace, key)) = self.0.split_once(':') else { bail!("Key {:?} has no namespace", self.0) };
+        if namespace.is_empty() || !namespace.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '_' | '-')) {
+            bail!("Namespace {:?} must be made of [a-z0-9._-]", namespace);
+        }
+        if key.is_empty() || !key.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '/' | '.' | '_' | '-')) {
+            bail!("Key {:?} must be made of [a-z0-9/._-]", key);
+        }
+        Ok(())
+    }
+}
+
+/// Bukkit's `PersistentDataType`s, in the order of their tags
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub enum PersistentKind {
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    String,
+    ByteArray,
+    IntArray,
+    LongArray,
+    Container
+}
+
+/// A value as stored in a persistent data container, tagged with its [`PersistentKind`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BukrsType)]
+pub enum PersistentValue {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+    Container(#[bukrs(strategy = "crate::arbitrary::nested_container")] PersistentContainer)
+}
+
+impl PersistentValue {
+    pub fn kind(&self) -> PersistentKind {
+        match self {
+            PersistentValue::Byte(_) => PersistentKind::Byte,
+            PersistentValue::Short(_) => PersistentKind::Short,
+            PersistentValue::Int(_) => PersistentKind::Int,
+            PersistentValue::Long(_) => PersistentKind::Long,
+            PersistentValue::Float(_) => PersistentKind::Float,
+            PersistentValue::Double(_) => PersistentKind::Double,
+            PersistentValue::String(_) => PersistentKind::String,
+            PersistentValue::ByteArray(_) => PersistentKind::ByteArray,
+            PersistentValue::IntArray(_) => PersistentKind::IntArray,
+            PersistentValue::LongArray(_) => PersistentKind::LongArray,
+            PersistentValue::Container(_) => PersistentKind::Container
+        }
+    }
+}
+
+/// A nested container, Bukkit's `TAG_CONTAINER`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct PersistentContainer(pub BTreeMap<NamespacedKey, PersistentValue>);
+
+/// Bukkit's limit on nested NBT. Deeper containers from the wire are rejected before they can overflow the stack.
+pub const MAX_CONTAINER_DEPTH: usize = 512;
+
+thread_local! {
+    /// Containers being decoded on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Leaves a level of nesting when dropped, also when decoding fails
+struct Nested;
+
+impl Nested {
+    fn enter() -> Result<Nested, DecodeError> {
+        let depth = DEPTH.with(|depth| depth.get()) + 1;
+        if depth > MAX_CONTAINER_DEPTH {
+            return Err(DecodeError::Invalid(format!("Containers nested deeper than {}", MAX_CONTAINER_DEPTH)));
+        }
+        DEPTH.with(|cell| cell.set(depth));
+        Ok(Nested)
+    }
+}
+
+impl Drop for Nested {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Written like the derive would, the entries as a map
+impl BukrsType for PersistentContainer {
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let _nested = Nested::enter()?;
+        Ok(PersistentContainer(BTreeMap::decode(bytes)?))
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.0.encode(bytes);
+    }
+
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::CUSTOM
+    }
+
+    fn schema() -> TypeSchema {
+        schema::derived("PersistentContainer", || {
+            TypeSchema::Struct { name: "PersistentContainer".to_string(), fields: vec![FieldSchema { name: "0".to_string(), ty: BTreeMap::<NamespacedKey, PersistentValue>::schema() }] }
+        })
+    }
+}
+
+impl PersistentContainer {
+    pub fn get<T: PersistentType>(&self, key: &NamespacedKey) -> Option<T> {
+        self.0.get(key).cloned().and_then(T::from_value)
+    }
+
+    pub fn set<T: PersistentType>(&mut self, key: &NamespacedKey, value: T) {
+        self.0.insert(key.clone(), value.into_value());
+    }
+
+    pub fn remove(&mut self, key: &NamespacedKey) -> Option<PersistentValue> {
+        self.0.remove(key)
+    }
+}
+
+/// Rust types that map onto one [`PersistentKind`]
+pub trait PersistentType: Sized {
+    const KIND: PersistentKind;
+
+    fn into_value(self) -> PersistentValue;
+
+    /// `None` if the value is of another kind
+    fn from_value(value: PersistentValue) -> Option<Self>;
+}
+
+macro_rules! persistent_type {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl PersistentType for $ty {
+                const KIND: PersistentKind = PersistentKind::$variant;
+
+                fn into_value(self) -> PersistentValue {
+                    PersistentValue::$variant(self)
+                }
+
+                fn from_value(value: PersistentValue) -> Option<Self> {
+                    match value {
+                        PersistentValue::$variant(value) => Some(value),
+                        _ => None
+                    }
+                }
+            }
+        )*
+    };
+}
+
+persistent_type!(i8 => Byte, i16 => Short, i32 => Int, i64 => Long, f32 => Float, f64 => Double, String => String, Vec<u8> => ByteArray, Vec<i32> => IntArray, Vec<i64> => LongArray, PersistentContainer => Container);
+
+/// Something with a persistent data container
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub enum DataHolder {
+    /// Any loaded entity, players included
+    Entity(EntityId),
+    /// A chunk, in chunk coordinates
+    Chunk { world: String, x: i32, z: i32 },
+    /// An item stack held by the client. Changes give back a new stack, see [`PersistentData::item`].
+    Item(Item)
+}
+
+impl DataHolder {
+    /// The chunk containing `pos`
+    pub fn chunk_of(pos: &BlockPos) -> DataHolder {
+        DataHolder::Chunk { world: pos.world.clone(), x: pos.x >> 4, z: pos.z >> 4 }
+    }
+}
+
+/// Typed access to the persistent data container of a [`DataHolder`]. Every call is a round trip.
+pub struct PersistentData {
+    holder: DataHolder,
+    api: API
+}
+
+impl PersistentData {
+    pub fn holder(&self) -> &DataHolder {
+        &self.holder
+    }
+
+    /// The item with every change so far, if the holder is an item
+    pub fn item(&self) -> Option<&Item> {
+        match &self.holder {
+            DataHolder::Item(item) => Some(item),
+            _ => None
+        }
+    }
+
+    fn check(&self, found: bool) -> anyhow::Result<()> {
+        if !found {
+            bail!("{:?} no longer exists, or the server refused a key", self.holder);
+        }
+        Ok(())
+    }
+
+    /// `None` if nothing is stored under `key`, or a value of another kind is
+    pub async fn get<T: PersistentType>(&mut self, key: &NamespacedKey) -> anyhow::Result<Option<T>> {
+        key.validate()?;
+        let BukrsResGetData { found, value } = self.api.send_packet_await(BukrsReqGetData { holder: self.holder.clone(), key: key.clone(), kind: T::KIND }).await?;
+        self.check(found)?;
+        Ok(value.and_then(T::from_value))
+    }
+
+    /// Replaces any value stored under `key`, whatever its kind
+    pub async fn set<T: PersistentType>(&mut self, key: &NamespacedKey, value: T) -> anyhow::Result<()> {
+        key.validate()?;
+        let BukrsResDataUpdate { found, holder, .. } = self.api.send_packet_await(BukrsReqSetData { holder: self.holder.clone(), key: key.clone(), value: value.into_value() }).await?;
+        self.check(found)?;
+        self.holder = holder;
+        Ok(())
+    }
+
+    /// Returns whether anything was stored under `key`
+    pub async fn remove(&mut self, key: &NamespacedKey) -> anyhow::Result<bool> {
+        key.validate()?;
+        let BukrsResDataUpdate { found, existed, holder } = self.api.send_packet_await(BukrsReqRemoveData { holder: self.holder.clone(), key: key.clone() }).await?;
+        self.check(found)?;
+        self.holder = holder;
+        Ok(existed)
+    }
+
+    /// Every key in the container, including those of other plugins
+    pub async fn keys(&mut self) -> anyhow::Result<Vec<NamespacedKey>> {
+        let BukrsResDataKeys { found, keys } = self.api.send_packet_await(BukrsReqDataKeys { holder: self.holder.clone() }).await?;
+        self.check(found)?;
+        Ok(keys)
+    }
+}
+
+impl API {
+    pub fn persistent_data(&self, holder: DataHolder) -> PersistentData {
+        PersistentData { holder, api: self.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bukrs_core::BukrsType;
+    use bytes::BytesMut;
+
+    use crate::{core::{block::Material, item::Item, player::PlayerId}, net::{BukrsReqGetData, BukrsResGetData, BukrsReqSetData, BukrsResDataUpdate}, tests::{loopback, respond}};
+
+    use super::{DataHolder, NamespacedKey, PersistentContainer, PersistentKind, PersistentValue, MAX_CONTAINER_DEPTH};
+
+    /// `depth` containers each holding the next under an empty key, the innermost empty
+    fn nested(depth: usize) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        for _ in 1..depth {
+            bytes.extend_from_slice(&[0x0a, 0x01, 0x00]);
+        }
+        bytes.extend_from_slice(&[0x0a, 0x00]);
+        bytes
+    }
+
+    /// Decoded on a thread with the stack of a tokio worker
+    #[test]
+    fn test_nesting_is_limited() {
+        std::thread::Builder::new().stack_size(2 * 1024 * 1024).spawn(|| {
+            let mut bytes = nested(MAX_CONTAINER_DEPTH);
+            PersistentValue::decode(&mut bytes).unwrap();
+            assert!(bytes.is_empty());
+
+            assert!(PersistentValue::decode(&mut nested(MAX_CONTAINER_DEPTH + 1)).is_err());
+            assert!(PersistentValue::decode(&mut nested(200_000)).is_err());
+
+            let mut bytes = nested(MAX_CONTAINER_DEPTH);    // The depth is back to zero after a rejected value
+            PersistentValue::decode(&mut bytes).unwrap();
+        }).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_namespaced_key() {
+        assert_eq!(NamespacedKey::new("my-plugin.v2", "homes/spawn_1").unwrap(), NamespacedKey("my-plugin.v2:homes/spawn_1".to_string()));
+        assert!(NamespacedKey::new("Warps", "home").is_err());
+        assert!(NamespacedKey::new("warps/x", "home").is_err());
+        assert!(NamespacedKey::new("warps", "Home").is_err());
+        assert!(NamespacedKey::new("warps", "a:b").is_err());
+        assert!(NamespacedKey::new("warps", "").is_err());
+        assert!(NamespacedKey::new("", "home").is_err());
+        assert!(NamespacedKey("home".to_string()).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_persistent_data() {
+        let (api, mut server) = loopback().await;
+        let coins = NamespacedKey::new("shop", "coins").unwrap();
+
+        let mut data = api.entity(PlayerId(1)).persistent_data();
+        let (value, request) = tokio::join!(data.get::<i32>(&coins), respond::<BukrsReqGetData>(&mut server, BukrsResGetData { found: true, value: Some(PersistentValue::Int(30)) }));
+        assert_eq!(value.unwrap(), Some(30));
+        let BukrsReqGetData { holder, key, kind } = request;
+        assert_eq!((holder, key, kind), (DataHolder::Entity(PlayerId(1).into()), coins.clone(), PersistentKind::Int));
+
+        let mut home = PersistentContainer::default();
+        home.set(&NamespacedKey::new("warps", "world").unwrap(), "world".to_string());
+        home.set(&NamespacedKey::new("warps", "pos").unwrap(), vec![0, 64, 0]);
+        let mut bytes = BytesMut::new();
+        PersistentValue::Container(home.clone()).encode(&mut bytes);
+        assert_eq!(PersistentValue::decode(&mut bytes).unwrap(), PersistentValue::Container(home.clone()));
+
+        let mut data = api.persistent_data(DataHolder::Item(Item::new(Material::new("compass"), 1)));
+        let tagged = Item { data: vec![1, 2, 3], ..Item::new(Material::new("compass"), 1) };
+        let key = NamespacedKey::new("warps", "home").unwrap();
+        let (set, request) = tokio::join!(data.set(&key, home.clone()), respond::<BukrsReqSetData>(&mut server, BukrsResDataUpdate { found: true, existed: false, holder: DataHolder::Item(tagged.clone()) }));
+        set.unwrap();
+        assert_eq!(request.value, PersistentValue::Container(home.clone()));
+        assert_eq!(data.item(), Some(&tagged));
+    }
+}
@@ -1,70 +1,52 @@
-use bukrs_core::{BukrsType, BukrsNativeType};
-use bytes::{BytesMut, BufMut, Buf};
+use bukrs_derive::BukrsType;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct PlayerId(pub u32);
-
-impl BukrsType for PlayerId {
-    fn ty(&self) -> BukrsNativeType {
-        BukrsNativeType::U32
-    }
+use crate::{API, net::{Packet, cast_packet, BukrsSDPlayerJoin, BukrsSDPlayerQuit}};
 
-    fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u32(self.0);
-    }
-
-    fn decode(bytes: &mut BytesMut) -> Self {
-        PlayerId(bytes.get_u32())
-    }
-}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, BukrsType)]
+pub struct PlayerId(pub u32);
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, BukrsType)]
 pub struct UUID {
     lsb: u64,
     msb: u64
 }
 
-impl BukrsType for UUID {
-    fn decode(bytes: &mut BytesMut) -> Self {
-        let lsb = bytes.get_u64();
-        let msb = bytes.get_u64();
-        UUID { lsb, msb }
-    }
-
-    fn encode(&self, bytes: &mut BytesMut) {
-        bytes.put_u64(self.lsb);
-        bytes.put_u64(self.msb);
+impl UUID {
+    pub fn from_u128(value: u128) -> UUID {
+        UUID { lsb: value as u64, msb: (value >> 64) as u64 }
     }
 
-    fn ty(&self) -> BukrsNativeType {
-        BukrsNativeType::CUSTOM
+    pub fn as_u128(&self) -> u128 {
+        ((self.msb as u128) << 64) | self.lsb as u128
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, BukrsType)]
 pub struct PlayerData {
     pub id: PlayerId,
     pub name: String,
     pub uuid: UUID
 }
 
-impl BukrsType for PlayerData {
-    fn decode(bytes: &mut BytesMut) -> Self {
-        let id = PlayerId::decode(bytes);
-        let name = String::decode(bytes);
-        let uuid = UUID::decode(bytes);
-        PlayerData { id, name, uuid }
+impl API {
+    /// Players currently online, as last reported by the server. Does not make a round trip.
+    pub fn cached_players(&self) -> Vec<PlayerData> {
+        self.players.lock().unwrap().values().cloned().collect()
     }
 
-    fn encode(&self, bytes: &mut BytesMut) {
-        self.id.encode(bytes);
-        self.name.encode(bytes);
-        self.uuid.encode(bytes);
+    pub fn cached_player(&self, player_id: &PlayerId) -> Option<PlayerData> {
+        self.players.lock().unwrap().get(player_id).cloned()
     }
+}
 
-    fn ty(&self) -> BukrsNativeType {
-        BukrsNativeType::CUSTOM
+/// Keeps the player cache in sync with join and quit packets
+#[allow(clippy::borrowed_box)]
+pub(crate) fn update_cache(api: &API, packet: &Box<dyn Packet>) {
+    if let Some(BukrsSDPlayerJoin { data }) = cast_packet(packet) {
+        api.players.lock().unwrap().insert(data.id.clone(), data);
+    } else if let Some(BukrsSDPlayerQuit { player_id }) = cast_packet(packet) {
+        api.players.lock().unwrap().remove(&player_id);
     }
 }
-
@@ -0,0 +1,273 @@
+use std::{pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll}};
+
+use anyhow::anyhow;
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError, schema::{TypeSchema, FieldSchema}};
+use bukrs_derive::BukrsType;
+use bytes::BytesMut;
+use futures::Future;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use tokio::sync::{oneshot, watch};
+
+use crate::{API, net::{Packet, cast_packet, BukrsReqStartJob, BukrsResStartJob, BukrsReqCancelJob, BukrsResCancelJob, BukrsSDJobProgress, BukrsSDJobDone}};
+
+use super::{block::BlockPos, world::BlockData};
+
+/// Blocks the server changes per tick unless told otherwise
+pub const DEFAULT_BLOCKS_PER_TICK: u32 = 4096;
+
+/// Inclusive cuboid of blocks in one world
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub world: String,
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32)
+}
+
+impl Region {
+    /// Region spanning both corners, in any order. Panics if they are in different worlds.
+    pub fn new(a: &BlockPos, b: &BlockPos) -> Region {
+        assert_eq!(a.world, b.world, "Region corners must be in the same world");
+        Region { world: a.world.clone(), min: (a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)), max: (a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)) }
+    }
+
+    /// Computed in i64, as a region spanning every i32 coordinate is 2^32 blocks wide
+    pub fn size(&self) -> (u64, u64, u64) {
+        let length = |min: i32, max: i32| (max as i64 - min as i64 + 1) as u64;
+        (length(self.min.0, self.max.0), length(self.min.1, self.max.1), length(self.min.2, self.max.2))
+    }
+
+    /// Saturates at `u64::MAX` for regions too large to count
+    pub fn volume(&self) -> u64 {
+        let (x, y, z) = self.size();
+        x.saturating_mul(y).saturating_mul(z)
+    }
+
+    pub fn contains(&self, pos: &BlockPos) -> bool {
+        pos.world == self.world
+            && (self.min.0..=self.max.0).contains(&pos.x)
+            && (self.min.1..=self.max.1).contains(&pos.y)
+            && (self.min.2..=self.max.2).contains(&pos.z)
+    }
+
+    pub fn min_pos(&self) -> BlockPos {
+        BlockPos::new(&self.world, self.min.0, self.min.1, self.min.2)
+    }
+
+    pub fn max_pos(&self) -> BlockPos {
+        BlockPos::new(&self.world, self.max.0, self.max.1, self.max.2)
+    }
+}
+
+impl BukrsType for Region {
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let min = BlockPos::decode(bytes)?;
+        let max = BlockPos::decode(bytes)?;
+        Ok(Region::new(&min, &max))
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.min_pos().encode(bytes);
+        self.max_pos().encode(bytes);
+    }
+
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::CUSTOM
+    }
+
+    /// Both corners as block positions
+    fn schema() -> TypeSchema {
+        let fields = vec![FieldSchema { name: "min".to_string(), ty: BlockPos::schema() }, FieldSchema { name: "max".to_string(), ty: BlockPos::schema() }];
+        TypeSchema::Struct { name: "Region".to_string(), fields }
+    }
+}
+
+/// Work the server carries out over several ticks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub enum RegionOperation {
+    Fill { region: Region, data: BlockData },
+    /// Only blocks whose state equals `from` are changed
+    Replace { region: Region, from: BlockData, to: BlockData },
+    /// Copies `source` so that its minimum corner lands on `destination`
+    Clone { source: Region, destination: BlockPos }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct JobProgress {
+    /// Blocks visited so far
+    pub done: u64,
+    pub total: u64
+}
+
+impl JobProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 { 1.0 } else { self.done as f64 / self.total as f64 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobOutcome {
+    /// Blocks whose state actually changed
+    pub changed: u64,
+    pub cancelled: bool
+}
+
+pub(crate) struct JobState {
+    progress: watch::Sender<JobProgress>,
+    done: Mutex<Option<oneshot::Sender<JobOutcome>>>
+}
+
+/// A running region operation. Awaiting it yields the outcome once the server finished or cancelled the job.
+pub struct Job {
+    pub id: u32,
+    api: API,
+    progress: watch::Receiver<JobProgress>,
+    done: oneshot::Receiver<JobOutcome>
+}
+
+impl Job {
+    pub fn progress(&self) -> JobProgress {
+        *self.progress.borrow()
+    }
+
+    /// Resolves on the next progress report
+    pub async fn progress_changed(&mut self) -> anyhow::Result<JobProgress> {
+        self.progress.changed().await?;
+        Ok(self.progress())
+    }
+
+    /// Asks the server to stop after the current batch. The job then completes with `cancelled` set.
+    pub async fn cancel(&mut self) -> anyhow::Result<()> {
+        let BukrsResCancelJob {  } = self.api.send_packet_await(BukrsReqCancelJob { job_id: self.id }).await?;
+        Ok(())
+    }
+}
+
+impl Future for Job {
+    type Output = anyhow::Result<JobOutcome>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.done).poll(cx).map(|outcome| outcome.map_err(|_| anyhow!("Connection closed before job {} finished", self.id)))
+    }
+}
+
+impl API {
+    /// Starts an operation on the server, changing at most `blocks_per_tick` blocks each tick.
+    /// Fails without starting anything if a world doesn't exist or a block state is invalid.
+    pub async fn start_job(&mut self, operation: RegionOperation, blocks_per_tick: u32, physics: bool) -> anyhow::Result<Job> {
+        let (progress_tx, progress) = watch::channel(JobProgress::default());
+        let (done_tx, done) = oneshot::channel();
+        let job_id = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job_id = loop {     // The server refuses the id of a job still running
+                let job_id = rand::thread_rng().gen_range(1..u32::MAX);
+                if !jobs.contains_key(&job_id) {
+                    break job_id;
+                }
+            };
+            jobs.insert(job_id, Arc::new(JobState { progress: progress_tx, done: Mutex::new(Some(done_tx)) }));    // Registered first so no report is missed
+            job_id
+        };
+
+        let response = self.send_packet_await(BukrsReqStartJob { job_id, operation, blocks_per_tick, physics }).await;
+        let BukrsResStartJob { total } = match response {
+            Ok(response) => response,
+            Err(error) => {
+                self.jobs.lock().unwrap().remove(&job_id);
+                return Err(error);
+            }
+        };
+        if let Some(state) = self.jobs.lock().unwrap().get(&job_id) {
+            state.progress.send_modify(|progress| progress.total = total);
+        }
+        let mut job = Job { id: job_id, api: self.clone(), progress, done };
+        job.progress.borrow_and_update();   // Only later reports count as changes
+        Ok(job)
+    }
+
+    pub async fn fill(&mut self, region: Region, data: &BlockData) -> anyhow::Result<Job> {
+        self.start_job(RegionOperation::Fill { region, data: data.clone() }, DEFAULT_BLOCKS_PER_TICK, false).await
+    }
+
+    pub async fn replace(&mut self, region: Region, from: &BlockData, to: &BlockData) -> anyhow::Result<Job> {
+        self.start_job(RegionOperation::Replace { region, from: from.clone(), to: to.clone() }, DEFAULT_BLOCKS_PER_TICK, false).await
+    }
+
+    /// `clone` itself is taken by [`Clone`]
+    pub async fn clone_region(&mut self, source: Region, destination: &BlockPos) -> anyhow::Result<Job> {
+        self.start_job(RegionOperation::Clone { source, destination: destination.clone() }, DEFAULT_BLOCKS_PER_TICK, false).await
+    }
+}
+
+/// Forwards progress and completion reports to the matching [`Job`]
+#[allow(clippy::borrowed_box)]
+pub(crate) fn update_jobs(api: &API, packet: &Box<dyn Packet>) {
+    if let Some(BukrsSDJobProgress { job_id, done, total }) = cast_packet(packet) {
+        if let Some(state) = api.jobs.lock().unwrap().get(&job_id) {
+            state.progress.send_replace(JobProgress { done, total });
+        }
+    } else if let Some(BukrsSDJobDone { job_id, changed, cancelled }) = cast_packet(packet) {
+        if let Some(state) = api.jobs.lock().unwrap().remove(&job_id) {
+            state.progress.send_modify(|progress| progress.done = if cancelled { progress.done } else { progress.total });
+            if let Some(done) = state.done.lock().unwrap().take() {
+                let _ = done.send(JobOutcome { changed, cancelled });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{core::{block::{BlockPos, Material}, world::BlockData}, net::{BukrsPacketData, BukrsReqStartJob, BukrsResStartJob, BukrsSDJobProgress, BukrsSDJobDone, cast_packet}, tests::loopback};
+
+    use super::{Region, RegionOperation, JobProgress, JobOutcome};
+
+    #[test]
+    fn test_region() {
+        let region = Region::new(&BlockPos::new("world", 10, 70, -5), &BlockPos::new("world", -10, 64, 5));
+        assert_eq!(region.min, (-10, 64, -5));
+        assert_eq!(region.volume(), 21 * 7 * 11);
+        assert!(region.contains(&BlockPos::new("world", 0, 64, 0)));
+        assert!(!region.contains(&BlockPos::new("world_nether", 0, 64, 0)));
+
+        let everything = Region::new(&BlockPos::new("world", i32::MIN, i32::MIN, 0), &BlockPos::new("world", i32::MAX, i32::MAX, 0));
+        assert_eq!(everything.size(), (1 << 32, 1 << 32, 1));
+        assert_eq!(everything.volume(), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_is_forgotten() {
+        let (mut api, _server) = loopback().await;
+        api.tx.lock().await.get_mut().shutdown().await.unwrap();    // Sending the request now fails
+
+        let region = Region::new(&BlockPos::new("world", 0, 0, 0), &BlockPos::new("world", 15, 15, 15));
+        assert!(api.fill(region, &BlockData::new(Material::new("stone"))).await.is_err());
+        assert!(api.jobs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_lifecycle() {
+        let (mut api, mut server) = loopback().await;
+        let region = Region::new(&BlockPos::new("world", 0, 0, 0), &BlockPos::new("world", 15, 15, 15));
+
+        let stone = BlockData::new(Material::new("stone"));
+        let (job, job_id) = tokio::join!(api.fill(region.clone(), &stone), async {
+            let request = server.next().await.unwrap().unwrap();
+            let BukrsReqStartJob { job_id, operation, .. } = cast_packet(&request.event).unwrap();
+            assert_eq!(operation, RegionOperation::Fill { region: region.clone(), data: stone.clone() });
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResStartJob { total: region.volume() }) }).await.unwrap();
+            job_id
+        });
+        let mut job = job.unwrap();
+        assert_eq!(job.progress().total, 4096);
+
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDJobProgress { job_id, done: 1024, total: 4096 }) }).await.unwrap();
+        assert_eq!(job.progress_changed().await.unwrap(), JobProgress { done: 1024, total: 4096 });
+
+        server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDJobDone { job_id, changed: 4000, cancelled: false }) }).await.unwrap();
+        assert_eq!(job.await.unwrap(), JobOutcome { changed: 4000, cancelled: false });
+    }
+}
@@ -0,0 +1,188 @@
+use std::{collections::HashMap, future::Future, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}};
+
+use futures::future::BoxFuture;
+use tokio::sync::watch;
+
+use crate::{API, net::{Packet, cast_packet, BukrsReqSubscribeTicks, BukrsResSubscribeTicks, BukrsSDTick}};
+
+type TaskFn = Box<dyn FnMut() -> BoxFuture<'static, ()> + Send>;
+
+struct Task {
+    due: u64,
+    period: Option<u64>,
+    run: TaskFn
+}
+
+/// Tick counter and pending tasks, shared by every clone of the [`API`]
+pub(crate) struct SchedulerState {
+    tick: watch::Sender<u64>,
+    tasks: Mutex<HashMap<u64, Task>>,
+    next_id: AtomicU64,
+    subscribed: tokio::sync::Mutex<bool>
+}
+
+impl SchedulerState {
+    pub(crate) fn new() -> SchedulerState {
+        SchedulerState { tick: watch::channel(0).0, tasks: Mutex::new(HashMap::new()), next_id: AtomicU64::new(1), subscribed: tokio::sync::Mutex::new(false) }
+    }
+}
+
+/// A scheduled task. Dropping the handle does not cancel the task.
+#[derive(Clone)]
+pub struct TaskHandle {
+    pub id: u64,
+    state: Arc<SchedulerState>
+}
+
+impl TaskHandle {
+    /// Returns whether the task was still pending
+    pub fn cancel(&self) -> bool {
+        self.state.tasks.lock().unwrap().remove(&self.id).is_some()
+    }
+
+    /// False once a delayed task has run or any task was cancelled
+    pub fn is_pending(&self) -> bool {
+        self.state.tasks.lock().unwrap().contains_key(&self.id)
+    }
+}
+
+/// Runs tasks on the server's ticks, so they slow down with the server when it lags.
+/// The server starts sending ticks the first time the scheduler is used.
+pub struct Scheduler {
+    api: API
+}
+
+impl Scheduler {
+    async fn subscribe(&mut self) -> anyhow::Result<()> {
+        let state = self.api.scheduler.clone();
+        let mut subscribed = state.subscribed.lock().await;
+        if !*subscribed {
+            let BukrsResSubscribeTicks { tick } = self.api.send_packet_await(BukrsReqSubscribeTicks {  }).await?;
+            state.tick.send_replace(tick);
+            *subscribed = true;
+        }
+        Ok(())
+    }
+
+    async fn schedule(&mut self, delay: u64, period: Option<u64>, run: TaskFn) -> anyhow::Result<TaskHandle> {
+        self.subscribe().await?;
+        let state = self.api.scheduler.clone();
+        let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+        let due = *state.tick.borrow() + delay.max(1);
+        state.tasks.lock().unwrap().insert(id, Task { due, period, run });
+        Ok(TaskHandle { id, state })
+    }
+
+    /// The last tick the server reported
+    pub fn current_tick(&self) -> u64 {
+        *self.api.scheduler.tick.borrow()
+    }
+
+    /// Runs `task` once, `ticks` ticks from now. A delay of 0 runs it on the next tick.
+    pub async fn run_later<F, Fut>(&mut self, ticks: u64, task: F) -> anyhow::Result<TaskHandle> where F: FnOnce() -> Fut + Send + 'static, Fut: Future<Output = ()> + Send + 'static {
+        let mut task = Some(task);
+        self.schedule(ticks, None, Box::new(move || match task.take() {
+            Some(task) => Box::pin(task()),
+            None => Box::pin(async {})
+        })).await
+    }
+
+    /// Runs `task` after `delay` ticks and then every `period` ticks until cancelled
+    pub async fn run_timer<F, Fut>(&mut self, delay: u64, period: u64, task: F) -> anyhow::Result<TaskHandle> where F: Fn() -> Fut + Send + 'static, Fut: Future<Output = ()> + Send + 'static {
+        self.schedule(delay, Some(period.max(1)), Box::new(move || Box::pin(task()))).await
+    }
+
+    /// Waits for the next server tick and returns its number
+    pub async fn next_tick(&mut self) -> anyhow::Result<u64> {
+        let mut tick = self.api.scheduler.tick.subscribe();
+        self.subscribe().await?;
+        tick.borrow_and_update();
+        tick.changed().await?;
+        let current = *tick.borrow();
+        Ok(current)
+    }
+}
+
+impl API {
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler { api: self.clone() }
+    }
+}
+
+/// Advances the tick counter and starts every task that is due
+#[allow(clippy::borrowed_box)]
+pub(crate) fn tick(api: &API, packet: &Box<dyn Packet>) {
+    let Some(BukrsSDTick { tick }) = cast_packet(packet) else { return };
+    api.scheduler.tick.send_replace(tick);
+
+    let mut tasks = api.scheduler.tasks.lock().unwrap();
+    let mut finished = vec![];
+    let mut due = tasks.iter_mut().filter(|(_, task)| task.due <= tick).collect::<Vec<_>>();
+    due.sort_by_key(|(id, task)| (task.due, **id));    // Earlier tasks first, then in scheduling order
+    for (id, task) in due {
+        tokio::spawn((task.run)());
+        match task.period {
+            Some(period) => task.due = tick + period,
+            None => finished.push(*id)
+        }
+    }
+    for id in finished {
+        tasks.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::sync::mpsc;
+
+    use crate::{net::{BukrsPacketData, BukrsReqSubscribeTicks, BukrsResSubscribeTicks, BukrsSDTick, cast_packet}, tests::loopback};
+
+    #[tokio::test]
+    async fn test_delayed_and_repeating_tasks() {
+        let (api, mut server) = loopback().await;
+        let (log, mut ran) = mpsc::unbounded_channel();
+        let mut scheduler = api.scheduler();
+
+        let later_log = log.clone();
+        let later = scheduler.run_later(2, move || async move { later_log.send("later").unwrap() });
+        let accept = async {
+            let request = server.next().await.unwrap().unwrap();
+            cast_packet::<BukrsReqSubscribeTicks>(&request.event).unwrap();
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResSubscribeTicks { tick: 100 }) }).await.unwrap();
+        };
+        let (later, _) = tokio::join!(later, accept);
+        let later = later.unwrap();
+
+        let timer_log = log.clone();
+        let timer = scheduler.run_timer(1, 2, move || {
+            let timer_log = timer_log.clone();
+            async move { timer_log.send("timer").unwrap() }
+        }).await.unwrap();
+        assert_eq!(scheduler.current_tick(), 100);
+
+        let mut waiting = api.scheduler();
+        let mut next_tick = Box::pin(waiting.next_tick());
+        assert!(futures::poll!(&mut next_tick).is_pending());     // Now watching the tick counter
+
+        for (tick, name) in [(101, "timer"), (102, "later"), (103, "timer")] {
+            server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDTick { tick }) }).await.unwrap();
+            assert_eq!(ran.recv().await, Some(name));
+            if tick == 101 {
+                assert_eq!((&mut next_tick).await.unwrap(), 101);
+            }
+        }
+        assert!(timer.cancel());
+
+        // Due on tick 105 with the timer, and started after it, as tasks are spawned in scheduling order
+        let sentinel = scheduler.run_later(2, move || async move { log.send("sentinel").unwrap() }).await.unwrap();
+        for tick in 104..=105 {
+            server.send(BukrsPacketData { payload_id: None, event: Box::new(BukrsSDTick { tick }) }).await.unwrap();
+        }
+        assert_eq!(ran.recv().await, Some("sentinel"));
+
+        assert!(!later.is_pending());
+        assert!(!timer.is_pending());
+        assert!(!sentinel.is_pending());
+    }
+}
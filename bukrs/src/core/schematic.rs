@@ -0,0 +1,492 @@
+use std::{collections::HashMap, fs::File, io::{Read, Write, BufReader, BufWriter}, path::Path};
+
+use anyhow::{anyhow, bail};
+use quartz_nbt::{NbtCompound, NbtList, io::{Flavor, read_nbt, write_nbt}};
+
+use crate::{API, net::{BukrsReqGetBlocks, BukrsResGetBlocks}};
+
+use super::{block::Material, region::Region, world::{World, BlockData, BlockChanges}};
+
+/// Data version of Minecraft 1.19.2, the version the plugin is built against
+pub const DEFAULT_DATA_VERSION: i32 = 3120;
+
+/// Blocks sent per [`BukrsReqSetBlocks`](crate::net::BukrsReqSetBlocks) while pasting
+const PASTE_BATCH: usize = 32768;
+
+/// Edge of the cubes requested per [`BukrsReqGetBlocks`] while copying. The server reads each in one main-thread task,
+/// and refuses regions larger than such a cube.
+pub const COPY_SECTION: u64 = 64;
+
+/// Clockwise, looking down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Flips along the x axis, swapping east and west
+    LeftRight,
+    /// Flips along the z axis, swapping north and south
+    FrontBack
+}
+
+const HORIZONTAL: [&str; 4] = ["north", "east", "south", "west"];
+
+fn transform_direction(direction: &str, rotation: Rotation, mirror: Mirror) -> Option<&'static str> {
+    let mut index = HORIZONTAL.iter().position(|horizontal| *horizontal == direction)?;
+    index = match (mirror, index) {
+        (Mirror::LeftRight, 1 | 3) | (Mirror::FrontBack, 0 | 2) => (index + 2) % 4,
+        _ => index
+    };
+    Some(HORIZONTAL[(index + rotation as usize) % 4])
+}
+
+/// Rotates and mirrors the directional properties of a block state: `facing`, `axis`, `rotation`,
+/// the four side connections and the handedness of stairs and doors. Anything else is kept as is.
+pub fn transform_block(data: &BlockData, rotation: Rotation, mirror: Mirror) -> BlockData {
+    let mut transformed = BlockData::new(data.material.clone());
+    for (key, value) in data.properties.iter() {
+        let (key, value) = match key.as_str() {
+            "facing" => (key.clone(), transform_direction(value, rotation, mirror).map(str::to_string).unwrap_or_else(|| value.clone())),
+            "axis" if rotation as usize % 2 == 1 => (key.clone(), match value.as_str() { "x" => "z", "z" => "x", other => other }.to_string()),
+            "rotation" => {
+                let Ok(mut angle) = value.parse::<u32>() else { continue };
+                angle = match mirror {
+                    Mirror::None => angle,
+                    Mirror::LeftRight => (16 - angle) % 16,
+                    Mirror::FrontBack => (24 - angle) % 16
+                };
+                (key.clone(), ((angle + rotation as u32 * 4) % 16).to_string())
+            }
+            "shape" | "hinge" if mirror != Mirror::None => (key.clone(), if value.contains("left") { value.replace("left", "right") } else { value.replace("right", "left") }),
+            _ => match transform_direction(key, rotation, mirror) {
+                Some(side) => (side.to_string(), value.clone()),
+                None => (key.clone(), value.clone())
+            }
+        };
+        transformed.properties.insert(key, value);
+    }
+    transformed
+}
+
+/// Moves a relative position the same way [`transform_block`] turns the block
+pub fn transform_position((mut x, y, mut z): (i32, i32, i32), rotation: Rotation, mirror: Mirror) -> (i32, i32, i32) {
+    match mirror {
+        Mirror::None => {}
+        Mirror::LeftRight => x = -x,
+        Mirror::FrontBack => z = -z
+    }
+    for _ in 0..rotation as usize {
+        (x, z) = (-z, x);
+    }
+    (x, y, z)
+}
+
+/// Tile entity data, stored so it survives loading and saving. Pasting only places the block itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    pub pos: (i32, i32, i32),
+    pub id: String,
+    pub data: NbtCompound
+}
+
+/// A Sponge schematic. Versions 1 to 3 can be read; files are written as version 2, which every WorldEdit release reads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    pub width: u16,
+    pub height: u16,
+    pub length: u16,
+    /// Added to block positions when pasting
+    pub offset: (i32, i32, i32),
+    pub data_version: i32,
+    pub palette: Vec<BlockData>,
+    /// Palette indices, ordered x first, then z, then y
+    blocks: Vec<u32>,
+    pub block_entities: Vec<BlockEntity>
+}
+
+fn varint_decode(data: &[i8]) -> anyhow::Result<Vec<u32>> {
+    let mut values = vec![];
+    let mut bytes = data.iter().map(|byte| *byte as u8);
+    while let Some(mut byte) = bytes.next() {
+        let mut value = 0u32;
+        let mut shift = 0;
+        loop {
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 28 {
+                bail!("Block data varint is too long");
+            }
+            byte = bytes.next().ok_or_else(|| anyhow!("Block data ends in the middle of a varint"))?;
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn varint_encode(values: &[u32]) -> Vec<i8> {
+    let mut data = vec![];
+    for value in values.iter() {
+        let mut value = *value;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                data.push(byte as i8);
+                break;
+            }
+            data.push((byte | 0x80) as i8);
+        }
+    }
+    data
+}
+
+fn read_position(compound: &NbtCompound, key: &str) -> anyhow::Result<(i32, i32, i32)> {
+    match compound.get::<_, &[i32]>(key)? {
+        [x, y, z] => Ok((*x, *y, *z)),
+        _ => bail!("{} must hold three integers", key)
+    }
+}
+
+impl Schematic {
+    /// An empty schematic filled with air
+    pub fn new(width: u16, height: u16, length: u16) -> Schematic {
+        let volume = width as usize * height as usize * length as usize;
+        Schematic { width, height, length, offset: (0, 0, 0), data_version: DEFAULT_DATA_VERSION, palette: vec![BlockData::new(Material::new("air"))], blocks: vec![0; volume], block_entities: vec![] }
+    }
+
+    pub fn volume(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn index(&self, x: u16, y: u16, z: u16) -> usize {
+        assert!(x < self.width && y < self.height && z < self.length, "({}, {}, {}) is outside the schematic", x, y, z);
+        x as usize + z as usize * self.width as usize + y as usize * self.width as usize * self.length as usize
+    }
+
+    pub fn get(&self, x: u16, y: u16, z: u16) -> &BlockData {
+        &self.palette[self.blocks[self.index(x, y, z)] as usize]
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, z: u16, data: &BlockData) {
+        let index = self.index(x, y, z);
+        let state = match self.palette.iter().position(|entry| entry == data) {
+            Some(state) => state,
+            None => {
+                self.palette.push(data.clone());
+                self.palette.len() - 1
+            }
+        };
+        self.blocks[index] = state as u32;
+    }
+
+    fn position(&self, index: usize) -> (u16, u16, u16) {
+        let (width, length) = (self.width as usize, self.length as usize);
+        ((index % width) as u16, (index / (width * length)) as u16, ((index / width) % length) as u16)
+    }
+
+    /// Every block with its position inside the schematic
+    pub fn iter(&self) -> impl Iterator<Item = ((u16, u16, u16), &BlockData)> {
+        self.blocks.iter().enumerate().map(|(index, state)| (self.position(index), &self.palette[*state as usize]))
+    }
+
+    /// Reads a gzip-compressed `.schem`
+    pub fn read(reader: &mut impl Read) -> anyhow::Result<Schematic> {
+        let (root, _) = read_nbt(reader, Flavor::GzCompressed)?;
+        let root = match root.get::<_, &NbtCompound>("Schematic") {
+            Ok(schematic) => schematic,    // Version 3 nests everything in a `Schematic` compound
+            Err(_) => &root
+        };
+
+        let version = root.get::<_, i32>("Version")?;
+        if !(1..=3).contains(&version) {
+            bail!("Unsupported schematic version {}", version);
+        }
+        let data_version = if version == 1 { DEFAULT_DATA_VERSION } else { root.get::<_, i32>("DataVersion")? };
+        let width = root.get::<_, i16>("Width")? as u16;
+        let height = root.get::<_, i16>("Height")? as u16;
+        let length = root.get::<_, i16>("Length")? as u16;
+        let offset = if root.contains_key("Offset") { read_position(root, "Offset")? } else { (0, 0, 0) };
+
+        let blocks_root = if version == 3 { root.get::<_, &NbtCompound>("Blocks")? } else { root };
+        let palette_tag = blocks_root.get::<_, &NbtCompound>("Palette")?;
+        let mut palette = vec![None; palette_tag.len()];
+        for (state, index) in palette_tag.inner().iter() {
+            let index = i32::try_from(index)?;
+            let Some(entry) = usize::try_from(index).ok().and_then(|index| palette.get_mut(index)) else {
+                bail!("Palette index {} of {} is outside a palette of {}", index, state, palette_tag.len());
+            };
+            *entry = Some(BlockData::parse(state)?);
+        }
+        let palette = palette.into_iter().map(|entry| entry.unwrap_or_else(|| BlockData::new(Material::new("air")))).collect::<Vec<BlockData>>();
+
+        let blocks = varint_decode(blocks_root.get::<_, &[i8]>(if version == 3 { "Data" } else { "BlockData" })?)?;
+        if blocks.len() != width as usize * height as usize * length as usize {
+            bail!("Expected {} blocks, found {}", width as usize * height as usize * length as usize, blocks.len());
+        }
+        if let Some(state) = blocks.iter().find(|state| **state as usize >= palette.len()) {
+            bail!("Block state {} is missing from the palette", state);
+        }
+
+        let mut block_entities = vec![];
+        let entities_key = if version == 1 { "TileEntities" } else { "BlockEntities" };
+        if blocks_root.contains_key(entities_key) {
+            for entity in blocks_root.get::<_, &NbtList>(entities_key)?.iter_map::<&NbtCompound>() {
+                let entity = entity?;
+                let pos = read_position(entity, "Pos")?;
+                let id = entity.get::<_, &str>("Id")?.to_string();
+                let data = if version == 3 {
+                    entity.get::<_, &NbtCompound>("Data").cloned().unwrap_or_default()
+                } else {
+                    let mut data = entity.clone();    // Versions 1 and 2 keep the fields next to `Pos` and `Id`
+                    data.inner_mut().remove("Pos");
+                    data.inner_mut().remove("Id");
+                    data
+                };
+                block_entities.push(BlockEntity { pos, id, data });
+            }
+        }
+
+        Ok(Schematic { width, height, length, offset, data_version, palette, blocks, block_entities })
+    }
+
+    /// Writes a gzip-compressed version 2 `.schem`
+    pub fn write(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut palette = NbtCompound::new();
+        for (index, data) in self.palette.iter().enumerate() {
+            palette.insert(data.to_string(), index as i32);
+        }
+
+        let mut block_entities = NbtList::new();
+        for entity in self.block_entities.iter() {
+            let mut compound = entity.data.clone();
+            compound.insert("Pos", vec![entity.pos.0, entity.pos.1, entity.pos.2]);
+            compound.insert("Id", entity.id.clone());
+            block_entities.push(compound);
+        }
+
+        let mut root = NbtCompound::new();
+        root.insert("Version", 2);
+        root.insert("DataVersion", self.data_version);
+        root.insert("Width", self.width as i16);
+        root.insert("Height", self.height as i16);
+        root.insert("Length", self.length as i16);
+        root.insert("Offset", vec![self.offset.0, self.offset.1, self.offset.2]);
+        root.insert("PaletteMax", self.palette.len() as i32);
+        root.insert("Palette", palette);
+        root.insert("BlockData", varint_encode(&self.blocks));
+        root.insert("BlockEntities", block_entities);
+        write_nbt(writer, Some("Schematic"), &root, Flavor::GzCompressed)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Schematic> {
+        Schematic::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Block changes placing the schematic at `origin`, split into packet-sized batches. Blocks outside the world's height are left out.
+    pub fn changes(&self, world: &World, origin: (i32, i32, i32), rotation: Rotation, mirror: Mirror) -> Vec<BlockChanges> {
+        let palette = self.palette.iter().map(|data| transform_block(data, rotation, mirror)).collect::<Vec<BlockData>>();
+        let mut batches = vec![BlockChanges::new(&world.name)];
+        for (index, state) in self.blocks.iter().enumerate() {
+            let (x, y, z) = self.position(index);
+            let (dx, dy, dz) = transform_position((x as i32 + self.offset.0, y as i32 + self.offset.1, z as i32 + self.offset.2), rotation, mirror);
+            let (x, y, z) = (origin.0 + dx, origin.1 + dy, origin.2 + dz);
+            if y < world.min_height || y >= world.max_height {
+                continue;
+            }
+
+            if batches.last().unwrap().len() >= PASTE_BATCH {
+                batches.push(BlockChanges::new(&world.name));
+            }
+            batches.last_mut().unwrap().set(x, y, z, &palette[*state as usize]);
+        }
+        batches
+    }
+}
+
+impl API {
+    /// Places the schematic at `origin`, returning how many blocks changed
+    pub async fn paste_schematic(&mut self, world: &World, origin: (i32, i32, i32), schematic: &Schematic, rotation: Rotation, mirror: Mirror) -> anyhow::Result<u32> {
+        let mut changed = 0;
+        for changes in schematic.changes(world, origin, rotation, mirror) {
+            changed += self.set_blocks(changes, false).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Copies the blocks of a region, one [`COPY_SECTION`] cube per request. Block entity data is not copied.
+    pub async fn copy_schematic(&mut self, region: &Region) -> anyhow::Result<Schematic> {
+        let (width, height, length) = region.size();
+        let (Ok(width), Ok(height), Ok(length)) = (u16::try_from(width), u16::try_from(height), u16::try_from(length)) else {
+            bail!("Region is too large for a schematic: {:?}", region.size());
+        };
+        let volume = width as usize * height as usize * length as usize;
+        let mut blocks = vec![];
+        blocks.try_reserve_exact(volume).map_err(|_| anyhow!("Not enough memory for the {} blocks of {:?}", volume, region.size()))?;
+        blocks.resize(volume, 0);
+
+        // Servers may repeat palette entries and each section has its own palette, merge them so equal blocks share an index
+        let mut index = HashMap::new();
+        let mut merged = vec![];
+        let mut data_version = DEFAULT_DATA_VERSION;
+        for y in (0..height as u64).step_by(COPY_SECTION as usize) {
+            for z in (0..length as u64).step_by(COPY_SECTION as usize) {
+                for x in (0..width as u64).step_by(COPY_SECTION as usize) {
+                    let corner = |offset: u64, min: i32| (min as i64 + offset as i64) as i32;
+                    let min = (corner(x, region.min.0), corner(y, region.min.1), corner(z, region.min.2));
+                    let max = (corner((x + COPY_SECTION).min(width as u64) - 1, region.min.0), corner((y + COPY_SECTION).min(height as u64) - 1, region.min.1), corner((z + COPY_SECTION).min(length as u64) - 1, region.min.2));
+                    let section = Region { world: region.world.clone(), min, max };
+                    let (section_width, _, section_length) = section.size();
+
+                    let response: BukrsResGetBlocks = self.send_packet_await(BukrsReqGetBlocks { region: section.clone() }).await?;
+                    if response.blocks.len() as u64 != section.volume() {
+                        bail!("Expected {} blocks, received {}", section.volume(), response.blocks.len());
+                    }
+                    data_version = response.data_version;
+
+                    let remap = response.palette.into_iter().map(|data| *index.entry(data.clone()).or_insert_with(|| {
+                        merged.push(data);
+                        merged.len() as u32 - 1
+                    })).collect::<Vec<u32>>();
+                    for (offset, state) in response.blocks.into_iter().enumerate() {
+                        let offset = offset as u64;
+                        let (dx, dz, dy) = (offset % section_width, offset / section_width % section_length, offset / (section_width * section_length));
+                        let at = (x + dx) as usize + (z + dz) as usize * width as usize + (y + dy) as usize * width as usize * length as usize;
+                        blocks[at] = remap.get(state as usize).copied().ok_or_else(|| anyhow!("Block state {} is missing from the palette", state))?;
+                    }
+                }
+            }
+        }
+
+        Ok(Schematic { width, height, length, offset: (0, 0, 0), data_version, palette: merged, blocks, block_entities: vec![] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use quartz_nbt::{NbtCompound, io::{Flavor, read_nbt, write_nbt}};
+
+    use crate::{core::{block::Material, player::UUID, region::Region, world::{World, BlockData}}, net::{BukrsPacketData, BukrsReqSetBlocks, BukrsResSetBlocks, BukrsReqGetBlocks, BukrsResGetBlocks, cast_packet}, tests::{loopback, respond}};
+
+    use super::{Schematic, Rotation, Mirror, COPY_SECTION, transform_block, transform_position};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/schematics");
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let v2 = Schematic::load(format!("{}/tower_v2.schem", FIXTURES)).unwrap();
+        let v3 = Schematic::load(format!("{}/tower_v3.schem", FIXTURES)).unwrap();
+        assert_eq!(v2, v3);
+
+        assert_eq!((v2.width, v2.height, v2.length), (6, 5, 7));
+        assert_eq!(v2.offset, (-3, 0, -2));
+        assert_eq!(v2.palette.len(), 160);
+        assert_eq!(v2.get(0, 0, 0), &BlockData::new(Material::new("stone")));
+        assert_eq!(v2.get(0, 1, 0).get("facing"), Some("east"));
+        assert!(v2.get(5, 4, 6).material.is_air());
+        assert_eq!(v2.block_entities.len(), 1);
+        assert_eq!(v2.block_entities[0].pos, (2, 1, 3));
+        assert_eq!(v2.get(2, 1, 3).material, Material::new("chest"));
+        assert!(v2.block_entities[0].data.contains_key("Items"));
+
+        let mut buf = vec![];
+        v2.write(&mut buf).unwrap();
+        assert_eq!(Schematic::read(&mut buf.as_slice()).unwrap(), v2);
+    }
+
+    #[test]
+    fn test_bad_palette_index() {
+        let mut buf = vec![];
+        Schematic::new(1, 1, 1).write(&mut buf).unwrap();
+        let (mut root, _) = read_nbt(&mut buf.as_slice(), Flavor::GzCompressed).unwrap();
+        for index in [-1, 1, i32::MAX] {
+            let mut palette = NbtCompound::new();
+            palette.insert("minecraft:air", index);
+            root.insert("Palette", palette);
+            let mut buf = vec![];
+            write_nbt(&mut buf, Some("Schematic"), &root, Flavor::GzCompressed).unwrap();
+            assert!(Schematic::read(&mut buf.as_slice()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let stairs = BlockData::new(Material::new("oak_stairs")).with("facing", "north").with("shape", "inner_left");
+        let rotated = transform_block(&stairs, Rotation::Clockwise90, Mirror::None);
+        assert_eq!(rotated.get("facing"), Some("east"));
+        assert_eq!(rotated.get("shape"), Some("inner_left"));
+        let mirrored = transform_block(&stairs, Rotation::None, Mirror::FrontBack);
+        assert_eq!(mirrored.get("facing"), Some("south"));
+        assert_eq!(mirrored.get("shape"), Some("inner_right"));
+
+        let fence = BlockData::new(Material::new("oak_fence")).with("north", "true").with("west", "false");
+        let rotated = transform_block(&fence, Rotation::Clockwise180, Mirror::None);
+        assert_eq!((rotated.get("south"), rotated.get("east")), (Some("true"), Some("false")));
+
+        let sign = BlockData::new(Material::new("oak_sign")).with("rotation", "4");
+        assert_eq!(transform_block(&sign, Rotation::Clockwise90, Mirror::LeftRight).get("rotation"), Some("0"));
+
+        assert_eq!(transform_position((0, 0, -1), Rotation::Clockwise90, Mirror::None), (1, 0, 0));
+        assert_eq!(transform_position((1, 2, 3), Rotation::Clockwise270, Mirror::LeftRight), (3, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_paste() {
+        let (mut api, mut server) = loopback().await;
+        let world = World { name: "world".to_string(), uuid: UUID::from_u128(1), min_height: -64, max_height: 320 };
+        let mut schematic = Schematic::new(2, 1, 1);
+        schematic.set(1, 0, 0, &BlockData::new(Material::new("stone")));
+
+        let (changed, _) = tokio::join!(api.paste_schematic(&world, (10, 64, 10), &schematic, Rotation::Clockwise90, Mirror::None), async {
+            let request = server.next().await.unwrap().unwrap();
+            let BukrsReqSetBlocks { changes, physics } = cast_packet(&request.event).unwrap();
+            assert!(!physics);
+            let blocks = changes.iter().map(|(pos, data)| ((pos.x, pos.y, pos.z), data.material.clone())).collect::<Vec<_>>();
+            assert_eq!(blocks, vec![((10, 64, 10), Material::new("air")), ((10, 64, 11), Material::new("stone"))]);
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResSetBlocks { changed: 2 }) }).await.unwrap();
+        });
+        assert_eq!(changed.unwrap(), 2);
+    }
+    #[tokio::test]
+    async fn test_copy_in_sections() {
+        let (mut api, mut server) = loopback().await;
+        let width = COPY_SECTION as i32 + 6;
+        let region = Region { world: "world".to_string(), min: (-10, 64, 0), max: (width - 11, 65, 0) };
+        let (stone, dirt) = (BlockData::new(Material::new("stone")), BlockData::new(Material::new("dirt")));
+
+        let (schematic, _) = tokio::join!(api.copy_schematic(&region), async {
+            let first = respond::<BukrsReqGetBlocks>(&mut server, BukrsResGetBlocks { data_version: 3120, palette: vec![stone.clone()], blocks: vec![0; COPY_SECTION as usize * 2] }).await;
+            assert_eq!((first.region.min, first.region.max), ((-10, 64, 0), (COPY_SECTION as i32 - 11, 65, 0)));
+            let second = respond::<BukrsReqGetBlocks>(&mut server, BukrsResGetBlocks { data_version: 3120, palette: vec![dirt.clone(), stone.clone()], blocks: vec![0, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1] }).await;
+            assert_eq!((second.region.min, second.region.max), ((COPY_SECTION as i32 - 10, 64, 0), (width - 11, 65, 0)));
+        });
+        let schematic = schematic.unwrap();
+
+        assert_eq!((schematic.width, schematic.height, schematic.length), (width as u16, 2, 1));
+        assert_eq!(schematic.palette, vec![stone.clone(), dirt.clone()]);
+        assert_eq!(schematic.get(COPY_SECTION as u16 - 1, 0, 0), &stone);
+        assert_eq!(schematic.get(COPY_SECTION as u16, 0, 0), &dirt);
+        assert_eq!(schematic.get(COPY_SECTION as u16 + 1, 0, 0), &stone);
+        assert_eq!(schematic.get(COPY_SECTION as u16 + 5, 0, 0), &dirt);
+        assert_eq!(schematic.get(COPY_SECTION as u16, 1, 0), &stone);
+    }
+}
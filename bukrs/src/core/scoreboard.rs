@@ -0,0 +1,241 @@
+use anyhow::bail;
+use bukrs_derive::BukrsType;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqUpdateSidebar, BukrsResUpdateSidebar, BukrsReqRemoveSidebar, BukrsReqSetTeam, BukrsResSetTeam, BukrsReqTeamMembers, BukrsReqRemoveTeam, BukrsResScoreboardUpdate}};
+
+use super::player::PlayerId;
+
+/// Lines a sidebar can show
+pub const MAX_SIDEBAR_LINES: usize = 15;
+
+/// A changed sidebar line, counted from the top
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub struct SidebarLine {
+    pub index: u8,
+    pub text: String
+}
+
+/// The sidebar of one player. Text may use `§` colour codes.
+///
+/// Only lines that differ from what the player sees are sent, and the server swaps the text of a line in place,
+/// so updates don't flicker. Use one `Sidebar` per player, as each keeps its own copy of the lines.
+pub struct Sidebar {
+    pub player_id: PlayerId,
+    api: API,
+    title: String,
+    lines: Vec<String>,
+    shown: bool
+}
+
+impl Sidebar {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub async fn set_title(&mut self, title: &str) -> anyhow::Result<()> {
+        if self.shown && self.title == title {
+            return Ok(());
+        }
+        self.send(title.to_string(), self.lines.clone(), vec![]).await
+    }
+
+    /// Replaces a single line, `index` counted from the top. Lines up to `index` are added blank if missing.
+    pub async fn set_line(&mut self, index: usize, text: &str) -> anyhow::Result<()> {
+        let mut lines = self.lines.clone();
+        if lines.len() <= index {
+            lines.resize(index + 1, String::new());
+        }
+        lines[index] = text.to_string();
+        self.set_lines(lines).await
+    }
+
+    /// Replaces every line, sending only those that changed
+    pub async fn set_lines(&mut self, lines: Vec<String>) -> anyhow::Result<()> {
+        if lines.len() > MAX_SIDEBAR_LINES {
+            bail!("A sidebar shows at most {} lines, got {}", MAX_SIDEBAR_LINES, lines.len());
+        }
+
+        let changes = diff_lines(&self.lines, &lines);
+        if self.shown && changes.is_empty() && lines.len() == self.lines.len() {
+            return Ok(());
+        }
+        self.send(self.title.clone(), lines, changes).await
+    }
+
+    /// Keeps the old title and lines unless the player received the new ones, so the next diff stays correct
+    async fn send(&mut self, title: String, lines: Vec<String>, changes: Vec<SidebarLine>) -> anyhow::Result<()> {
+        let request = BukrsReqUpdateSidebar { player_id: self.player_id.clone(), title: title.clone(), line_count: lines.len() as u8, changes };
+        let BukrsResUpdateSidebar { found } = self.api.send_packet_await(request).await?;
+        if !found {
+            bail!("Player {} is offline", self.player_id.0);
+        }
+        self.title = title;
+        self.lines = lines;
+        self.shown = true;
+        Ok(())
+    }
+
+    /// Takes the sidebar away from the player
+    pub async fn hide(mut self) -> anyhow::Result<bool> {
+        let BukrsResScoreboardUpdate { found } = self.api.send_packet_await(BukrsReqRemoveSidebar { player_id: self.player_id.clone() }).await?;
+        Ok(found)
+    }
+}
+
+/// Lines of `new` that differ from `old`
+fn diff_lines(old: &[String], new: &[String]) -> Vec<SidebarLine> {
+    new.iter().enumerate()
+        .filter(|(index, text)| old.get(*index) != Some(text))
+        .map(|(index, text)| SidebarLine { index: index as u8, text: text.clone() })
+        .collect()
+}
+
+/// Colour of a team's names, in the order of the legacy `§0`-`§f` codes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub enum TeamColor {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+    /// No colour, the default
+    #[default]
+    Reset
+}
+
+/// Bukkit's `Team.OptionStatus`, used for collisions and name tags
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default, BukrsType)]
+pub enum OptionStatus {
+    #[default]
+    Always,
+    Never,
+    ForOtherTeams,
+    ForOwnTeam
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default, BukrsType)]
+pub struct TeamSettings {
+    pub prefix: String,
+    pub suffix: String,
+    pub color: TeamColor,
+    pub collision: OptionStatus,
+    pub name_tag_visibility: OptionStatus
+}
+
+/// A name-tag team, kept the same on every scoreboard the server shows, including private sidebars.
+/// Members are scoreboard entries: player names, or UUIDs for other entities.
+pub struct Team {
+    pub name: String,
+    api: API,
+    settings: TeamSettings
+}
+
+impl Team {
+    pub fn settings(&self) -> &TeamSettings {
+        &self.settings
+    }
+
+    pub async fn update(&mut self, settings: TeamSettings) -> anyhow::Result<()> {
+        let BukrsResSetTeam {  } = self.api.send_packet_await(BukrsReqSetTeam { name: self.name.clone(), settings: settings.clone() }).await?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    async fn members(&mut self, add: bool, members: &[&str]) -> anyhow::Result<()> {
+        let members = members.iter().map(|member| member.to_string()).collect();
+        let BukrsResScoreboardUpdate { found } = self.api.send_packet_await(BukrsReqTeamMembers { name: self.name.clone(), add, members }).await?;
+        if !found {
+            bail!("Team {} was removed", self.name);
+        }
+        Ok(())
+    }
+
+    /// Entries already in another team move to this one
+    pub async fn add_members(&mut self, members: &[&str]) -> anyhow::Result<()> {
+        self.members(true, members).await
+    }
+
+    pub async fn remove_members(&mut self, members: &[&str]) -> anyhow::Result<()> {
+        self.members(false, members).await
+    }
+
+    pub async fn remove(mut self) -> anyhow::Result<bool> {
+        let BukrsResScoreboardUpdate { found } = self.api.send_packet_await(BukrsReqRemoveTeam { name: self.name.clone() }).await?;
+        Ok(found)
+    }
+}
+
+impl API {
+    /// The sidebar of `player_id`. Nothing is shown until a title or line is set.
+    pub fn sidebar(&self, player_id: &PlayerId) -> Sidebar {
+        Sidebar { player_id: player_id.clone(), api: self.clone(), title: String::new(), lines: vec![], shown: false }
+    }
+
+    /// Creates the team, or updates it if one of that name exists
+    pub async fn team(&mut self, name: &str, settings: TeamSettings) -> anyhow::Result<Team> {
+        let BukrsResSetTeam {  } = self.send_packet_await(BukrsReqSetTeam { name: name.to_string(), settings: settings.clone() }).await?;
+        Ok(Team { name: name.to_string(), api: self.clone(), settings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{core::player::PlayerId, net::{BukrsReqUpdateSidebar, BukrsResUpdateSidebar}, tests::{loopback, respond}};
+
+    use super::SidebarLine;
+
+    #[tokio::test]
+    async fn test_sidebar_diff() {
+        let (api, mut server) = loopback().await;
+        let mut sidebar = api.sidebar(&PlayerId(1));
+        let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+
+        let (set, request) = tokio::join!(sidebar.set_lines(lines(&["Kills: 0", "", "Map: Forest"])), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: true }));
+        set.unwrap();
+        assert_eq!(request.line_count, 3);
+        assert_eq!(request.changes.len(), 3);
+
+        let (set, request) = tokio::join!(sidebar.set_lines(lines(&["Kills: 1", "", "Map: Forest", "Time: 3:00"])), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: true }));
+        set.unwrap();
+        assert_eq!(request.line_count, 4);
+        assert_eq!(request.changes, vec![SidebarLine { index: 0, text: "Kills: 1".to_string() }, SidebarLine { index: 3, text: "Time: 3:00".to_string() }]);
+
+        let (set, request) = tokio::join!(sidebar.set_lines(lines(&["Kills: 1", ""])), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: true }));
+        set.unwrap();
+        assert_eq!((request.line_count, request.changes.len()), (2, 0));
+
+        sidebar.set_line(1, "").await.unwrap();    // Unchanged, nothing is sent
+        assert!(sidebar.set_lines(vec![String::new(); 16]).await.is_err());
+
+        let (set, request) = tokio::join!(sidebar.set_title("§6Arena"), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: false }));
+        assert!(set.is_err());
+        assert_eq!((request.title.as_str(), request.changes.len()), ("§6Arena", 0));
+        assert_eq!(sidebar.title(), "");
+
+        let (set, request) = tokio::join!(sidebar.set_line(0, "Kills: 2"), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: false }));
+        assert!(set.is_err());
+        assert_eq!(request.changes.len(), 1);
+        assert_eq!(sidebar.lines(), lines(&["Kills: 1", ""]));
+
+        let (set, request) = tokio::join!(sidebar.set_line(0, "Kills: 2"), respond::<BukrsReqUpdateSidebar>(&mut server, BukrsResUpdateSidebar { found: true }));
+        set.unwrap();
+        assert_eq!(request.changes, vec![SidebarLine { index: 0, text: "Kills: 2".to_string() }]);
+    }
+}
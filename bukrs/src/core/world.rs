@@ -0,0 +1,338 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Display};
+
+use anyhow::anyhow;
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError, schema::{TypeSchema, FieldSchema}};
+use bukrs_derive::BukrsType;
+use bytes::BytesMut;
+use serde::{Serialize, Deserialize};
+
+use crate::{API, net::{BukrsReqWorlds, BukrsReqWorldByName, BukrsReqWorldByUuid, BukrsResWorlds, BukrsReqGetBlock, BukrsResGetBlock, BukrsReqSetBlock, BukrsResSetBlock, BukrsReqSetBlocks, BukrsResSetBlocks}};
+
+use super::{block::{BlockPos, Material}, player::UUID};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, BukrsType)]
+pub struct World {
+    pub name: String,
+    pub uuid: UUID,
+    pub min_height: i32,
+    pub max_height: i32
+}
+
+impl World {
+    pub fn block(&self, x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos::new(&self.name, x, y, z)
+    }
+}
+
+/// A precise position in a world, with the direction an entity looks in
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BukrsType)]
+pub struct Location {
+    pub world: String,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32
+}
+
+impl Location {
+    pub fn new(world: &str, x: f64, y: f64, z: f64) -> Location {
+        Location { world: world.to_string(), x, y, z, yaw: 0.0, pitch: 0.0 }
+    }
+
+    pub fn with_rotation(mut self, yaw: f32, pitch: f32) -> Location {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self
+    }
+
+    /// The block this location is in
+    pub fn block(&self) -> BlockPos {
+        BlockPos::new(&self.world, self.x.floor() as i32, self.y.floor() as i32, self.z.floor() as i32)
+    }
+
+    /// Distance to `other`, ignoring the world
+    pub fn distance(&self, other: &Location) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2)).sqrt()
+    }
+}
+
+impl From<&BlockPos> for Location {
+    /// The centre of the bottom face, where entities stand
+    fn from(pos: &BlockPos) -> Self {
+        Location::new(&pos.world, pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5)
+    }
+}
+
+/// Velocity in blocks per tick
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default, BukrsType)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+impl Vector {
+    pub fn new(x: f64, y: f64, z: f64) -> Vector {
+        Vector { x, y, z }
+    }
+}
+
+/// Material and block-state properties, sent as the state string, e.g. `minecraft:oak_stairs[facing=north,half=top]`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlockData {
+    pub material: Material,
+    pub properties: BTreeMap<String, String>
+}
+
+impl BlockData {
+    pub fn new(material: Material) -> BlockData {
+        BlockData { material, properties: BTreeMap::new() }
+    }
+
+    pub fn with(mut self, key: &str, value: &str) -> BlockData {
+        self.properties.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|value| value.as_str())
+    }
+
+    pub fn parse(state: &str) -> anyhow::Result<BlockData> {
+        let Some((material, properties)) = state.split_once('[') else {
+            return Ok(BlockData::new(Material::new(state)));
+        };
+        let properties = properties.strip_suffix(']').ok_or_else(|| anyhow!("Unclosed block state: {}", state))?;
+
+        let mut data = BlockData::new(Material::new(material));
+        for property in properties.split(',').filter(|property| !property.is_empty()) {
+            let (key, value) = property.split_once('=').ok_or_else(|| anyhow!("Invalid block state property: {}", property))?;
+            data.properties.insert(key.to_string(), value.to_string());
+        }
+        Ok(data)
+    }
+}
+
+impl Display for BlockData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.material.0)?;
+        if !self.properties.is_empty() {
+            let properties = self.properties.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<String>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl BukrsType for BlockData {
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        BlockData::parse(&String::decode(bytes)?).map_err(|error| DecodeError::Invalid(error.to_string()))
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.to_string().encode(bytes);
+    }
+
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::STRING
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::native(BukrsNativeType::STRING)
+    }
+}
+
+/// A single entry of [`BlockChanges`], `state` indexes into the palette
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BukrsType)]
+pub struct BlockChange {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub state: u32
+}
+
+/// Block changes in one world, palette-encoded so repeated states are only sent once.
+/// Only [`BlockChanges::set`] adds to it, which keeps the palette and its index in step.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(from = "PaletteChanges")]
+pub struct BlockChanges {
+    world: String,
+    palette: Vec<BlockData>,
+    blocks: Vec<BlockChange>,
+    #[serde(skip)]
+    index: HashMap<BlockData, u32>
+}
+
+/// [`BlockChanges`] as serialized, without the index
+#[derive(Deserialize)]
+struct PaletteChanges {
+    world: String,
+    palette: Vec<BlockData>,
+    blocks: Vec<BlockChange>
+}
+
+impl From<PaletteChanges> for BlockChanges {
+    fn from(PaletteChanges { world, palette, blocks }: PaletteChanges) -> BlockChanges {
+        let index = palette.iter().enumerate().map(|(state, data)| (data.clone(), state as u32)).collect();
+        BlockChanges { world, palette, blocks, index }
+    }
+}
+
+impl BlockChanges {
+    pub fn new(world: &str) -> BlockChanges {
+        BlockChanges { world: world.to_string(), ..Default::default() }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, z: i32, data: &BlockData) {
+        let state = match self.index.get(data) {
+            Some(state) => *state,
+            None => {
+                let state = self.palette.len() as u32;
+                self.palette.push(data.clone());
+                self.index.insert(data.clone(), state);
+                state
+            }
+        };
+        self.blocks.push(BlockChange { x, y, z, state });
+    }
+
+    pub fn world(&self) -> &str {
+        &self.world
+    }
+
+    /// Every distinct state, in the order they were first set
+    pub fn palette(&self) -> &[BlockData] {
+        &self.palette
+    }
+
+    /// The changes in the order they were set, their `state` indexing into [`BlockChanges::palette`]
+    pub fn blocks(&self) -> &[BlockChange] {
+        &self.blocks
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Changes whose state is missing from the palette are skipped
+    pub fn iter(&self) -> impl Iterator<Item = (BlockPos, &BlockData)> {
+        self.blocks.iter().filter_map(|change| Some((BlockPos::new(&self.world, change.x, change.y, change.z), self.palette.get(change.state as usize)?)))
+    }
+}
+
+impl BukrsType for BlockChanges {
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        Ok(PaletteChanges { world: String::decode(bytes)?, palette: Vec::decode(bytes)?, blocks: Vec::decode(bytes)? }.into())
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        self.world.encode(bytes);
+        self.palette.encode(bytes);
+        self.blocks.encode(bytes);
+    }
+
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::CUSTOM
+    }
+
+    /// The fields without the index, which is rebuilt from the palette
+    fn schema() -> TypeSchema {
+        let fields = vec![
+            FieldSchema { name: "world".to_string(), ty: String::schema() },
+            FieldSchema { name: "palette".to_string(), ty: Vec::<BlockData>::schema() },
+            FieldSchema { name: "blocks".to_string(), ty: Vec::<BlockChange>::schema() }
+        ];
+        TypeSchema::Struct { name: "BlockChanges".to_string(), fields }
+    }
+}
+
+impl API {
+    pub async fn worlds(&mut self) -> anyhow::Result<Vec<World>> {
+        let BukrsResWorlds { worlds } = self.send_packet_await(BukrsReqWorlds {  }).await?;
+        Ok(worlds)
+    }
+
+    pub async fn world(&mut self, name: &str) -> anyhow::Result<Option<World>> {
+        let BukrsResWorlds { worlds } = self.send_packet_await(BukrsReqWorldByName { name: name.to_string() }).await?;
+        Ok(worlds.into_iter().next())
+    }
+
+    pub async fn world_by_uuid(&mut self, uuid: UUID) -> anyhow::Result<Option<World>> {
+        let BukrsResWorlds { worlds } = self.send_packet_await(BukrsReqWorldByUuid { uuid }).await?;
+        Ok(worlds.into_iter().next())
+    }
+
+    pub async fn get_block(&mut self, pos: &BlockPos) -> anyhow::Result<BlockData> {
+        let BukrsResGetBlock { data } = self.send_packet_await(BukrsReqGetBlock { pos: pos.clone() }).await?;
+        Ok(data)
+    }
+
+    /// With `physics` off, neighbours are not updated (no falling sand, no water flow)
+    pub async fn set_block(&mut self, pos: &BlockPos, data: &BlockData, physics: bool) -> anyhow::Result<()> {
+        let BukrsResSetBlock {  } = self.send_packet_await(BukrsReqSetBlock { pos: pos.clone(), data: data.clone(), physics }).await?;
+        Ok(())
+    }
+
+    /// Applies all changes in a single packet, returning how many blocks actually changed
+    pub async fn set_blocks(&mut self, changes: BlockChanges, physics: bool) -> anyhow::Result<u32> {
+        let BukrsResSetBlocks { changed } = self.send_packet_await(BukrsReqSetBlocks { changes, physics }).await?;
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bukrs_core::BukrsType;
+    use bytes::BytesMut;
+
+    use crate::core::block::Material;
+
+    use super::{BlockData, BlockChange, BlockChanges};
+
+    #[test]
+    fn test_block_data() {
+        let data = BlockData::parse("minecraft:oak_stairs[half=top,facing=north]").unwrap();
+        assert_eq!(data.material, Material::new("oak_stairs"));
+        assert_eq!(data.get("facing"), Some("north"));
+        assert_eq!(data.to_string(), "minecraft:oak_stairs[facing=north,half=top]");
+        assert_eq!(BlockData::parse("stone").unwrap().to_string(), "minecraft:stone");
+        assert!(BlockData::parse("minecraft:oak_stairs[facing").is_err());
+    }
+
+    #[test]
+    fn test_block_changes_palette() {
+        let stone = BlockData::new(Material::new("stone"));
+        let mut changes = BlockChanges::new("world");
+        for x in 0..16 {
+            changes.set(x, 64, 0, &stone);
+        }
+        changes.set(0, 65, 0, &BlockData::new(Material::new("torch")));
+        assert_eq!(changes.palette.len(), 2);
+
+        let mut buf = BytesMut::new();
+        changes.encode(&mut buf);
+        let decoded = BlockChanges::decode(&mut buf).unwrap();
+        assert_eq!(decoded.len(), 17);
+        assert_eq!(decoded.iter().last().unwrap().1.material, Material::new("torch"));
+    }
+
+    #[test]
+    fn test_block_changes_after_deserializing() {
+        let stone = BlockData::new(Material::new("stone"));
+        let mut changes = BlockChanges::new("world");
+        changes.set(0, 64, 0, &stone);
+
+        let mut changes: BlockChanges = serde_json::from_str(&serde_json::to_string(&changes).unwrap()).unwrap();
+        changes.set(1, 64, 0, &stone);
+        assert_eq!(changes.palette.len(), 1);
+        assert_eq!(changes.blocks[1].state, 0);
+
+        changes.blocks.push(BlockChange { x: 2, y: 64, z: 0, state: 5 });
+        assert_eq!(changes.iter().count(), 2);
+    }
+}
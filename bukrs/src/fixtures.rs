@@ -0,0 +1,289 @@
+//! Golden wire format tests. One canonical instance of every packet is encoded with [`Codec`] and compared to
+//! `fixtures/packets.hex`, and every fixture is decoded back and compared to its instance.
+//!
+//! When a change to the wire format is intended, bump [`PROTOCOL_VERSION`] and rewrite the fixtures with
+//! `BUKRS_BLESS=1 cargo test -p bukrs fixtures`. Rewriting changed fixtures without a bump is refused.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{net::*, core::{
+    block::{BlockPos, Material, Hand, InteractAction, InteractTarget, BlockFace},
+    bossbar::{BossBarId, BossBarChange, BarColor, BarStyle, BarFlags},
+    combat::DamageCause,
+    command::{CommandSpec, CommandSender, ArgSpec, ArgKind},
+    effect::{EffectTarget, Sound, SoundCategory, Particle, ParticleData},
+    entity::{EntityId, EntityType, EntityData, EntityFlags, EntityFilter},
+    invfx::{InventorySize, InvList, InvfxId},
+    item::Item,
+    persistent::{DataHolder, NamespacedKey, PersistentKind, PersistentValue, PersistentContainer},
+    player::{PlayerId, PlayerData, UUID},
+    region::{Region, RegionOperation},
+    scoreboard::{SidebarLine, TeamSettings, TeamColor, OptionStatus},
+    world::{World, BlockData, BlockChanges, Location, Vector}
+}};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/packets.hex");
+
+/// Sent with every canonical packet, so the header is covered too
+const PAYLOAD_ID: u32 = 0x01020304;
+
+fn uuid() -> UUID {
+    UUID::from_u128(0x0123456789abcdef_fedcba9876543210)
+}
+
+fn location() -> Location {
+    Location::new("world", 1.5, -64.0, 0.25).with_rotation(90.0, -45.0)
+}
+
+fn block_data() -> BlockData {
+    BlockData::new(Material::new("minecraft:oak_stairs")).with("facing", "north").with("half", "top")
+}
+
+fn region() -> Region {
+    Region { world: "world".to_string(), min: (-1, 0, -1), max: (1, 2, 1) }
+}
+
+fn entity() -> EntityData {
+    EntityData { id: EntityId(42), entity_type: EntityType("minecraft:zombie".to_string()), uuid: uuid(), location: location(), custom_name: "Bob".to_string(), flags: EntityFlags(5) }
+}
+
+fn item() -> Item {
+    Item { material: Material::new("minecraft:diamond_sword"), amount: 1, data: vec![0xca, 0xfe] }
+}
+
+fn holder() -> DataHolder {
+    DataHolder::Chunk { world: "world".to_string(), x: -3, z: 7 }
+}
+
+fn key() -> NamespacedKey {
+    NamespacedKey::new("bukrs", "visits").unwrap()
+}
+
+/// One instance of every registered packet, filling in every field with something other than zero where possible
+fn canonical_packets() -> Vec<Box<dyn Packet>> {
+    let player = PlayerId(7);
+    let player_data = PlayerData { id: player.clone(), name: "Steve".to_string(), uuid: uuid() };
+    let mut changes = BlockChanges::new("world");
+    changes.set(1, 64, -1, &block_data());
+    changes.set(2, 64, -1, &BlockData::new(Material::new("minecraft:stone")));
+    let command = CommandSpec {
+        name: "warp".to_string(),
+        permission: "warps.use".to_string(),
+        usage: "/warp <name>".to_string(),
+        args: vec![ArgSpec { name: "name".to_string(), kind: ArgKind::String, optional: false }],
+        subcommands: vec![CommandSpec { name: "set".to_string(), permission: "warps.set".to_string(), usage: "/warp set <name>".to_string(), args: vec![ArgSpec { name: "target".to_string(), kind: ArgKind::Player, optional: true }], subcommands: vec![] }]
+    };
+    let team = TeamSettings { prefix: "[R] ".to_string(), suffix: "!".to_string(), color: TeamColor::Gold, collision: OptionStatus::Never, name_tag_visibility: OptionStatus::ForOwnTeam };
+    let container = PersistentContainer(BTreeMap::from([(NamespacedKey::new("bukrs", "nested").unwrap(), PersistentValue::LongArray(vec![-1, 1]))]));
+
+    vec![
+        Box::new(BukrsReqAPI { protocol_version: PROTOCOL_VERSION }),
+        Box::new(BukrsResAPI { api_id: 1024, protocol_version: PROTOCOL_VERSION }),
+        Box::new(BukrsReqOnlinePlayers {  }),
+        Box::new(BukrsResOnlinePlayers { players: vec![player.clone(), PlayerId(8)] }),
+        Box::new(BukrsReqPlayerById { player_id: player.clone() }),
+        Box::new(BukrsReqPlayerByName { player_name: "Steve".to_string() }),
+        Box::new(BukrsResPlayerData { data: player_data.clone() }),
+        Box::new(BukrsSDPlayerJoin { data: player_data }),
+        Box::new(BukrsSDPlayerQuit { player_id: player.clone() }),
+        Box::new(BukrsReqCreateInventory { name: "Shop".to_string(), size: InventorySize::Inv27 }),
+        Box::new(BukrsResCreateInventory { inv_id: InvfxId(3) }),
+        Box::new(BukrsSDInvClick { slot: 13, player_id: player.clone() }),
+        Box::new(BukrsSDInvOpen { player_id: player.clone() }),
+        Box::new(BukrsSDInvClose { player_id: player.clone() }),
+        Box::new(BukrsReqPlayerInvOpen { inv_id: InvfxId(3), player_id: player.clone() }),
+        Box::new(BukrsResPlayerInvOpen {  }),
+        Box::new(BukrsReqCreateInvList { inv_id: InvfxId(3), list: InvList { id: InvfxId(3), data: vec![] } }),
+        Box::new(BukrsResCreateInvList {  }),
+        Box::new(BukrsReqModifyInvList { inv_id: InvfxId(3), list: InvList { id: InvfxId(3), data: vec![] } }),
+        Box::new(BukrsResModifyInvList {  }),
+        Box::new(BukrsReqRegisterCommand { command }),
+        Box::new(BukrsResRegisterCommand {  }),
+        Box::new(BukrsSDCommand { sender: CommandSender::Player(player.clone()), label: "warp".to_string(), args: vec!["spawn".to_string()] }),
+        Box::new(BukrsReqSendMessage { target: CommandSender::Console, message: "§aHello".to_string() }),
+        Box::new(BukrsResSendMessage {  }),
+        Box::new(BukrsSDTabComplete { sender: CommandSender::Player(player.clone()), label: "warp".to_string(), args: vec!["sp".to_string()] }),
+        Box::new(BukrsResTabComplete { suggestions: vec!["spawn".to_string(), "spleef".to_string()] }),
+        Box::new(BukrsReqBatch { requests: PacketList(vec![Box::new(BukrsReqIsOp { player_id: player.clone() }), Box::new(BukrsReqWorlds {  })]) }),
+        Box::new(BukrsResBatch { responses: PacketList(vec![Box::new(BukrsResOp { found: true, op: false })]) }),
+        Box::new(BukrsResError { message: "No handler for BukrsReqWorlds".to_string() }),
+        Box::new(BukrsReqSubscribeEvent { event: "BukrsSDPlayerChat".to_string(), deadline_ms: 50, blocking: true }),
+        Box::new(BukrsResSubscribeEvent {  }),
+        Box::new(BukrsSDEventDropped { event: "PlayerMove".to_string() }),
+        Box::new(BukrsSDPlayerChat { player_id: player.clone(), message: "hi".to_string(), format: "<%1$s> %2$s".to_string(), recipients: vec![player.clone()] }),
+        Box::new(BukrsResPlayerChat { cancelled: false, message: "hi!".to_string(), format: "%1$s: %2$s".to_string(), recipients: vec![] }),
+        Box::new(BukrsSDBlockBreak { player_id: player.clone(), block: BlockPos::new("world", 1, 64, -1), material: Material::new("minecraft:stone"), drop_items: true }),
+        Box::new(BukrsResBlockBreak { cancelled: false, drop_items: true }),
+        Box::new(BukrsSDBlockPlace { player_id: player.clone(), block: BlockPos::new("world", 1, 65, -1), material: Material::new("minecraft:torch"), against: BlockPos::new("world", 1, 64, -1), hand: Hand::OffHand }),
+        Box::new(BukrsResBlockPlace { cancelled: true }),
+        Box::new(BukrsSDPlayerInteract { player_id: player.clone(), action: InteractAction::RightClickBlock, target: InteractTarget::Block { pos: BlockPos::new("world", 1, 64, -1), material: Material::new("minecraft:lever"), face: BlockFace::Up }, hand: Hand::MainHand, item: Material::new("minecraft:air") }),
+        Box::new(BukrsResPlayerInteract { cancelled: false }),
+        Box::new(BukrsReqWorlds {  }),
+        Box::new(BukrsReqWorldByName { name: "world_nether".to_string() }),
+        Box::new(BukrsReqWorldByUuid { uuid: uuid() }),
+        Box::new(BukrsResWorlds { worlds: vec![World { name: "world".to_string(), uuid: uuid(), min_height: -64, max_height: 320 }] }),
+        Box::new(BukrsReqGetBlock { pos: BlockPos::new("world", 1, 64, -1) }),
+        Box::new(BukrsResGetBlock { data: block_data() }),
+        Box::new(BukrsReqSetBlock { pos: BlockPos::new("world", 1, 64, -1), data: block_data(), physics: true }),
+        Box::new(BukrsResSetBlock {  }),
+        Box::new(BukrsReqSetBlocks { changes, physics: false }),
+        Box::new(BukrsResSetBlocks { changed: 2 }),
+        Box::new(BukrsReqGetBlocks { region: region() }),
+        Box::new(BukrsResGetBlocks { data_version: 3120, palette: vec![block_data()], blocks: vec![0, 0, 1] }),
+        Box::new(BukrsReqSpawnEntity { entity_type: EntityType("minecraft:zombie".to_string()), location: location() }),
+        Box::new(BukrsReqNearbyEntities { location: location(), radius: 16.0, filter: EntityFilter { types: vec![EntityType("minecraft:zombie".to_string())], include_players: true, limit: 5 } }),
+        Box::new(BukrsReqEntityById { entity_id: EntityId(42) }),
+        Box::new(BukrsResEntities { entities: vec![entity()] }),
+        Box::new(BukrsReqTeleportEntity { entity_id: EntityId(42), location: location() }),
+        Box::new(BukrsReqSetVelocity { entity_id: EntityId(42), velocity: Vector::new(0.5, 1.0, -0.5) }),
+        Box::new(BukrsReqRemoveEntity { entity_id: EntityId(42) }),
+        Box::new(BukrsReqSetCustomName { entity_id: EntityId(42), name: "Bob".to_string() }),
+        Box::new(BukrsReqSetEntityFlags { entity_id: EntityId(42), flags: EntityFlags(3) }),
+        Box::new(BukrsReqChangeEntityFlags { entity_id: EntityId(42), set: EntityFlags(1), clear: EntityFlags(4) }),
+        Box::new(BukrsResEntityUpdate { found: true }),
+        Box::new(BukrsResRemoveEntity { found: true, removed: false }),
+        Box::new(BukrsReqSpawnTypes { types: vec![EntityType("minecraft:zombie".to_string())] }),
+        Box::new(BukrsResSpawnTypes {  }),
+        Box::new(BukrsSDEntitySpawn { entity: entity() }),
+        Box::new(BukrsResEntitySpawn { cancelled: false }),
+        Box::new(BukrsSDEntityDeath { entity: entity(), dropped_exp: 5 }),
+        Box::new(BukrsResEntityDeath { cancelled: false, dropped_exp: 10 }),
+        Box::new(BukrsSDEntityDamage { victim: EntityId(42), attacker: Some(EntityId(7)), cause: DamageCause::Fall, raw_damage: 4.5, final_damage: 2.25 }),
+        Box::new(BukrsResEntityDamage { cancelled: false, final_damage: 1.0 }),
+        Box::new(BukrsSDPlayerDeath { player_id: player.clone(), drops: vec![item()], dropped_exp: 7, death_message: "Steve fell".to_string(), keep_inventory: false }),
+        Box::new(BukrsResPlayerDeath { cancelled: false, drops: vec![], dropped_exp: 0, death_message: "".to_string(), keep_inventory: true }),
+        Box::new(BukrsReqDefineRegion { name: "spawn".to_string(), region: region() }),
+        Box::new(BukrsResDefineRegion {  }),
+        Box::new(BukrsReqRemoveRegion { name: "spawn".to_string() }),
+        Box::new(BukrsResRemoveRegion { found: true }),
+        Box::new(BukrsReqMoveInterval { interval_ms: 250 }),
+        Box::new(BukrsResMoveInterval {  }),
+        Box::new(BukrsSDPlayerMove { player_id: player.clone(), from: location(), to: Location::new("world", 2.0, -64.0, 0.25) }),
+        Box::new(BukrsResPlayerMove { cancelled: true }),
+        Box::new(BukrsSDRegionEnter { player_id: player.clone(), region: "spawn".to_string(), from: location(), to: Location::new("world", 0.0, 1.0, 0.0) }),
+        Box::new(BukrsResRegionEnter { cancelled: false }),
+        Box::new(BukrsSDRegionLeave { player_id: player.clone(), region: "spawn".to_string(), from: Location::new("world", 0.0, 1.0, 0.0), to: location() }),
+        Box::new(BukrsResRegionLeave { cancelled: false }),
+        Box::new(BukrsReqUpdateSidebar { player_id: player.clone(), title: "§6Stats".to_string(), line_count: 2, changes: vec![SidebarLine { index: 0, text: "Kills: 3".to_string() }, SidebarLine { index: 1, text: "".to_string() }] }),
+        Box::new(BukrsResUpdateSidebar { found: true }),
+        Box::new(BukrsReqRemoveSidebar { player_id: player.clone() }),
+        Box::new(BukrsReqSetTeam { name: "red".to_string(), settings: team }),
+        Box::new(BukrsResSetTeam {  }),
+        Box::new(BukrsReqTeamMembers { name: "red".to_string(), add: true, members: vec!["Steve".to_string(), "Alex".to_string()] }),
+        Box::new(BukrsReqRemoveTeam { name: "red".to_string() }),
+        Box::new(BukrsResScoreboardUpdate { found: true }),
+        Box::new(BukrsReqGetData { holder: holder(), key: key(), kind: PersistentKind::Int }),
+        Box::new(BukrsResGetData { found: true, value: Some(PersistentValue::Int(-2)) }),
+        Box::new(BukrsReqSetData { holder: DataHolder::Item(item()), key: key(), value: PersistentValue::Container(container) }),
+        Box::new(BukrsReqRemoveData { holder: DataHolder::Entity(EntityId(42)), key: key() }),
+        Box::new(BukrsResDataUpdate { found: true, existed: true, holder: holder() }),
+        Box::new(BukrsReqDataKeys { holder: holder() }),
+        Box::new(BukrsResDataKeys { found: true, keys: vec![key()] }),
+        Box::new(BukrsReqHasPermission { player_id: player.clone(), permission: "warps.use".to_string() }),
+        Box::new(BukrsResHasPermission { found: true, value: true }),
+        Box::new(BukrsReqHasPermissions { player_id: player.clone(), permissions: vec!["warps.use".to_string(), "warps.set".to_string()] }),
+        Box::new(BukrsResHasPermissions { found: true, values: vec![true, false] }),
+        Box::new(BukrsReqAddAttachment { attachment_id: 99, player_id: player.clone() }),
+        Box::new(BukrsReqSetAttachmentPermission { attachment_id: 99, permission: "warps.set".to_string(), value: true }),
+        Box::new(BukrsReqUnsetAttachmentPermission { attachment_id: 99, permission: "warps.set".to_string() }),
+        Box::new(BukrsReqRemoveAttachment { attachment_id: 99 }),
+        Box::new(BukrsResAttachment { found: true }),
+        Box::new(BukrsReqIsOp { player_id: player.clone() }),
+        Box::new(BukrsReqSetOp { player_id: player.clone(), op: true }),
+        Box::new(BukrsResOp { found: true, op: true }),
+        Box::new(BukrsSDPermissionChange { player_id: player.clone() }),
+        Box::new(BukrsReqPlaySound { target: EffectTarget::Player(player.clone()), sound: Sound::BlockAnvilLand, category: SoundCategory::Blocks, volume: 1.0, pitch: 0.5, location: Some(location()) }),
+        Box::new(BukrsReqSpawnParticle { target: EffectTarget::Players(vec![player.clone(), PlayerId(8)]), particle: Particle::Flame, location: location(), count: 10, offset: Vector::new(0.5, 0.5, 0.5), speed: 0.125, data: ParticleData::Dust { color: 0xff0000, size: 1.5 } }),
+        Box::new(BukrsReqSendTitle { target: EffectTarget::World("world".to_string()), title: "Welcome".to_string(), subtitle: "to bukrs".to_string(), fade_in: 10, stay: 70, fade_out: 20 }),
+        Box::new(BukrsResEffect { players: 2 }),
+        Box::new(BukrsReqCreateBossBar { bar_id: BossBarId(5), title: "Boss".to_string(), color: BarColor::Red, style: BarStyle::Segmented10 }),
+        Box::new(BukrsReqUpdateBossBar { bar_id: BossBarId(5), changes: vec![BossBarChange::Progress(0.5), BossBarChange::Flags(BarFlags(1)), BossBarChange::AddPlayers(vec![player.clone()])] }),
+        Box::new(BukrsReqRemoveBossBar { bar_id: BossBarId(5) }),
+        Box::new(BukrsResBossBar { found: true }),
+        Box::new(BukrsReqSubscribeTicks {  }),
+        Box::new(BukrsResSubscribeTicks { tick: 1200 }),
+        Box::new(BukrsSDTick { tick: 1201 }),
+        Box::new(BukrsReqStartJob { job_id: 11, operation: RegionOperation::Replace { region: region(), from: BlockData::new(Material::new("minecraft:stone")), to: block_data() }, blocks_per_tick: 4096, physics: false }),
+        Box::new(BukrsResStartJob { total: 27 }),
+        Box::new(BukrsSDJobProgress { job_id: 11, done: 9, total: 27 }),
+        Box::new(BukrsSDJobDone { job_id: 11, changed: 20, cancelled: false }),
+        Box::new(BukrsReqCancelJob { job_id: 11 }),
+        Box::new(BukrsResCancelJob {  }),
+    ]
+}
+
+fn encode(packet: Box<dyn Packet>) -> BytesMut {
+    let mut frame = BytesMut::new();
+    Codec.encode(BukrsPacketData { payload_id: Some(PAYLOAD_ID), event: packet }, &mut frame).unwrap();
+    frame
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> BytesMut {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("Invalid hex in fixture")).collect::<Vec<u8>>().as_slice().into()
+}
+
+/// The protocol version the fixtures were written with, and each packet's frame as hex
+fn read_fixtures() -> (u32, BTreeMap<String, String>) {
+    let source = std::fs::read_to_string(FIXTURES).unwrap_or_default();
+    let mut lines = source.lines();
+    let version = lines.next().and_then(|line| line.strip_prefix("# protocol ")).and_then(|version| version.parse().ok()).unwrap_or(0);
+    let frames = lines.filter_map(|line| line.split_once(' ')).map(|(name, hex)| (name.to_string(), hex.to_string())).collect();
+    (version, frames)
+}
+
+fn write_fixtures(frames: &BTreeMap<String, String>) {
+    let mut source = format!("# protocol {}\n", PROTOCOL_VERSION);
+    for (name, hex) in frames {
+        source += &format!("{} {}\n", name, hex);
+    }
+    std::fs::write(FIXTURES, source).unwrap();
+}
+
+#[test]
+fn test_every_packet_is_canonical() {
+    let canonical = canonical_packets().iter().map(|packet| packet.id()).collect::<Vec<String>>();
+    let unique = canonical.iter().cloned().collect::<BTreeSet<String>>();
+    assert_eq!(unique.len(), canonical.len(), "A packet is listed twice");
+
+    let registered = CONSTRUCTORS.lock().unwrap().keys().filter(|name| !name.starts_with("BukrsTest")).cloned().collect::<BTreeSet<String>>();
+    assert_eq!(unique, registered, "Every registered packet needs a canonical instance");
+}
+
+#[test]
+fn test_golden_encoding() {
+    let encoded = canonical_packets().into_iter().map(|packet| (packet.id(), to_hex(&encode(packet)))).collect::<BTreeMap<String, String>>();
+    let (version, fixtures) = read_fixtures();
+
+    if std::env::var("BUKRS_BLESS").is_ok() {
+        let changed = encoded.iter().filter(|(name, hex)| fixtures.get(*name).is_some_and(|fixture| fixture != *hex)).map(|(name, _)| name.as_str()).collect::<Vec<&str>>();
+        assert!(changed.is_empty() || version != PROTOCOL_VERSION, "The wire format of {:?} changed, bump PROTOCOL_VERSION before rewriting the fixtures", changed);
+        write_fixtures(&encoded);
+        return;
+    }
+
+    assert_eq!(version, PROTOCOL_VERSION, "Fixtures were written for another protocol version, rewrite them with BUKRS_BLESS=1");
+    for (name, hex) in encoded.iter() {
+        let fixture = fixtures.get(name).unwrap_or_else(|| panic!("No fixture for {}, add it with BUKRS_BLESS=1", name));
+        assert_eq!(hex, fixture, "The wire format of {} changed, bump PROTOCOL_VERSION and rewrite the fixtures with BUKRS_BLESS=1", name);
+    }
+}
+
+#[test]
+fn test_golden_decoding() {
+    let (_, fixtures) = read_fixtures();
+    for packet in canonical_packets() {
+        let Some(fixture) = fixtures.get(&packet.id()) else { continue };  // Reported by test_golden_encoding
+        let mut frame = from_hex(fixture);
+        let decoded = Codec.decode(&mut frame).unwrap().unwrap_or_else(|| panic!("Fixture of {} is cut short", packet.id()));
+        assert!(frame.is_empty(), "Fixture of {} has bytes left over", packet.id());
+        assert_eq!(decoded.payload_id, Some(PAYLOAD_ID));
+        assert_eq!(serde_json::to_value(&decoded.event).unwrap(), serde_json::to_value(&packet).unwrap(), "Fixture of {} decodes differently", packet.id());
+    }
+}
@@ -1,69 +1,161 @@
-mod net;
-mod varint;
-mod core;
-mod api;
+pub mod net;
+pub mod core;
+pub mod api;
+pub mod schema;
 mod macros;
+#[cfg(test)]
+mod fixtures;
+bukrs_core::__if_proptest! {
+    mod arbitrary;
+}
 
-use std::{net::SocketAddr, sync::{Arc, Mutex}, collections::HashMap};
-use futures::{StreamExt, stream::{SplitSink, SplitStream}, SinkExt};
-use net::{Codec, BukrsPacketData, BukrsFuture, Packet, BukrsReqAPI, cast_packet, BukrsResAPI};
+use std::{net::SocketAddr, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, collections::HashMap};
+use futures::{StreamExt, SinkExt};
+use anyhow::{anyhow, bail};
+use net::{Codec, LenientCodec, BukrsPacketData, BukrsFuture, Packet, BukrsReqAPI, cast_packet, BukrsResAPI, BukrsResError, BukrsSDCommand, BukrsSDTabComplete, PROTOCOL_VERSION};
+use crate::core::{command::{self, Command}, player::{self, PlayerId, PlayerData}, event::{self, Events}, region::{self, JobState}, scheduler::{self, SchedulerState}, permission::{self, PermissionState}};
 use rand::Rng;
-use tokio::net::TcpStream;
-use tokio_util::codec::{Decoder, Framed};
+use tokio::{net::{TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::broadcast};
+use tokio_util::codec::{FramedRead, FramedWrite};
 
-type DefaultTx = SplitSink<Framed<TcpStream, Codec>, BukrsPacketData>;
-type DefaultRx = SplitStream<Framed<TcpStream, Codec>>;
+type DefaultTx = FramedWrite<OwnedWriteHalf, Codec>;
+type DefaultRx = FramedRead<OwnedReadHalf, LenientCodec>;
 type BukrsListener = fn (Box<dyn Packet>) -> ();
 type ArcMutex<T> = Arc<Mutex<T>>;
 
+/// Something the connection recovered from on its own, reported through [`API::warnings`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// A frame from the server couldn't be decoded and was skipped, with the reason
+    DroppedPacket(String),
+    /// The server stopped sending the named event after a missed deadline, and its handlers were removed
+    EventDropped(String),
+}
+
+#[derive(Clone)]
 pub struct API {
-    pub(crate) tx: DefaultTx,
+    pub(crate) tx: Arc<tokio::sync::Mutex<DefaultTx>>,
     pub(crate) listeners: Arc<Mutex<Vec<BukrsListener>>>,
     pub(crate) payload_listeners: Arc<Mutex<HashMap<u32, Arc<BukrsFuture>>>>,
+    pub(crate) commands: ArcMutex<HashMap<String, Arc<Command>>>,
+    pub(crate) players: ArcMutex<HashMap<PlayerId, PlayerData>>,
+    pub(crate) events: ArcMutex<Events>,
+    pub(crate) jobs: ArcMutex<HashMap<u32, Arc<JobState>>>,
+    pub(crate) scheduler: Arc<SchedulerState>,
+    pub(crate) permissions: Arc<PermissionState>,
+    pub(crate) warnings: broadcast::Sender<Warning>,
+    pub(crate) closed: Arc<AtomicBool>,
 }
 
 async fn send_packet_tx(tx: &mut DefaultTx, event: impl Packet, payload_id: Option<u32>) -> anyhow::Result<()> {
-    tx.send(BukrsPacketData { payload_id, event: Box::new(event) }).await.unwrap();
-    tx.flush().await.unwrap();
+    tx.send(BukrsPacketData { payload_id, event: Box::new(event) }).await?;
+    tx.flush().await?;
     Ok(())
 }
 
+/// Unregisters the payload listener once the response arrived, sending failed or the caller stopped waiting
+struct PendingResponse {
+    payload_id: u32,
+    listeners: ArcMutex<HashMap<u32, Arc<BukrsFuture>>>,
+}
+
+impl Drop for PendingResponse {
+    fn drop(&mut self) {
+        self.listeners.lock().unwrap().remove(&self.payload_id);
+    }
+}
+
 impl API {
-    async fn init_listener(map: ArcMutex<HashMap<u32, Arc<BukrsFuture>>>, listeners: ArcMutex<Vec<BukrsListener>>, mut rx: DefaultRx) {
-        while let Some(Ok(msg)) = rx.next().await {
+    async fn init_listener(api: API, mut rx: DefaultRx) {
+        while let Some(Ok(frame)) = rx.next().await {
+            let msg = match frame {
+                Ok(msg) => msg,
+                Err(error) => {
+                    api.warn(Warning::DroppedPacket(format!("{:#}", error)));
+                    continue;
+                }
+            };
+
             if let Some(payload_id) = msg.payload_id {
-                if let Some(future) = map.lock().unwrap().get(&payload_id) {
+                if let Some(future) = api.payload_listeners.lock().unwrap().get(&payload_id) {
                     let future = future.clone();
                     future.set_data(msg.event.clone_box());
                     future.wake();
                 }
             }
+
+            player::update_cache(&api, &msg.event);
+            permission::update_cache(&api, &msg.event);
+            region::update_jobs(&api, &msg.event);
+            scheduler::tick(&api, &msg.event);
+
+            if let Some(invocation) = cast_packet::<BukrsSDCommand>(&msg.event) {
+                command::dispatch(&api, invocation);
+            }
+
+            if let Some(request) = cast_packet::<BukrsSDTabComplete>(&msg.event) {
+                command::complete(&api, msg.payload_id, request);
+            }
+
+            event::dispatch_any(&api, msg.payload_id, &msg.event);
     
-            for listener in listeners.lock().unwrap().iter() {
+            for listener in api.listeners.lock().unwrap().iter() {
                 listener(msg.event.clone_box());
             }
         }
+
+        api.closed.store(true, Ordering::SeqCst);   // Set before draining, so requests registered later see it
+        for (_, future) in api.payload_listeners.lock().unwrap().drain() {
+            future.close();
+        }
+    }
+
+    fn from_stream(stream: TcpStream) -> API {
+        let (read, write) = stream.into_split();
+        let (tx, rx) = (FramedWrite::new(write, Codec), FramedRead::new(read, LenientCodec));
+        let api = API { tx: Arc::new(tokio::sync::Mutex::new(tx)), listeners: arc_mutex!(vec![]), payload_listeners: arc_mutex!(HashMap::new()), commands: arc_mutex!(HashMap::new()), players: arc_mutex!(HashMap::new()), events: arc_mutex!(HashMap::new()), jobs: arc_mutex!(HashMap::new()), scheduler: Arc::new(SchedulerState::new()), permissions: Arc::new(PermissionState::new()), warnings: broadcast::channel(64).0, closed: Arc::new(AtomicBool::new(false)) };  // Initiate api
+        tokio::spawn(Self::init_listener(api.clone(), rx));   // Initiate listeners
+        api
     }
 
     /// Request for API
     pub async fn request(server: SocketAddr) -> anyhow::Result<API> {
-        let codec = Codec;
         let client = TcpStream::connect(server).await?;
-        let (tx, rx) = codec.framed(client).split();
-        let mut api = API { tx, listeners: arc_mutex!(vec![]), payload_listeners: arc_mutex!(HashMap::new()) };  // Initiate api
-        tokio::spawn(Self::init_listener(api.payload_listeners.clone(), api.listeners.clone(), rx));   // Initiate listeners
-        let _response = api.send_packet_await::<BukrsResAPI>(BukrsReqAPI {  }).await?;
+        Self::handshake(Self::from_stream(client)).await
+    }
+
+    async fn handshake(mut api: API) -> anyhow::Result<API> {
+        let BukrsResAPI { protocol_version, .. } = api.send_packet_await(BukrsReqAPI { protocol_version: PROTOCOL_VERSION }).await?;
+        if protocol_version != PROTOCOL_VERSION {
+            bail!("Server speaks protocol version {}, but this client speaks {}", protocol_version, PROTOCOL_VERSION);
+        }
 
         Ok(api)
     }
 
     pub async fn send_packet_await<T: Packet + Clone>(&mut self, packet: impl Packet) -> anyhow::Result<T> {
-        let payload_id = rand::thread_rng().gen_range(1..u32::MAX);  // TODO a better way for this // maybe uuid?
-        self.send_packet(packet, Some(payload_id)).await?;  // send packet with payload id
+        let payload_id = rand::thread_rng().gen_range(1..=i32::MAX as u32);  // Positive as an i32, the server numbers batched requests below zero
         let future = Arc::new(BukrsFuture::new(payload_id, self.payload_listeners.clone()));
-        self.payload_listeners.lock().unwrap().insert(payload_id, future.clone());  // Add future to payload handlers
-        let response_packet = future.as_ref().await;
-        Ok(cast_packet::<T>(&response_packet).unwrap())
+        self.payload_listeners.lock().unwrap().insert(payload_id, future.clone());  // Add future to payload handlers before the response can arrive
+        let _pending = PendingResponse { payload_id, listeners: self.payload_listeners.clone() };
+        if self.closed.load(Ordering::SeqCst) {
+            bail!("The connection to the server is closed");
+        }
+        self.send_packet(packet, Some(payload_id)).await?;  // send packet with payload id
+        let response_packet = future.as_ref().await.ok_or_else(|| anyhow!("The connection to the server closed before the response arrived"))?;
+        if let Some(BukrsResError { message }) = cast_packet(&response_packet) {
+            bail!("The server couldn't handle the request: {}", message);
+        }
+        cast_packet::<T>(&response_packet).ok_or_else(|| anyhow!("Unexpected response: {}", response_packet))
+    }
+
+    /// Receives every [`Warning`] from now on
+    pub fn warnings(&self) -> broadcast::Receiver<Warning> {
+        self.warnings.subscribe()
+    }
+
+    pub(crate) fn warn(&self, warning: Warning) {
+        let _ = self.warnings.send(warning);    // Fails only when nobody listens
     }
 
     pub fn add_listener(&self, listener: fn(Box<dyn Packet>) -> ()) {    // For ServerData
@@ -71,7 +163,7 @@ impl API {
     }
 
     pub async fn send_packet(&mut self, packet: impl Packet, payload_id: Option<u32>) -> anyhow::Result<()> {
-        send_packet_tx(&mut self.tx, packet, payload_id).await?;    // Sends packet to server
+        send_packet_tx(&mut *self.tx.lock().await, packet, payload_id).await?;    // Sends packet to server
         Ok(())
     }
 }
@@ -80,27 +172,105 @@ impl API {
 mod tests {
     use std::net::SocketAddr;
 
-    use futures::StreamExt;
-    use tokio::net::TcpListener;
-    use tokio_util::codec::Decoder;
+    use bukrs_core::varint::write_varint;
+    use bytes::{BufMut, BytesMut};
+    use futures::{SinkExt, StreamExt};
+    use tokio::{io::AsyncWriteExt, net::{TcpListener, TcpStream}};
+    use tokio_util::codec::{Decoder, Framed, FramedRead, FramedWrite};
+
+    use crate::{Warning, net::{Codec, Packet, BukrsReqCreateInventory, BukrsResCreateInventory, BukrsResOnlinePlayers, BukrsReqOnlinePlayers, BukrsReqPlayerInvOpen, BukrsResPlayerInvOpen, BukrsReqAPI, BukrsResAPI, BukrsResError, BukrsReqSubscribeEvent, BukrsResSubscribeEvent, BukrsPacketData, PROTOCOL_VERSION, MAX_FRAME_LEN, cast_packet, tests::raw_frame}, API, send_packet_tx, core::{player::PlayerId, invfx::{InventorySize, InvfxId}}};
+
+    /// API connected to an in-process server, without the BukrsReqAPI handshake
+    pub(crate) async fn loopback() -> (API, Framed<TcpStream, Codec>) {
+        let server = TcpListener::bind::<SocketAddr>("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let client = TcpStream::connect(server.local_addr().unwrap()).await.unwrap();
+        let (socket, _) = server.accept().await.unwrap();
+        (API::from_stream(client), Codec.framed(socket))
+    }
+
+    /// Answers the next request with `response` and returns the request
+    pub(crate) async fn respond<T: Packet + Clone>(server: &mut Framed<TcpStream, Codec>, response: impl Packet) -> T {
+        let request = server.next().await.unwrap().unwrap();
+        server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(response) }).await.unwrap();
+        cast_packet(&request.event).unwrap()
+    }
+
+    /// Accepts the next event subscription and returns its request
+    pub(crate) async fn accept_subscription(server: &mut Framed<TcpStream, Codec>) -> BukrsReqSubscribeEvent {
+        respond(server, BukrsResSubscribeEvent {  }).await
+    }
+
+    #[tokio::test]
+    async fn test_protocol_mismatch() {
+        let (api, mut server) = loopback().await;
+        let (handshake, _) = tokio::join!(API::handshake(api), async {
+            let request = server.next().await.unwrap().unwrap();
+            let BukrsReqAPI { protocol_version } = cast_packet(&request.event).unwrap();
+            assert_eq!(protocol_version, PROTOCOL_VERSION);
+            server.send(BukrsPacketData { payload_id: request.payload_id, event: Box::new(BukrsResAPI { api_id: 1024, protocol_version: 1 }) }).await.unwrap();
+        });
+        assert!(handshake.is_err());
+    }
 
-    use crate::{net::{Codec, BukrsReqCreateInventory, BukrsResCreateInventory, BukrsResOnlinePlayers, BukrsReqOnlinePlayers, BukrsReqPlayerInvOpen, BukrsResPlayerInvOpen, BukrsReqAPI, BukrsResAPI, cast_packet}, API, send_packet_tx, core::{player::PlayerId, invfx::{InventorySize, InvfxId}}};
+    #[tokio::test]
+    async fn test_error_responses() {
+        let (mut api, mut server) = loopback().await;
+        let (response, _) = tokio::join!(api.send_packet_await::<BukrsResOnlinePlayers>(BukrsReqOnlinePlayers {  }), async {
+            respond::<BukrsReqOnlinePlayers>(&mut server, BukrsResError { message: "No world named nether".to_string() }).await;
+        });
+        assert!(response.unwrap_err().to_string().contains("No world named nether"));
+    }
+
+    #[tokio::test]
+    async fn test_listener_survives_bad_frames() {
+        let (api, mut server) = loopback().await;
+        let mut warnings = api.warnings();
+        let (handshake, _) = tokio::join!(API::handshake(api), async {
+            let request = server.next().await.unwrap().unwrap();
+            let payload_id = request.payload_id.unwrap();
+            server.get_mut().write_all(&raw_frame(payload_id, "BukrsResFromANewerServer", &[1, 2, 3])).await.unwrap();
+            server.get_mut().write_all(&raw_frame(payload_id, "BukrsResAPI", &[0])).await.unwrap();
+            server.send(BukrsPacketData { payload_id: Some(payload_id), event: Box::new(BukrsResAPI { api_id: 1024, protocol_version: PROTOCOL_VERSION }) }).await.unwrap();
+        });
+        assert!(handshake.is_ok());
+        for _ in 0..2 {
+            assert!(matches!(warnings.recv().await.unwrap(), Warning::DroppedPacket(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_fails_requests() {
+        let (mut api, mut server) = loopback().await;
+        let (response, _) = tokio::join!(api.send_packet_await::<BukrsResOnlinePlayers>(BukrsReqOnlinePlayers {  }), async {
+            let request = server.next().await.unwrap().unwrap();
+            let mut header = BytesMut::new();
+            write_varint(MAX_FRAME_LEN as i32 + 1, &mut header);
+            header.put_u32(request.payload_id.unwrap());
+            server.get_mut().write_all(&header).await.unwrap();
+        });
+        assert!(response.unwrap_err().to_string().contains("closed"));
+        assert!(api.payload_listeners.lock().unwrap().is_empty());
+
+        let response = api.send_packet_await::<BukrsResOnlinePlayers>(BukrsReqOnlinePlayers {  }).await;
+        assert!(response.unwrap_err().to_string().contains("closed"));
+    }
 
     #[tokio::test]
+    #[ignore = "manual: runs forever, pair with `client` in another process"]
     async fn server() -> anyhow::Result<()> {
         println!("Server Initialized");
         let server = TcpListener::bind::<SocketAddr>("127.0.0.1:25565".parse()?).await?;
         loop {
             let (socket, _) = server.accept().await?;
             tokio::spawn(async move {
-                let codec = Codec;
-                let (mut tx, mut rx) = codec.framed(socket).split();
+                let (read, write) = socket.into_split();
+                let (mut tx, mut rx) = (FramedWrite::new(write, Codec), FramedRead::new(read, Codec));
                 loop {
                     if let Some(Ok(msg)) = rx.next().await {
                         // println!("Income: {:?}", msg.event);
                         if let Some(BukrsReqAPI { .. }) = cast_packet(&msg.event) {
                             println!("BukrsReq");
-                            send_packet_tx(&mut tx, BukrsResAPI { api_id: 1024 }, msg.payload_id).await.unwrap();
+                            send_packet_tx(&mut tx, BukrsResAPI { api_id: 1024, protocol_version: PROTOCOL_VERSION }, msg.payload_id).await.unwrap();
                         }
                         if let Some(BukrsReqOnlinePlayers { .. }) = cast_packet(&msg.event) {
                             println!("ReqOnlinePlayers");
@@ -122,6 +292,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "manual: needs `server` running"]
     async fn client() -> anyhow::Result<()> {
         println!("Client Initialized");
         let mut api = API::request("127.0.0.1:25565".parse()?).await?;
@@ -6,28 +6,36 @@ macro_rules! arc_mutex {
 }
 
 /// https://github.com/feather-rs/feather/blob/main/feather/protocol/src/packets.rs
+///
+/// Declares packets as `Name { field Type; ... }`. Fields take any type implementing `BukrsType`,
+/// and both packets and fields take attributes and doc comments, such as `#[bukrs(varint)]` on a field.
+/// A packet's name is its id on the wire, so it has to be unique across every module declaring packets.
 #[macro_export]
 macro_rules! register_packet {
     (
-        $(  
+        $(
+            $(#[$packet_meta:meta])*
             $packet:ident {
                 $(
-                    $field:ident $typ:ident $(<$generics:ident>)?
+                    $(#[$field_meta:meta])*
+                    $field:ident $typ:ty
                 );* $(;)?
             } $(,)?
         )*
     ) => {
         $(
+            $(#[$packet_meta])*
             #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bukrs_derive::BukrsPacket)]
             pub struct $packet {
                 $(
-                    pub $field: $typ $(::<$generics>)?,
+                    $(#[$field_meta])*
+                    pub $field: $typ,
                 )*
             }
 
             #[typetag::serde]
-            impl crate::net::Packet for $packet {
-                fn clone_box(&self) -> Box<dyn crate::net::Packet> {
+            impl $crate::net::Packet for $packet {
+                fn clone_box(&self) -> Box<dyn $crate::net::Packet> {
                     Box::new(self.clone())
                 }
 
@@ -42,13 +50,26 @@ macro_rules! register_packet {
                 }
             }
 
-            #[ctor::ctor]
-            #[allow(non_snake_case)]
-            fn $packet() {
-                crate::net::CONSTRUCTORS.lock().unwrap().insert(stringify!($packet).to_string(), |buf: &mut bytes::BytesMut| {
-                    Box::new($packet::decode(buf))
-                });
-            }
+            // Scoped so the registration function can't clash with other items of the module
+            const _: () = {
+                #[ctor::ctor]
+                fn register() {
+                    $crate::net::CONSTRUCTORS.lock().unwrap().insert(stringify!($packet).to_string(), |buf: &mut bytes::BytesMut| {
+                        <$packet as bukrs_core::BukrsDecodable>::decode(buf).map(|packet| Box::new(packet) as Box<dyn $crate::net::Packet>)
+                    });
+                    $crate::net::SCHEMAS.lock().unwrap().insert(stringify!($packet).to_string(), <$packet as bukrs_core::BukrsPacket>::schema);
+                }
+
+                bukrs_core::__if_proptest! {
+                    #[ctor::ctor]
+                    fn register_strategy() {
+                        $crate::net::STRATEGIES.lock().unwrap().insert(stringify!($packet).to_string(), || {
+                            use bukrs_core::proptest::strategy::Strategy;
+                            bukrs_core::proptest::arbitrary::any::<$packet>().prop_map(|packet| Box::new(packet) as Box<dyn $crate::net::Packet>).boxed()
+                        });
+                    }
+                }
+            };
         )*
     };
-}
\ No newline at end of file
+}
@@ -1,16 +1,31 @@
 use std::{task::{Poll, Waker}, sync::{Arc, Mutex}, pin::Pin, collections::HashMap, fmt::{Debug, Display}};
 
+use anyhow::{anyhow, bail};
 use bytes::{Buf, BytesMut, BufMut};
 use futures::Future;
 use serde::{Serialize, Deserialize};
 use tokio_util::codec::{Encoder, Decoder};
-use bukrs_core::BukrsType;
-use bukrs_core::{BukrsPacket,BukrsDecodable};
+use bukrs_core::{BukrsType, BukrsNativeType, DecodeError};
+use bukrs_core::{BukrsPacket, varint::{self, VarIntError}, schema::{PacketSchema, TypeSchema}};
 
-use crate::{varint, core::{invfx::{InventorySize, InvList, InvfxId}, player::{PlayerId, PlayerData}}, register_packet, arc_mutex};
+use crate::{core::{invfx::{InventorySize, InvList, InvfxId}, player::{PlayerId, PlayerData, UUID}, world::{World, BlockData, BlockChanges, Location, Vector}, entity::{EntityId, EntityType, EntityData, EntityFlags, EntityFilter}, combat::DamageCause, item::Item, command::{CommandSpec, CommandSender}, block::{BlockPos, Material, Hand, InteractAction, InteractTarget}, region::{Region, RegionOperation}, scoreboard::{SidebarLine, TeamSettings}, bossbar::{BossBarId, BossBarChange, BarColor, BarStyle}, effect::{EffectTarget, Sound, SoundCategory, Particle, ParticleData}, persistent::{DataHolder, NamespacedKey, PersistentKind, PersistentValue}}, register_packet, arc_mutex};
 
-#[ctor::ctor]
-pub static CONSTRUCTORS: Arc<Mutex<HashMap<String, fn(buf: &mut BytesMut) -> Box<dyn Packet>>>> = arc_mutex!(HashMap::new());
+pub type PacketConstructor = fn(buf: &mut BytesMut) -> Result<Box<dyn Packet>, DecodeError>;
+pub type SchemaConstructor = fn() -> PacketSchema;
+
+lazy_static::lazy_static! {
+    pub static ref CONSTRUCTORS: Arc<Mutex<HashMap<String, PacketConstructor>>> = arc_mutex!(HashMap::new());
+    pub static ref SCHEMAS: Arc<Mutex<HashMap<String, SchemaConstructor>>> = arc_mutex!(HashMap::new());
+}
+
+bukrs_core::__if_proptest! {
+    /// Any value of one packet, boxed like the packets the codec returns
+    pub type StrategyConstructor = fn() -> bukrs_core::proptest::strategy::BoxedStrategy<Box<dyn Packet>>;
+
+    lazy_static::lazy_static! {
+        pub static ref STRATEGIES: Arc<Mutex<HashMap<String, StrategyConstructor>>> = arc_mutex!(HashMap::new());
+    }
+}
 
 #[typetag::serde(tag = "type")]
 pub trait Packet: Send + Sync + std::any::Any + Debug + Display + BukrsPacket {
@@ -18,16 +33,61 @@ pub trait Packet: Send + Sync + std::any::Any + Debug + Display + BukrsPacket {
     fn get_any(&self) -> Box<dyn std::any::Any>;
 }
 
+#[allow(clippy::borrowed_box)]
 pub fn cast_packet<T: Packet + Clone>(packet: &Box<dyn Packet>) -> Option<T> {
-    packet.get_any().downcast_ref::<T>().map(|p| p.clone())
+    packet.get_any().downcast_ref::<T>().cloned()
+}
+
+/// Packets carried inside another packet, each written as its name and fields like the body of a frame
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PacketList(pub Vec<Box<dyn Packet>>);
+
+impl Clone for PacketList {
+    fn clone(&self) -> Self {
+        PacketList(self.0.iter().map(|packet| packet.clone_box()).collect())
+    }
+}
+
+impl BukrsType for PacketList {
+    fn ty(&self) -> BukrsNativeType {
+        BukrsNativeType::CUSTOM
+    }
+
+    fn schema() -> TypeSchema {
+        TypeSchema::Vector { item: Box::new(TypeSchema::Packet) }
+    }
+
+    fn encode(&self, bytes: &mut BytesMut) {
+        varint::write_length(self.0.len(), bytes);
+        for packet in self.0.iter() {
+            packet.id().encode(bytes);
+            BukrsPacket::encode(packet.as_ref(), bytes);
+        }
+    }
+
+    fn decode(bytes: &mut BytesMut) -> Result<Self, DecodeError> {
+        let size = varint::read_length(bytes)?;
+        let mut packets = vec![];
+        for _ in 0..size {
+            let name = String::decode(bytes)?;
+            let constructor = CONSTRUCTORS.lock().unwrap().get(&name).copied();  // Released before decoding, the packet may be a batch itself
+            let constructor = constructor.ok_or_else(|| DecodeError::Invalid(format!("Unknown packet {}", name)))?;
+            packets.push(constructor(bytes)?);
+        }
+        Ok(PacketList(packets))
+    }
 }
 
+/// Version of the wire format, compared in the BukrsReqAPI handshake
+pub const PROTOCOL_VERSION: u32 = 2;
+
 register_packet! {
     BukrsReqAPI {
-
+        protocol_version u32
     }
     BukrsResAPI { 
-        api_id u32
+        api_id u32;
+        protocol_version u32    // The server's version. On a mismatch the server closes the connection after answering.
     }
 }
 
@@ -50,6 +110,8 @@ register_packet! {
     BukrsResPlayerData { 
         data PlayerData
     }
+    BukrsSDPlayerJoin { data PlayerData }   // Also sent for every online player right after BukrsResAPI
+    BukrsSDPlayerQuit { player_id PlayerId }
 }
 
 register_packet! {
@@ -66,12 +128,175 @@ register_packet! {
     BukrsResModifyInvList {  }
 }
 
+register_packet! {
+    BukrsReqRegisterCommand { command CommandSpec }
+    BukrsResRegisterCommand {  }
+    BukrsSDCommand { sender CommandSender; label String; args Vec<String> }    // Sent on every invocation of a registered command
+    BukrsReqSendMessage { target CommandSender; message String }
+    BukrsResSendMessage {  }
+    BukrsSDTabComplete { sender CommandSender; label String; args Vec<String> }   // Carries a payload id, answered with BukrsResTabComplete
+    BukrsResTabComplete { suggestions Vec<String> }
+}
+
+register_packet! {
+    BukrsReqBatch { requests PacketList }   // The server handles every request within one tick, under negative payload ids
+    BukrsResBatch { responses PacketList }  // In the order of the requests
+    BukrsResError { message String }    // Stands in for the response of a request the server couldn't handle
+}
+
+register_packet! {
+    BukrsReqSubscribeEvent { event String; deadline_ms u32; blocking bool }   // Blocking, the server holds the event back for at most `deadline_ms` awaiting the verdict. Otherwise it is sent without a payload id once decided.
+    BukrsResSubscribeEvent {  }
+    BukrsSDEventDropped { event String }   // The server stopped sending the event after the client missed a deadline
+}
+
+register_packet! {
+    BukrsSDPlayerChat { player_id PlayerId; message String; format String; recipients Vec<PlayerId> }    // Carries a payload id, answered with BukrsResPlayerChat
+    BukrsResPlayerChat { cancelled bool; message String; format String; recipients Vec<PlayerId> }
+}
+
+register_packet! {
+    BukrsSDBlockBreak { player_id PlayerId; block BlockPos; material Material; drop_items bool }
+    BukrsResBlockBreak { cancelled bool; drop_items bool }
+    BukrsSDBlockPlace { player_id PlayerId; block BlockPos; material Material; against BlockPos; hand Hand }
+    BukrsResBlockPlace { cancelled bool }
+    BukrsSDPlayerInteract { player_id PlayerId; action InteractAction; target InteractTarget; hand Hand; item Material }
+    BukrsResPlayerInteract { cancelled bool }
+}
+
+register_packet! {
+    BukrsReqWorlds {  }
+    BukrsReqWorldByName { name String }
+    BukrsReqWorldByUuid { uuid UUID }
+    BukrsResWorlds { worlds Vec<World> }    // Empty if a lookup found nothing
+    BukrsReqGetBlock { pos BlockPos }
+    BukrsResGetBlock { data BlockData }
+    BukrsReqSetBlock { pos BlockPos; data BlockData; physics bool }
+    BukrsResSetBlock {  }
+    BukrsReqSetBlocks { changes BlockChanges; physics bool }
+    BukrsResSetBlocks { changed u32 }
+    BukrsReqGetBlocks { region Region }     // At most 64³ blocks, see COPY_SECTION
+    BukrsResGetBlocks { data_version i32; palette Vec<BlockData>; blocks Vec<u32> }  // Ordered x first, then z, then y, like Sponge schematics
+}
+
+register_packet! {
+    BukrsReqSpawnEntity { entity_type EntityType; location Location }
+    BukrsReqNearbyEntities { location Location; radius f64; filter EntityFilter }
+    BukrsReqEntityById { entity_id EntityId }
+    BukrsResEntities { entities Vec<EntityData> }   // Empty if nothing was spawned or found
+    BukrsReqTeleportEntity { entity_id EntityId; location Location }
+    BukrsReqSetVelocity { entity_id EntityId; velocity Vector }
+    BukrsReqRemoveEntity { entity_id EntityId }
+    BukrsReqSetCustomName { entity_id EntityId; name String }
+    BukrsReqSetEntityFlags { entity_id EntityId; flags EntityFlags }
+    BukrsReqChangeEntityFlags { entity_id EntityId; set EntityFlags; clear EntityFlags }   // Applied to the current flags in one step
+    BukrsResEntityUpdate { found bool }
+    BukrsResRemoveEntity { found bool; removed bool }   // Players are found but not removed
+    BukrsReqSpawnTypes { types Vec<EntityType> }   // Only these types fire BukrsSDEntitySpawn, every type if empty
+    BukrsResSpawnTypes {  }
+    BukrsSDEntitySpawn { entity EntityData }
+    BukrsResEntitySpawn { cancelled bool }
+    BukrsSDEntityDeath { entity EntityData; dropped_exp u32 }
+    BukrsResEntityDeath { cancelled bool; dropped_exp u32 }
+}
+
+register_packet! {
+    BukrsSDEntityDamage { victim EntityId; attacker Option<EntityId>; cause DamageCause; raw_damage f64; final_damage f64 }   // `attacker` is None for environmental damage
+    BukrsResEntityDamage { cancelled bool; final_damage f64 }
+    BukrsSDPlayerDeath { player_id PlayerId; drops Vec<Item>; dropped_exp u32; death_message String; keep_inventory bool }    // An empty message is hidden
+    BukrsResPlayerDeath { cancelled bool; drops Vec<Item>; dropped_exp u32; death_message String; keep_inventory bool }
+}
+
+register_packet! {
+    BukrsReqDefineRegion { name String; region Region }  // Replaces a region of the same name defined by the same client
+    BukrsResDefineRegion {  }
+    BukrsReqRemoveRegion { name String }
+    BukrsResRemoveRegion { found bool }
+    BukrsReqMoveInterval { interval_ms u32 }    // Minimum time between two BukrsSDPlayerMove of one player
+    BukrsResMoveInterval {  }
+    BukrsSDPlayerMove { player_id PlayerId; from Location; to Location }  // Only sent when the block changes
+    BukrsResPlayerMove { cancelled bool }
+    BukrsSDRegionEnter { player_id PlayerId; region String; from Location; to Location }
+    BukrsResRegionEnter { cancelled bool }
+    BukrsSDRegionLeave { player_id PlayerId; region String; from Location; to Location }
+    BukrsResRegionLeave { cancelled bool }
+}
+
+register_packet! {
+    BukrsReqUpdateSidebar { player_id PlayerId; title String; line_count u8; changes Vec<SidebarLine> }  // Shows the sidebar if it isn't already
+    BukrsResUpdateSidebar { found bool }
+    BukrsReqRemoveSidebar { player_id PlayerId }
+    BukrsReqSetTeam { name String; settings TeamSettings }  // Creates the team if it doesn't exist
+    BukrsResSetTeam {  }
+    BukrsReqTeamMembers { name String; add bool; members Vec<String> }    // Removes the members if `add` is false
+    BukrsReqRemoveTeam { name String }
+    BukrsResScoreboardUpdate { found bool }
+}
+
+register_packet! {
+    BukrsReqGetData { holder DataHolder; key NamespacedKey; kind PersistentKind }
+    BukrsResGetData { found bool; value Option<PersistentValue> }  // None if no value of that kind is stored
+    BukrsReqSetData { holder DataHolder; key NamespacedKey; value PersistentValue }
+    BukrsReqRemoveData { holder DataHolder; key NamespacedKey }
+    BukrsResDataUpdate { found bool; existed bool; holder DataHolder }  // Items come back with the change applied
+    BukrsReqDataKeys { holder DataHolder }
+    BukrsResDataKeys { found bool; keys Vec<NamespacedKey> }
+}
+
+register_packet! {
+    BukrsReqHasPermission { player_id PlayerId; permission String }
+    BukrsResHasPermission { found bool; value bool }
+    BukrsReqHasPermissions { player_id PlayerId; permissions Vec<String> }
+    BukrsResHasPermissions { found bool; values Vec<bool> }    // In the order of the request
+    BukrsReqAddAttachment { attachment_id u32; player_id PlayerId }  // The client picks the id
+    BukrsReqSetAttachmentPermission { attachment_id u32; permission String; value bool }
+    BukrsReqUnsetAttachmentPermission { attachment_id u32; permission String }
+    BukrsReqRemoveAttachment { attachment_id u32 }
+    BukrsResAttachment { found bool }
+    BukrsReqIsOp { player_id PlayerId }
+    BukrsReqSetOp { player_id PlayerId; op bool }
+    BukrsResOp { found bool; op bool }
+    BukrsSDPermissionChange { player_id PlayerId }  // Sent before the response to the change that caused it
+}
+
+register_packet! {
+    BukrsReqPlaySound { target EffectTarget; sound Sound; category SoundCategory; volume f32; pitch f32; location Option<Location> }    // None plays the sound at each player
+    BukrsReqSpawnParticle { target EffectTarget; particle Particle; location Location; count u32; offset Vector; speed f64; data ParticleData }
+    BukrsReqSendTitle { target EffectTarget; title String; subtitle String; fade_in u32; stay u32; fade_out u32 }
+    BukrsResEffect { players u32 }  // Players the effect was sent to
+}
+
+register_packet! {
+    BukrsReqCreateBossBar { bar_id BossBarId; title String; color BarColor; style BarStyle }  // The client picks the id
+    BukrsReqUpdateBossBar { bar_id BossBarId; changes Vec<BossBarChange> }
+    BukrsReqRemoveBossBar { bar_id BossBarId }
+    BukrsResBossBar { found bool }
+}
+
+register_packet! {
+    BukrsReqSubscribeTicks {  }
+    BukrsResSubscribeTicks { tick u64 }
+    BukrsSDTick { tick u64 }   // Sent at the start of every server tick once subscribed
+}
+
+register_packet! {
+    BukrsReqStartJob { job_id u32; operation RegionOperation; blocks_per_tick u32; physics bool }    // The client picks the id so reports can't outrun the response, and may not reuse the id of a running job
+    BukrsResStartJob { total u64 }
+    BukrsSDJobProgress { job_id u32; done u64; total u64 }
+    BukrsSDJobDone { job_id u32; changed u64; cancelled bool }
+    BukrsReqCancelJob { job_id u32 }
+    BukrsResCancelJob {  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServerResponse(pub u32);
 
 #[derive(Serialize, Deserialize)]
 pub struct BukrsPacketData { pub payload_id: Option::<u32>, pub event: Box::<dyn Packet> }
 
+/// Largest payload either side of `Codec` accepts. Block reads take 4 bytes per block, so regions of about 16 million blocks fit.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
 pub struct Codec;
 
 impl Encoder<BukrsPacketData> for Codec {
@@ -82,7 +307,10 @@ impl Encoder<BukrsPacketData> for Codec {
         event.event.id().encode(&mut payload);
         event.event.encode(&mut payload);
 
-        let payload_id = if event.payload_id.is_none() { 0 } else { event.payload_id.unwrap() };
+        if payload.len() > MAX_FRAME_LEN {
+            bail!("{} is {} bytes, more than the {} a frame may hold", event.event.id(), payload.len(), MAX_FRAME_LEN);
+        }
+        let payload_id = event.payload_id.unwrap_or(0);
         encode_header(dst, &payload, payload_id)?;
         dst.put(payload);
         Ok(())
@@ -95,30 +323,46 @@ impl Decoder for Codec {
 
 
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let mut read_buffer = src.clone();
-        if read_buffer.remaining() < 6 {
+        let mut header = BytesMut::from(&src[..src.len().min(HEADER_MAX_LEN)]);
+        let Some((remaining_length, payload_id)) = decode_header(&mut header)? else {
           return Ok(None);
+        };
+        if remaining_length > MAX_FRAME_LEN {
+            bail!("Frame of {} bytes is larger than {}", remaining_length, MAX_FRAME_LEN);     // Not skipped, as that means buffering all of it
         }
-
-        let (remaining_length, payload_id) = decode_header(&mut read_buffer)?;
-        if read_buffer.remaining() < remaining_length {
+        let header_length = src.len().min(HEADER_MAX_LEN) - header.len();
+        if src.len() - header_length < remaining_length {
           return Ok(None);
         }
-        src.advance(src.remaining() - read_buffer.remaining()); // Increment header
-
-        let packet_name = String::decode(&mut read_buffer);
-
-        let map = CONSTRUCTORS.lock().unwrap();
-
-        let func = map.get(&packet_name).unwrap();
+        src.advance(header_length);
+        let mut frame = src.split_to(remaining_length);     // Consumed even if it can't be decoded, so the next frame still can
 
         let payload_id = if payload_id == 0 { None } else { Some(payload_id) };
 
-        let packet = BukrsPacketData { payload_id, event: func(&mut read_buffer) };
+        let packet_name = String::decode(&mut frame).map_err(|error| anyhow!("Malformed frame of {} bytes: {}", remaining_length, error))?;
+        let func = CONSTRUCTORS.lock().unwrap().get(&packet_name).copied();   // Released before decoding, batches decode nested packets
+        let Some(func) = func else {
+            bail!("Unknown packet {}", packet_name);
+        };
+        let event = func(&mut frame).map_err(|error| anyhow!("Malformed {} of {} bytes: {}", packet_name, remaining_length, error))?;
+        Ok(Some(BukrsPacketData { payload_id, event }))
+    }
+}
 
-        src.advance(remaining_length);
+/// `Codec` for the listener. `Framed` stops reading buffered frames after a decoder error, so frames `Codec` skipped
+/// are handed on as errors instead. Errors that lose track of where frames start still end the stream.
+pub struct LenientCodec;
+
+impl Decoder for LenientCodec {
+    type Error = anyhow::Error;
+    type Item = anyhow::Result<BukrsPacketData>;
 
-        Ok(Some(packet))
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let buffered = src.len();
+        match Codec.decode(src) {
+            Err(error) if src.len() < buffered => Ok(Some(Err(error))),
+            result => result.map(|packet| packet.map(Ok))
+        }
     }
 }
 
@@ -129,51 +373,34 @@ fn encode_header(dst: &mut BytesMut, packet_data: &BytesMut, payload_id: u32) ->
     Ok(())
 }
 
-fn decode_header(src: &mut BytesMut) -> anyhow::Result<(usize, u32)> {
-    let packet_size = usize::try_from(varint::read_varint(src)?)?; // packet size excluding header
-    let payload_id = src.get_u32(); 
-    Ok((packet_size, payload_id))
-}
-
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-
-    use bytes::BytesMut;
-    use tokio_util::codec::{Decoder, Encoder};
-
-    use crate::{net::BukrsPacketData, arc_mutex, core::invfx::{InvfxId, InvList}};
-
-    use super::{Codec, BukrsFuture, BukrsReqCreateInvList};
-
-    #[test]
-    fn codec_test() {
-        let mut buf = BytesMut::with_capacity(1024);
-
-        let mut codec = Codec;
-        codec.encode(BukrsPacketData { payload_id: Some(1024), event: Box::new(BukrsReqCreateInvList { inv_id: InvfxId(1024), list: InvList { id: InvfxId(1024), data: vec![] } }) }, &mut buf).unwrap();
-        if let Some(item) = codec.decode(&mut buf).unwrap() {
-            println!("{}", item.event);
-        }
-    }
-
-    #[tokio::test]
-    async fn futures_test() {
-        let future = BukrsFuture::new(1024, arc_mutex!(HashMap::new()));
-        (&future).await;
+/// The payload length as a varint, then the payload id
+const HEADER_MAX_LEN: usize = 5 + 4;
+
+/// `None` until the whole header has arrived
+fn decode_header(src: &mut BytesMut) -> anyhow::Result<Option<(usize, u32)>> {
+    let packet_size = match varint::read_varint(src) {
+        Ok(size) => usize::try_from(size)?, // packet size excluding header
+        Err(VarIntError::Truncated) => return Ok(None),
+        Err(error) => return Err(error.into())
+    };
+    if src.remaining() < 4 {
+        return Ok(None);
     }
+    let payload_id = src.get_u32();
+    Ok(Some((packet_size, payload_id)))
 }
 
 pub struct BukrsFuture {
     pub(crate) data: Mutex<Option<Box<dyn Packet>>>,
     pub(crate) waker: Mutex<Option<Waker>>,
+    pub(crate) closed: Mutex<bool>,
     pub(crate) payload_id: u32,
     pub(crate) payload_handler: Arc<Mutex<HashMap<u32, Arc<BukrsFuture>>>>,
 }
 
 impl BukrsFuture {
     pub fn new(payload_id: u32, payload_handler: Arc<Mutex<HashMap<u32, Arc<BukrsFuture>>>>) -> BukrsFuture {
-        BukrsFuture { data: Mutex::new(None), waker: Mutex::new(None), payload_id, payload_handler }
+        BukrsFuture { data: Mutex::new(None), waker: Mutex::new(None), closed: Mutex::new(false), payload_id, payload_handler }
     }
 
     pub fn set_waker(&self, waker: Waker) {
@@ -186,23 +413,205 @@ impl BukrsFuture {
         *locked = Some(data);
     }
 
+    /// Resolves the future to `None`, as no response can arrive anymore
+    pub fn close(&self) {
+        *self.closed.lock().unwrap() = true;
+        self.wake();
+    }
+
     pub fn wake(&self) {
-        self.waker.lock().unwrap().as_ref().unwrap().wake_by_ref();
+        if let Some(waker) = self.waker.lock().unwrap().as_ref() {   // Not polled yet: the data is picked up on first poll
+            waker.wake_by_ref();
+        }
     }
 }
 
 impl Future for &BukrsFuture {
-    type Output = Box<dyn Packet>;
+    type Output = Option<Box<dyn Packet>>;   // `None` if the connection closed first
 
     fn poll(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
         let fut = Pin::into_inner(self);
         if let Some(event) = fut.data.lock().unwrap().as_ref() {
             fut.payload_handler.lock().unwrap().remove(&fut.payload_id);
-            Poll::Ready(event.clone_box())
+            Poll::Ready(Some(event.clone_box()))
+        } else if *fut.closed.lock().unwrap() {
+            Poll::Ready(None)
         } else {
             let waker = cx.waker().clone();
             fut.set_waker(waker);
             Poll::Pending
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::{net::{BukrsPacketData, cast_packet}, arc_mutex, register_packet, core::{invfx::{InvfxId, InvList}, player::PlayerId}};
+
+    use super::{Codec, LenientCodec, BukrsFuture, BukrsReqCreateInvList, BukrsResCreateInvList, MAX_FRAME_LEN};
+
+    use bukrs_core::{BukrsType, BukrsNativeType, schema::TypeSchema, proptest::{proptest, strategy::Strategy}};
+    use bukrs_derive::BukrsType;
+
+    /// Written as a single byte
+    mod as_u8 {
+        use bukrs_core::{BukrsType, DecodeError};
+        use bytes::BytesMut;
+
+        pub fn encode(value: &u32, bytes: &mut BytesMut) {
+            (*value as u8).encode(bytes);
+        }
+
+        pub fn decode(bytes: &mut BytesMut) -> Result<u32, DecodeError> {
+            Ok(u8::decode(bytes)? as u32)
+        }
+    }
+
+    fn small_values() -> impl Strategy<Value = u32> {
+        0..=u8::MAX as u32
+    }
+
+    #[derive(BukrsType, Debug, PartialEq)]
+    struct Sample<T> {
+        #[bukrs(varint)]
+        count: u32,
+        #[bukrs(with = "as_u8", strategy = "small_values")]
+        small: u32,
+        #[bukrs(skip)]
+        cached: Option<String>,
+        shapes: Vec<Shape>,
+        inner: T
+    }
+
+    #[derive(BukrsType, Debug, PartialEq)]
+    #[repr(u16)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { width: u16, height: u16 },
+        Far = 300
+    }
+
+    #[derive(BukrsType, Debug, PartialEq)]
+    struct Wrapper(String, #[bukrs(varint)] i32, #[bukrs(zigzag)] i64);
+
+    #[test]
+    fn codec_test() {
+        let mut buf = BytesMut::with_capacity(1024);
+
+        let mut codec = Codec;
+        codec.encode(BukrsPacketData { payload_id: Some(1024), event: Box::new(BukrsReqCreateInvList { inv_id: InvfxId(1024), list: InvList { id: InvfxId(1024), data: vec![] } }) }, &mut buf).unwrap();
+        if let Some(item) = codec.decode(&mut buf).unwrap() {
+            println!("{}", item.event);
+        }
+    }
+
+    #[test]
+    fn test_derive_bukrs_type() {
+        let sample = Sample { count: 300, small: 7, cached: Some("dropped".to_string()), shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { width: 2, height: 3 }, Shape::Far], inner: Wrapper("w".to_string(), -1, -2) };
+        let mut bytes = BytesMut::new();
+        sample.encode(&mut bytes);
+        assert_eq!(&bytes[..4], &[0xac, 0x02, 7, 4]);  // 300 as a varint, `small` as one byte, then the varint length of `shapes`
+        assert_eq!(bytes[bytes.len() - 10..], [0xac, 0x02, 1, b'w', 0xff, 0xff, 0xff, 0xff, 0x0f, 3]);   // Shape::Far, then the wrapper with -2 zigzagged to 3
+
+        let decoded = Sample::<Wrapper>::decode(&mut bytes).unwrap();
+        assert_eq!(decoded, Sample { cached: None, ..sample });
+        assert!(bytes.is_empty());
+
+        let TypeSchema::Struct { fields, .. } = Sample::<Wrapper>::schema() else { panic!("Sample is a struct") };
+        assert_eq!(fields.iter().map(|field| field.name.as_str()).collect::<Vec<&str>>(), ["count", "small", "shapes", "inner"]);    // Skipped fields aren't written
+        assert_eq!(fields[0].ty, TypeSchema::native(BukrsNativeType::VARINT));
+        let TypeSchema::Vector { item } = &fields[2].ty else { panic!("shapes is a Vec") };
+        let TypeSchema::Enum { variants, .. } = item.as_ref() else { panic!("Shape is an enum") };
+        assert_eq!(variants.last().unwrap().discriminant, 300);
+    }
+
+    proptest! {
+        /// Skipped fields are generated as their default, and `small` only as values `as_u8` keeps
+        #[test]
+        fn test_derive_arbitrary(sample: Sample<Wrapper>) {
+            let mut bytes = BytesMut::new();
+            sample.encode(&mut bytes);
+            let decoded = Sample::<Wrapper>::decode(&mut bytes).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", sample));    // Circles may be NaN
+            assert!(bytes.is_empty());
+        }
+    }
+
+    register_packet! {
+        /// Exercises the field types `register_packet!` accepts
+        BukrsTestNested {
+            /// Nested generics
+            players Option<Vec<PlayerId>>;
+            uuid crate::core::player::UUID;
+            pair (u8, String);
+            corners [i32; 2];
+            #[bukrs(varint)]
+            count u32
+        }
+    }
+
+    #[test]
+    fn test_nested_packet_types() {
+        let packet = BukrsTestNested { players: Some(vec![PlayerId(3)]), uuid: crate::core::player::UUID::from_u128(1 << 64 | 2), pair: (4, "x".to_string()), corners: [-1, 1], count: 5 };
+        let mut buf = BytesMut::new();
+        Codec.encode(BukrsPacketData { payload_id: None, event: Box::new(packet.clone()) }, &mut buf).unwrap();
+        let decoded = Codec.decode(&mut buf).unwrap().unwrap();
+        let decoded = cast_packet::<BukrsTestNested>(&decoded.event).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", packet));
+    }
+
+    /// A frame around `body` that the encoder wouldn't produce
+    pub(crate) fn raw_frame(payload_id: u32, name: &str, body: &[u8]) -> BytesMut {
+        let mut payload = BytesMut::new();
+        name.to_string().encode(&mut payload);
+        payload.extend_from_slice(body);
+        let mut frame = BytesMut::new();
+        super::encode_header(&mut frame, &payload, payload_id).unwrap();
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    #[test]
+    fn test_bad_frames_are_skipped() {
+        let mut buf = raw_frame(1, "BukrsReqNoSuchPacket", &[]);
+        buf.extend_from_slice(&raw_frame(2, "BukrsResAPI", &[0, 0, 4]));    // Cut short, the two u32 need 8 bytes
+        Codec.encode(BukrsPacketData { payload_id: Some(3), event: Box::new(BukrsResCreateInvList {  }) }, &mut buf).unwrap();
+
+        let Err(unknown) = Codec.decode(&mut buf) else { panic!("Unknown packets are errors") };
+        assert!(unknown.to_string().contains("BukrsReqNoSuchPacket"));
+        let Err(truncated) = Codec.decode(&mut buf) else { panic!("Truncated packets are errors") };
+        assert!(truncated.to_string().contains("Input ended early"), "{}", truncated);
+        let decoded = Codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(decoded.payload_id, Some(3));
+        assert!(buf.is_empty());
+    }
+
+    /// Refused from the header alone, before the frame is buffered
+    #[test]
+    fn test_large_frames_are_refused() {
+        let mut header = BytesMut::new();
+        bukrs_core::varint::write_varint(MAX_FRAME_LEN as i32 + 1, &mut header);
+        header.extend_from_slice(&[0, 0, 0, 1]);
+
+        let Err(error) = Codec.decode(&mut header.clone()) else { panic!("Large frames are errors") };
+        assert!(error.to_string().contains("larger than"));
+        assert!(LenientCodec.decode(&mut header).is_err(), "The listener can't find the next frame, so the stream ends");
+    }
+
+    #[tokio::test]
+    async fn futures_test() {
+        let future = BukrsFuture::new(1024, arc_mutex!(HashMap::new()));
+        future.set_data(Box::new(BukrsResCreateInvList {  }));
+        assert!((&future).await.is_some());
+
+        let future = BukrsFuture::new(1025, arc_mutex!(HashMap::new()));
+        future.close();
+        assert!((&future).await.is_none());
+    }
+}
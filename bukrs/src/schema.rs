@@ -0,0 +1,134 @@
+//! JSON description of every registered packet, for generating bindings in other languages and checking them against Rust
+
+use bukrs_core::schema::PacketSchema;
+use serde::Serialize;
+
+use crate::net::{SCHEMAS, PROTOCOL_VERSION};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProtocolSchema {
+    pub protocol_version: u32,
+    pub packets: Vec<PacketSchema>   // Sorted by name
+}
+
+pub fn protocol_schema() -> ProtocolSchema {
+    let mut packets = SCHEMAS.lock().unwrap().values().map(|schema| schema()).collect::<Vec<PacketSchema>>();
+    packets.sort_by(|a, b| a.name.cmp(&b.name));
+    ProtocolSchema { protocol_version: PROTOCOL_VERSION, packets }
+}
+
+pub fn protocol_schema_json() -> String {
+    serde_json::to_string_pretty(&protocol_schema()).expect("Schemas are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bukrs_core::{BukrsNativeType, schema::{TypeSchema, FieldSchema}};
+
+    use super::{protocol_schema, protocol_schema_json, PROTOCOL_VERSION};
+
+    fn camel_case(name: &str) -> String {
+        let mut words = name.split('_');
+        let first = words.next().unwrap_or_default().to_string();
+        words.fold(first, |camel, word| camel + &word[..1].to_uppercase() + &word[1..])
+    }
+
+    /// Rust types the plugin names differently
+    const KOTLIN_NAMES: [(&str, &str); 7] = [
+        ("NamespacedKey", "DataKey"), ("EntityType", "EntityTypeKey"), ("Location", "LocationData"), ("Vector", "VectorData"),
+        ("World", "WorldData"), ("Item", "ItemData"), ("InvList", "InvListWrapper")
+    ];
+
+    /// Plugin classes with the same layout as a class of another name
+    const KOTLIN_ALIASES: [(&str, &str); 3] = [("BlockState", "String"), ("BlockStateList", "StringList"), ("WorldList", "WorldDataList")];
+
+    /// The plugin class a field of this layout is declared as. Lists and options get a wrapper class each, e.g. `PlayerIdList`.
+    fn kotlin_type(ty: &TypeSchema) -> String {
+        match ty {
+            TypeSchema::Native { ty } => match ty {
+                BukrsNativeType::U8 | BukrsNativeType::I8 => "Byte",
+                BukrsNativeType::U16 | BukrsNativeType::I16 => "Short",
+                BukrsNativeType::U32 | BukrsNativeType::I32 => "Int",
+                BukrsNativeType::U64 | BukrsNativeType::I64 => "Long",
+                BukrsNativeType::F32 => "Float",
+                BukrsNativeType::F64 => "Double",
+                BukrsNativeType::BOOL => "Boolean",
+                BukrsNativeType::STRING => "String",
+                BukrsNativeType::UUID => "UUID",
+                other => panic!("The plugin has no codec for {:?}", other)
+            }.to_string(),
+            TypeSchema::Vector { item } => kotlin_type(item) + "List",
+            TypeSchema::Option { item } => "Optional".to_string() + &kotlin_type(item),
+            TypeSchema::Struct { name, .. } | TypeSchema::Enum { name, .. } | TypeSchema::Recursive { name } =>
+                KOTLIN_NAMES.iter().find(|(rust, _)| rust == name).map(|(_, kotlin)| kotlin.to_string()).unwrap_or_else(|| name.clone()),
+            TypeSchema::Packet => "Packet".to_string(),
+            other => panic!("The plugin has no codec for {:?}", other)
+        }
+    }
+
+    /// The primitive the plugin may declare instead of a newtype or a fieldless enum, as they share its bytes
+    fn kotlin_primitive(ty: &TypeSchema) -> Option<String> {
+        match ty {
+            TypeSchema::Struct { fields, .. } if fields.len() == 1 && matches!(fields[0].ty, TypeSchema::Native { .. }) => Some(kotlin_type(&fields[0].ty)),
+            TypeSchema::Enum { variants, .. } if variants.iter().all(|variant| variant.fields.is_empty() && (0..128).contains(&variant.discriminant)) => Some("Byte".to_string()),
+            _ => None
+        }
+    }
+
+    /// Field names and types of every `@Packet` class in the plugin
+    fn kotlin_packets() -> BTreeMap<String, Vec<(String, String)>> {
+        let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../bukrs-plugin/src/main/kotlin/me/dolphin2410/bukrs/Packets.kt")).unwrap();
+        source.split("@Packet").skip(1).map(|declaration| {
+            let declaration = declaration.trim_start().trim_start_matches("data ").trim_start_matches("class ");
+            let name_end = declaration.find(|c: char| !c.is_alphanumeric()).unwrap_or(declaration.len());
+            let parameters = declaration[name_end..].strip_prefix('(').map(|rest| &rest[..rest.find(')').unwrap()]).unwrap_or_default();
+            let fields = parameters.split("val ").skip(1).map(|parameter| {
+                let (name, ty) = parameter.split_once(':').unwrap();
+                let ty = ty.split(',').next().unwrap().trim();
+                let ty = KOTLIN_ALIASES.iter().find(|(alias, _)| *alias == ty).map_or(ty, |(_, same)| same);
+                (name.trim().to_string(), ty.to_string())
+            }).collect();
+            (declaration[..name_end].to_string(), fields)
+        }).collect()
+    }
+
+    #[test]
+    fn test_schema_matches_kotlin() {
+        let kotlin = kotlin_packets();
+        let rust = protocol_schema().packets.into_iter()
+            .filter(|packet| !packet.name.starts_with("BukrsTest"))   // Declared by tests, unknown to the plugin
+            .map(|packet| {
+                let declared = kotlin.get(&packet.name);
+                let fields = packet.fields.iter().enumerate().map(|(index, field)| {
+                    let primitive = kotlin_primitive(&field.ty).filter(|primitive| declared.and_then(|fields| fields.get(index)).is_some_and(|(_, ty)| ty == primitive));
+                    (camel_case(&field.name), primitive.unwrap_or_else(|| kotlin_type(&field.ty)))
+                }).collect::<Vec<(String, String)>>();
+                (packet.name, fields)
+            })
+            .collect::<BTreeMap<String, Vec<(String, String)>>>();
+        assert_eq!(rust, kotlin);
+    }
+
+    #[test]
+    fn test_schema_layout() {
+        let schema = protocol_schema();
+        let player_id = TypeSchema::Struct { name: "PlayerId".to_string(), fields: vec![FieldSchema { name: "0".to_string(), ty: TypeSchema::native(BukrsNativeType::U32) }] };
+        let player_by_id = schema.packets.iter().find(|packet| packet.name == "BukrsReqPlayerById").unwrap();
+        assert_eq!(player_by_id.fields[0].ty, player_id);
+
+        let online = schema.packets.iter().find(|packet| packet.name == "BukrsResOnlinePlayers").unwrap();
+        assert_eq!(online.fields[0].ty, TypeSchema::Vector { item: Box::new(player_id) });
+
+        let register = schema.packets.iter().find(|packet| packet.name == "BukrsReqRegisterCommand").unwrap();
+        let TypeSchema::Struct { fields, .. } = &register.fields[0].ty else { panic!("CommandSpec is a struct") };
+        let subcommands = fields.iter().find(|field| field.name == "subcommands").unwrap();
+        assert_eq!(subcommands.ty, TypeSchema::Vector { item: Box::new(TypeSchema::Recursive { name: "CommandSpec".to_string() }) });
+
+        let json = protocol_schema_json();
+        assert!(json.contains(&format!(r#""protocol_version": {}"#, PROTOCOL_VERSION)));
+        assert!(json.contains(r#""kind": "struct""#));
+        assert!(!json.contains(r#""kind": "custom""#), "Every type describes its layout");
+    }
+}